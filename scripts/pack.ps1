@@ -35,6 +35,8 @@ $dataDir = Join-Path $root "huggingface"
 New-Item -ItemType Directory -Force -Path $binDir,$tplDir | Out-Null
 
 Copy-Item $exe -Destination (Join-Path $binDir ([IO.Path]::GetFileName($exe))) -Force
+$cli = $exe -replace [regex]::Escape($binName), "lerobot-servo-cli"
+Copy-Item $cli -Destination (Join-Path $binDir ([IO.Path]::GetFileName($cli))) -Force
 Copy-Item -Recurse templates\* $tplDir -Force
 if (Test-Path "huggingface") { Copy-Item -Recurse "huggingface" $root -Force }
 
@@ -28,6 +28,7 @@ rm -rf "$root"
 mkdir -p "$root/bin" "$root/templates"
 
 cp "$exe" "$root/bin/"
+cp "${exe/$bin_name/lerobot-servo-cli}" "$root/bin/"
 cp -r templates/* "$root/templates/"
 if [[ -d huggingface ]]; then cp -r huggingface "$root/"; fi
 cp -f README.md DEVELOP.md GUIDE.md "$root/" 2>/dev/null || true
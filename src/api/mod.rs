@@ -1,17 +1,30 @@
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, routing::{delete, get, patch, post, put}, Json, Router};
+use axum::{body::Bytes, extract::{DefaultBodyLimit, FromRequest, Path, Query, Request, State}, http::{header, HeaderMap, HeaderValue, StatusCode}, response::{sse::{Event, KeepAlive, Sse}, IntoResponse}, routing::{delete, get, patch, post, put}, Json, Router};
 use serde::{Deserialize, Serialize};
 
-use crate::model::Profile;
-use crate::store::{Kind, Store, StoreError};
+use crate::bus::{BusError, SharedBus};
+use crate::calib::control::{self, GoalResult};
+use crate::calib::eeprom::{self, JointVerification, RegisterWrite};
+use crate::calib::homing::{self, HomingEntry};
+use crate::calib::ids::{self, AssignError, Assignment, Device};
+use crate::calib::live::{self, JointSample, SimulatedArm};
+use crate::calib::readback::{self, MissingJoint};
+use crate::calib::record::{self, Recorder, RecordingSession};
+use crate::model::{self, legacy::{self, Legacy, Loss}, media::{self, Media, Positions}, registry, rules::{self, Issue}, Change, ExtraDiff, JointDiff, JointStatus, Profile};
+use crate::store::archive::{self, Archive, ArchiveFilter, ArchiveFormat, Conflict, ImportPlan};
+use crate::store::{parse_revision_ref, Duplicate, Kind, LintEntry, NameError, Origin, ProfileName, Revision, RevisionMeta, Store, StoreError, TrashEntry};
 
 #[derive(Clone)]
 pub struct AppState {
     pub store: Arc<Store>,
     pub base_url: Option<String>,
     pub read_only: bool,
+    /// Servo bus, when hardware (or `SERVO_PORT=sim`) is configured.
+    pub bus: Option<SharedBus>,
+    pub recorder: Arc<Recorder>,
 }
 
 pub fn router(state: AppState) -> Router {
@@ -23,6 +36,40 @@ pub fn router(state: AppState) -> Router {
         .route("/api/profiles/:kind/:profile", patch(patch_profile))
         .route("/api/profiles/:kind", post(create_profile))
         .route("/api/profiles/:kind/:profile", delete(delete_profile))
+        .route("/api/profiles/:kind/:profile/copy", post(copy_profile))
+        .route("/api/profiles/:kind/:profile/rename", post(rename_profile))
+        .route("/api/profiles/:kind/:profile/import", post(import_profile))
+        .route("/api/profiles/:kind/:profile/legacy", get(export_legacy))
+        .route("/api/archive", get(export_archive))
+        .route("/api/archive", post(import_archive).layer(DefaultBodyLimit::max(archive::MAX_TOTAL_BYTES as usize)))
+        .route("/api/trash", get(list_trash))
+        .route("/api/trash", delete(empty_trash))
+        .route("/api/trash/:id", get(get_trashed))
+        .route("/api/trash/:id", delete(purge_trashed))
+        .route("/api/trash/:id/restore", post(restore_trashed))
+        .route("/api/history/:kind/:profile", get(list_history))
+        .route("/api/history/:kind/:profile/diff", get(diff_revisions))
+        .route("/api/history/:kind/:profile/:rev", get(get_revision))
+        .route("/api/history/:kind/:profile/:rev/rollback", post(rollback_revision))
+        .route("/api/diff", get(diff_profiles_query))
+        .route("/api/diff", post(diff_profiles))
+        .route("/api/validate", post(validate_profile))
+        .route("/api/lint", get(lint_profiles))
+        .route("/api/robot-types", get(list_robot_types))
+        .route("/api/robot-types/:name", get(get_robot_type))
+        .route("/api/recordings/:kind/:profile", get(recording_status))
+        .route("/api/recordings/:kind/:profile/start", post(start_recording))
+        .route("/api/recordings/:kind/:profile/stop", post(stop_recording))
+        .route("/api/homing/:kind/:profile/preview", post(homing_preview))
+        .route("/api/homing/:kind/:profile/apply", post(homing_apply))
+        .route("/api/hardware/:kind/:profile/apply", post(hardware_apply))
+        .route("/api/hardware/:kind/:profile/readback", post(hardware_readback))
+        .route("/api/stream/:kind/:profile", get(stream_positions))
+        .route("/api/control/:kind/:profile/torque", post(set_torque))
+        .route("/api/control/:kind/:profile/goal", post(set_goal))
+        .route("/api/torque/off", post(torque_off_all))
+        .route("/api/ids/scan", post(scan_ids))
+        .route("/api/ids/:kind/:profile/assign", post(assign_id))
         .with_state(state)
 }
 
@@ -66,14 +113,88 @@ impl ApiError {
     fn from_store(e: StoreError) -> Self {
         match e {
             StoreError::NotFound(msg) => Self { status: StatusCode::NOT_FOUND, message: msg, details: None },
+            StoreError::Exists(msg) => Self { status: StatusCode::CONFLICT, message: format!("already exists: {}", msg), details: None },
             StoreError::Validation(msg) => Self { status: StatusCode::BAD_REQUEST, message: msg, details: None },
+            StoreError::Archive(msg) => Self { status: StatusCode::BAD_REQUEST, message: format!("invalid archive: {}", msg), details: None },
+            StoreError::InvalidName { name, reason } => Self::invalid_name(&name, &reason),
+            StoreError::Invalid(ref report) => Self { status: StatusCode::BAD_REQUEST, message: e.to_string(), details: Some(serde_json::json!({"issues": report.issues})) },
             StoreError::Json(err) => Self { status: StatusCode::BAD_REQUEST, message: "invalid json".into(), details: Some(serde_json::json!({"error": err.to_string()})) },
+            StoreError::Ambiguous { name, candidates } => Self {
+                status: StatusCode::CONFLICT,
+                message: format!("`{}` matches several profiles; use <robot_type>/<id>", name),
+                details: Some(serde_json::json!({"candidates": candidates})),
+            },
+            StoreError::VersionMismatch { expected, actual } => Self {
+                status: StatusCode::PRECONDITION_FAILED,
+                message: "profile was modified; reload and retry".into(),
+                details: Some(serde_json::json!({"if_match": expected, "current": actual})),
+            },
+            StoreError::Unchanged { .. } => Self { status: StatusCode::CONFLICT, message: e.to_string(), details: None },
             StoreError::Io(err) => Self { status: StatusCode::INTERNAL_SERVER_ERROR, message: "io error".into(), details: Some(serde_json::json!({"error": err.to_string()})) },
         }
     }
+    fn from_bus(e: BusError) -> Self {
+        Self { status: StatusCode::BAD_GATEWAY, message: "servo bus error".into(), details: Some(serde_json::json!({"error": e.to_string()})) }
+    }
+    fn from_assign(e: AssignError) -> Self {
+        match e {
+            AssignError::Bus(e) => Self::from_bus(e),
+            AssignError::UnknownJoint(_) => Self { status: StatusCode::NOT_FOUND, message: e.to_string(), details: None },
+            AssignError::InvalidId { .. } => Self { status: StatusCode::BAD_REQUEST, message: e.to_string(), details: None },
+            AssignError::NoDevice(_) | AssignError::Multiple(_) => Self { status: StatusCode::CONFLICT, message: e.to_string(), details: None },
+            AssignError::NotVerified { .. } => Self { status: StatusCode::BAD_GATEWAY, message: e.to_string(), details: None },
+        }
+    }
+    fn invalid_name(name: &str, reason: &NameError) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, message: "invalid profile name".into(), details: Some(serde_json::json!({"name": name, "reason": reason.to_string()})) }
+    }
     fn bad_request(msg: &str, details: Option<serde_json::Value>) -> Self {
         Self { status: StatusCode::BAD_REQUEST, message: msg.into(), details }
     }
+    fn not_found(msg: &str) -> Self {
+        Self { status: StatusCode::NOT_FOUND, message: msg.into(), details: None }
+    }
+    fn conflict(msg: &str) -> Self {
+        Self { status: StatusCode::CONFLICT, message: msg.into(), details: None }
+    }
+    /// Adds the row and column of each rule issue's joint and field in the request body.
+    fn locate(mut self, positions: &Positions) -> Self {
+        let issues = self.details.as_mut().and_then(|d| d.get_mut("issues")).and_then(|i| i.as_array_mut());
+        for issue in issues.into_iter().flatten() {
+            if let Some(at) = issue["joint"].as_str().and_then(|j| positions.find(j, issue["field"].as_str())) {
+                issue["row"] = at.row.into();
+                issue["column"] = at.column.into();
+            }
+        }
+        self
+    }
+}
+
+fn profile_name(name: &str) -> Result<ProfileName, ApiError> {
+    ProfileName::parse(name).map_err(|e| ApiError::invalid_name(name, &e))
+}
+
+fn require_bus(state: &AppState) -> Result<SharedBus, ApiError> {
+    state.bus.clone().ok_or_else(|| ApiError {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        message: "no servo bus configured".into(),
+        details: Some(serde_json::json!({"hint": "set SERVO_PORT to a serial device or `sim`"})),
+    })
+}
+
+/// Attribution for a write: `X-Author` and `X-Change-Summary` headers.
+fn origin(source: &str, headers: &HeaderMap) -> Origin {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    Origin::new(source).author(header("x-author")).summary(header("x-change-summary"))
+}
+
+fn if_match(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok())
+}
+
+/// `ETag` header for a store version.
+fn etag(version: &str) -> [(header::HeaderName, HeaderValue); 1] {
+    [(header::ETAG, HeaderValue::from_str(&format!("\"{}\"", version)).expect("hex is a valid header"))]
 }
 
 impl IntoResponse for ApiError {
@@ -89,7 +210,12 @@ impl IntoResponse for ApiError {
 struct ListQuery { kind: String }
 
 #[derive(Serialize)]
-struct ListResponse { items: Vec<String> }
+struct ListResponse {
+    items: Vec<String>,
+    /// Ids shared by profiles of different robot types.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    duplicates: Vec<Duplicate>,
+}
 
 async fn list_profiles(State(state): State<AppState>, Query(q): Query<ListQuery>) -> Result<Json<ListResponse>, ApiError> {
     let KindParam(kind) = KindParam::from_str(&q.kind)?;
@@ -100,25 +226,89 @@ async fn list_profiles(State(state): State<AppState>, Query(q): Query<ListQuery>
         .into_iter()
         .map(|m| m.name)
         .collect();
-    Ok(Json(ListResponse { items }))
+    let duplicates = state.store.duplicates(kind).map_err(ApiError::from_store)?;
+    Ok(Json(ListResponse { items, duplicates }))
 }
 
 #[derive(Serialize)]
 struct ProfileResponse(Profile);
 
-async fn get_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<Json<ProfileResponse>, ApiError> {
+#[derive(Deserialize, Default)]
+struct FormatQuery {
+    /// `json`, `csv` or `yaml`; overrides `Accept`.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// The profile as JSON, or as CSV / YAML when asked for by `?format=` or `Accept`.
+async fn get_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<FormatQuery>, headers: HeaderMap) -> Result<axum::response::Response, ApiError> {
     let KindParam(kind) = KindParam::from_str(&kind)?;
-    let p = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
-    Ok(Json(ProfileResponse(p)))
+    let media = match q.format.as_deref() {
+        Some(f) => Media::from_str(f).map_err(|e| ApiError::bad_request(&e, None))?,
+        None => headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).and_then(Media::from_accept).unwrap_or_default(),
+    };
+    let (p, version) = state.store.read_profile_versioned(kind, &profile).map_err(ApiError::from_store)?;
+    let body = match media {
+        Media::Json => return Ok((etag(&version), Json(ProfileResponse(p))).into_response()),
+        Media::Csv => media::to_csv(&p),
+        Media::Yaml => media::to_yaml(&p),
+    };
+    let id = profile.rsplit('/').next().unwrap_or(&profile);
+    let disposition = format!("attachment; filename=\"{}.{}\"", id, media.extension());
+    Ok((etag(&version), [(header::CONTENT_TYPE, media.mime().to_string()), (header::CONTENT_DISPOSITION, disposition)], body).into_response())
+}
+
+/// A profile request body: JSON, or CSV / YAML by `Content-Type`.
+struct ProfileBody {
+    profile: Profile,
+    media: Media,
+    /// Where joints and fields sat in a CSV or YAML body.
+    positions: Positions,
+}
+
+impl ProfileBody {
+    fn media(req: &Request) -> Media {
+        req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).and_then(Media::from_mime).unwrap_or_default()
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for ProfileBody {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let media = Self::media(&req);
+        let parse = match media {
+            Media::Json => {
+                let Json(profile) = Json::<Profile>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+                return Ok(Self { profile, media, positions: Positions::default() });
+            }
+            Media::Csv => media::from_csv,
+            Media::Yaml => media::from_yaml,
+        };
+        let bytes = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        let (profile, positions) = parse(&bytes).map_err(|e| ApiError::bad_request(&format!("invalid {}", media.extension()), Some(serde_json::json!(e))).into_response())?;
+        Ok(Self { profile, media, positions })
+    }
+}
+
+/// 204 carrying the version a write produced.
+fn written(version: &str) -> impl IntoResponse + use<> {
+    (StatusCode::NO_CONTENT, etag(version))
 }
 
-async fn put_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Json(body): Json<Profile>) -> Result<StatusCode, ApiError> {
+async fn put_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap, body: ProfileBody) -> Result<impl IntoResponse, ApiError> {
     let KindParam(kind) = KindParam::from_str(&kind)?;
-    state
+    let ProfileBody { profile: mut p, media, positions } = body;
+    // a sheet has no room for top-level fields; keep the stored ones
+    if media == Media::Csv && let Ok(old) = state.store.read_profile(kind, &profile) {
+        p.1 = old.1;
+    }
+    let (_, version) = state
         .store
-        .write_profile(kind, &profile, &body, true)
-        .map_err(ApiError::from_store)?;
-    Ok(StatusCode::NO_CONTENT)
+        .write_profile_if(kind, &profile, &p, true, origin("api", &headers), if_match(&headers))
+        .map_err(|e| ApiError::from_store(e).locate(&positions))?;
+    Ok(written(&version))
 }
 
 #[derive(Deserialize)]
@@ -132,9 +322,9 @@ struct PatchJoint {
 
 type PatchBody = std::collections::HashMap<String, PatchJoint>;
 
-async fn patch_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Json(patch): Json<PatchBody>) -> Result<StatusCode, ApiError> {
+async fn patch_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap, Json(patch): Json<PatchBody>) -> Result<impl IntoResponse, ApiError> {
     let KindParam(kind) = KindParam::from_str(&kind)?;
-    let mut current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let (mut current, version) = state.store.read_profile_versioned(kind, &profile).map_err(ApiError::from_store)?;
     for (name, pj) in patch {
         if let Some(mut j) = current.0.get(&name).cloned() {
             if let Some(v) = pj.id { j.id = v; }
@@ -142,14 +332,18 @@ async fn patch_profile(State(state): State<AppState>, Path((kind, profile)): Pat
             if let Some(v) = pj.homing_offset { j.homing_offset = v; }
             if let Some(v) = pj.range_min { j.range_min = v; }
             if let Some(v) = pj.range_max { j.range_max = v; }
-            j.validate().map_err(|e| ApiError::bad_request("invalid joint", Some(serde_json::json!({"joint": name, "error": e}))))?;
             current.0.insert(name, j);
         } else {
             return Err(ApiError::bad_request("unknown joint", Some(serde_json::json!(name))));
         }
     }
-    state.store.write_profile(kind, &profile, &current, true).map_err(ApiError::from_store)?;
-    Ok(StatusCode::NO_CONTENT)
+    // without If-Match, still refuse to clobber a write that landed since the read
+    let expected = if_match(&headers).unwrap_or(&version);
+    let (_, version) = state
+        .store
+        .write_profile_if(kind, &profile, &current, true, origin("api", &headers), Some(expected))
+        .map_err(ApiError::from_store)?;
+    Ok(written(&version))
 }
 
 #[derive(Deserialize)]
@@ -157,17 +351,810 @@ struct CreateBody {
     name: String,
     #[serde(default)]
     profile: Option<Profile>,
+    /// Robot type to start from instead of `profile`; a bare name is placed
+    /// in that type's directory.
+    #[serde(default)]
+    template: Option<String>,
+}
+
+/// Where a created, copied or renamed profile ended up.
+#[derive(Serialize)]
+struct Placed {
+    kind: Kind,
+    name: String,
+}
+
+/// A JSON `CreateBody`, or a bare CSV / YAML profile named by `?name=`.
+enum CreateRequest {
+    Json(CreateBody),
+    Text(ProfileBody),
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for CreateRequest {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match ProfileBody::media(&req) {
+            Media::Json => Ok(Self::Json(Json::<CreateBody>::from_request(req, state).await.map_err(IntoResponse::into_response)?.0)),
+            _ => Ok(Self::Text(ProfileBody::from_request(req, state).await?)),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct CreateQuery {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+async fn create_profile(State(state): State<AppState>, Path(kind): Path<String>, Query(q): Query<CreateQuery>, headers: HeaderMap, req: CreateRequest) -> Result<(StatusCode, Json<Placed>), ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let (body, positions) = match req {
+        CreateRequest::Json(body) => (body, Positions::default()),
+        CreateRequest::Text(text) => {
+            let name = q.name.ok_or_else(|| ApiError::bad_request("CSV and YAML bodies need the profile name in ?name=", None))?;
+            (CreateBody { name, profile: Some(text.profile), template: None }, text.positions)
+        }
+    };
+    let name = profile_name(&body.name)?;
+    let (name, profile) = match (body.template.as_deref(), body.profile) {
+        (Some(_), Some(_)) => return Err(ApiError::bad_request("give either profile or template, not both", None)),
+        (Some(t), None) => {
+            let ty = registry::lookup(t).filter(|ty| ty.kind == kind).ok_or_else(|| ApiError::bad_request("unknown robot type", Some(serde_json::json!({"template": t, "kind": kind}))))?;
+            (name.with_type(ty.name).map_err(|e| ApiError::invalid_name(&body.name, &e))?, ty.template())
+        }
+        (None, profile) => (name, profile.unwrap_or_default()),
+    };
+    state.store.write_profile_from(kind, name.as_str(), &profile, false, origin("api", &headers)).map_err(|e| ApiError::from_store(e).locate(&positions))?;
+    let name = state.store.canonical_name(kind, name.as_str()).map_err(ApiError::from_store)?;
+    Ok((StatusCode::CREATED, Json(Placed { kind, name })))
+}
+
+#[derive(Deserialize)]
+struct MoveBody {
+    /// Target kind; defaults to the source kind.
+    #[serde(default)]
+    kind: Option<String>,
+    to: String,
+}
+
+impl MoveBody {
+    /// Target kind and name, checked before touching the store.
+    fn target(&self, source: Kind) -> Result<(Kind, ProfileName), ApiError> {
+        let kind = match self.kind.as_deref() {
+            Some(k) => KindParam::from_str(k)?.0,
+            None => source,
+        };
+        Ok((kind, profile_name(&self.to)?))
+    }
+}
+
+async fn copy_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap, Json(body): Json<MoveBody>) -> Result<(StatusCode, Json<Placed>), ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let (to_kind, to) = body.target(kind)?;
+    let name = state.store.copy_profile(kind, &profile, to_kind, to.as_str(), origin("api", &headers)).map_err(ApiError::from_store)?;
+    Ok((StatusCode::CREATED, Json(Placed { kind: to_kind, name })))
+}
+
+async fn rename_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap, Json(body): Json<MoveBody>) -> Result<Json<Placed>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let (to_kind, to) = body.target(kind)?;
+    let name = state.store.rename_profile(kind, &profile, to_kind, to.as_str(), if_match(&headers), origin("api", &headers)).map_err(ApiError::from_store)?;
+    Ok(Json(Placed { kind: to_kind, name }))
+}
+
+#[derive(Deserialize, Default)]
+struct ImportQuery {
+    /// Robot type giving servo ids and limits; a bare name is placed in its directory.
+    #[serde(default)]
+    robot_type: Option<String>,
+    #[serde(default)]
+    overwrite: bool,
+    /// Convert and check only.
+    #[serde(default)]
+    dry_run: bool,
 }
 
-async fn create_profile(State(state): State<AppState>, Path(kind): Path<String>, Json(body): Json<CreateBody>) -> Result<StatusCode, ApiError> {
+#[derive(Serialize)]
+struct Imported {
+    kind: Kind,
+    name: String,
+    #[serde(flatten)]
+    import: legacy::Import,
+    /// Rule findings for the converted profile.
+    issues: Vec<Issue>,
+}
+
+/// Saves a calibration file in either layout; legacy files are converted
+/// and every lossy step is listed.
+async fn import_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<ImportQuery>, headers: HeaderMap, Json(body): Json<serde_json::Value>) -> Result<(StatusCode, Json<Imported>), ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let mut name = profile_name(&profile)?;
+    let ty = match q.robot_type.as_deref() {
+        Some(t) => {
+            let ty = registry::lookup(t).filter(|ty| ty.kind == kind).ok_or_else(|| ApiError::bad_request("unknown robot type", Some(serde_json::json!({"robot_type": t, "kind": kind}))))?;
+            name = name.with_type(ty.name).map_err(|e| ApiError::invalid_name(&profile, &e))?;
+            Some(ty)
+        }
+        None => registry::type_of(kind, name.as_str()),
+    };
+    let import = legacy::import(body, ty).map_err(|e| ApiError::bad_request("invalid calibration file", Some(serde_json::json!(e.to_string()))))?;
+    let issues = rules::check(&import.profile, ty).issues;
+    if q.dry_run {
+        return Ok((StatusCode::OK, Json(Imported { kind, name: name.to_string(), import, issues })));
+    }
+    if !q.overwrite && state.store.exists(kind, name.as_str()) {
+        return Err(ApiError::conflict("profile already exists; pass overwrite=true to replace it"));
+    }
+    let mut origin = origin("api", &headers);
+    if origin.summary.is_none() && import.layout == legacy::Layout::Legacy {
+        origin.summary = Some(format!("imported from legacy layout ({} lossy)", import.losses.len()));
+    }
+    state.store.write_profile_from(kind, name.as_str(), &import.profile, true, origin).map_err(ApiError::from_store)?;
+    let name = state.store.canonical_name(kind, name.as_str()).map_err(ApiError::from_store)?;
+    Ok((StatusCode::CREATED, Json(Imported { kind, name, import, issues })))
+}
+
+#[derive(Serialize)]
+struct LegacyExport {
+    legacy: Legacy,
+    losses: Vec<Loss>,
+}
+
+/// The profile in the legacy parallel-array layout, for old checkouts.
+async fn export_legacy(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<Json<LegacyExport>, ApiError> {
     let KindParam(kind) = KindParam::from_str(&kind)?;
-    let profile = body.profile.unwrap_or_else(|| Profile(Default::default()));
-    state.store.write_profile(kind, &body.name, &profile, false).map_err(ApiError::from_store)?;
-    Ok(StatusCode::CREATED)
+    let p = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let (legacy, losses) = Legacy::from_profile(&p);
+    Ok(Json(LegacyExport { legacy, losses }))
+}
+
+#[derive(Deserialize, Default)]
+struct ExportArchiveQuery {
+    #[serde(default)]
+    format: ArchiveFormat,
+    #[serde(default)]
+    kind: Option<Kind>,
+    #[serde(default)]
+    robot_type: Option<String>,
 }
 
-async fn delete_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<StatusCode, ApiError> {
+/// Every profile (or those of one kind / robot type) as a tar.gz or zip with a manifest.
+async fn export_archive(State(state): State<AppState>, Query(q): Query<ExportArchiveQuery>) -> Result<impl IntoResponse, ApiError> {
+    let filter = ArchiveFilter { kind: q.kind, robot_type: q.robot_type };
+    let bytes = state.store.export_archive(&filter, q.format).map_err(ApiError::from_store)?;
+    let disposition = format!("attachment; filename=\"calibration.{}\"", q.format.extension());
+    Ok(([(header::CONTENT_TYPE, q.format.content_type().to_string()), (header::CONTENT_DISPOSITION, disposition)], bytes))
+}
+
+#[derive(Deserialize, Default)]
+struct ImportArchiveQuery {
+    #[serde(default)]
+    conflict: Conflict,
+    /// Only return the plan.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Unpacks an uploaded archive into the store; see `Store::import_archive`.
+async fn import_archive(State(state): State<AppState>, Query(q): Query<ImportArchiveQuery>, headers: HeaderMap, body: Bytes) -> Result<Json<ImportPlan>, ApiError> {
+    let archive = Archive::read(&body).map_err(ApiError::from_store)?;
+    let plan = if q.dry_run {
+        state.store.preview_archive(&archive, q.conflict)
+    } else {
+        state.store.import_archive(&archive, q.conflict, origin("api", &headers))
+    };
+    plan.map(Json).map_err(ApiError::from_store)
+}
+
+async fn delete_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap) -> Result<StatusCode, ApiError> {
     let KindParam(kind) = KindParam::from_str(&kind)?;
-    state.store.delete_profile(kind, &profile).map_err(ApiError::from_store)?;
+    state.store.delete_profile_if(kind, &profile, if_match(&headers)).map_err(ApiError::from_store)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ---- validation ----
+
+#[derive(Deserialize)]
+struct ValidateBody {
+    profile: Profile,
+    /// Registry type to check the joint schema against.
+    #[serde(default)]
+    robot_type: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ValidateResponse {
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    issues: Vec<Issue>,
+}
+
+async fn validate_profile(Json(body): Json<ValidateBody>) -> Result<Json<ValidateResponse>, ApiError> {
+    let ty = match body.robot_type.as_deref() {
+        Some(name) => Some(registry::lookup(name).ok_or_else(|| ApiError::bad_request("unknown robot type", Some(serde_json::json!({"robot_type": name}))))?),
+        None => None,
+    };
+    let report = rules::check(&body.profile, ty);
+    let (errors, warnings) = (report.errors().count(), report.warnings().count());
+    Ok(Json(ValidateResponse { valid: errors == 0, errors, warnings, issues: report.issues }))
+}
+
+#[derive(Deserialize)]
+struct LintQuery {
+    kind: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LintResponse {
+    errors: usize,
+    warnings: usize,
+    items: Vec<LintEntry>,
+}
+
+async fn lint_profiles(State(state): State<AppState>, Query(q): Query<LintQuery>) -> Result<Json<LintResponse>, ApiError> {
+    let kinds = match q.kind.as_deref() {
+        Some(k) => vec![KindParam::from_str(k)?.0],
+        None => vec![Kind::Robots, Kind::Teleoperators],
+    };
+    let items = state.store.lint(&kinds).map_err(ApiError::from_store)?;
+    let errors = items.iter().map(LintEntry::errors).sum();
+    let warnings = items.iter().map(LintEntry::warnings).sum();
+    Ok(Json(LintResponse { errors, warnings, items }))
+}
+
+// ---- robot types ----
+
+#[derive(Serialize)]
+struct RobotTypesResponse {
+    items: &'static [registry::RobotType],
+}
+
+async fn list_robot_types() -> Json<RobotTypesResponse> {
+    Json(RobotTypesResponse { items: registry::TYPES })
+}
+
+async fn get_robot_type(Path(name): Path<String>) -> Result<Json<&'static registry::RobotType>, ApiError> {
+    registry::lookup(&name).map(Json).ok_or_else(|| ApiError::not_found("unknown robot type"))
+}
+
+// ---- trash ----
+
+#[derive(Serialize)]
+struct TrashResponse {
+    items: Vec<TrashEntry>,
+}
+
+async fn list_trash(State(state): State<AppState>) -> Result<Json<TrashResponse>, ApiError> {
+    state.store.trash().map(|items| Json(TrashResponse { items })).map_err(ApiError::from_store)
+}
+
+#[derive(Serialize)]
+struct TrashedProfile {
+    #[serde(flatten)]
+    entry: TrashEntry,
+    profile: Profile,
+}
+
+async fn get_trashed(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<TrashedProfile>, ApiError> {
+    let profile = state.store.trashed_profile(&id).map_err(ApiError::from_store)?;
+    let entry = state.store.trash().map_err(ApiError::from_store)?.into_iter().find(|e| e.id == id).ok_or_else(|| ApiError::not_found("no such trash entry"))?;
+    Ok(Json(TrashedProfile { entry, profile }))
+}
+
+#[derive(Deserialize, Default)]
+struct RestoreBody {
+    /// Restore under another kind or name instead of the original one.
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+}
+
+async fn restore_trashed(State(state): State<AppState>, Path(id): Path<String>, body: Option<Json<RestoreBody>>) -> Result<Json<Placed>, ApiError> {
+    let body = body.map(|b| b.0).unwrap_or_default();
+    let target = match (body.kind.as_deref(), body.to.as_deref()) {
+        (None, None) => None,
+        (kind, to) => {
+            let entry = state.store.trash().map_err(ApiError::from_store)?.into_iter().find(|e| e.id == id).ok_or_else(|| ApiError::not_found("no such trash entry"))?;
+            let kind = match kind {
+                Some(k) => KindParam::from_str(k)?.0,
+                None => entry.kind,
+            };
+            Some((kind, profile_name(to.unwrap_or(&entry.name))?))
+        }
+    };
+    let (kind, name) = state.store.restore(&id, target.as_ref().map(|(k, n)| (*k, n.as_str()))).map_err(ApiError::from_store)?;
+    Ok(Json(Placed { kind, name }))
+}
+
+async fn purge_trashed(State(state): State<AppState>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    state.store.purge(&id).map_err(ApiError::from_store)?;
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[derive(Deserialize)]
+struct EmptyTrashQuery {
+    /// Only purge entries older than this.
+    older_than_days: Option<u64>,
+    /// Purge everything; required when `older_than_days` is omitted.
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Serialize)]
+struct PurgeResponse {
+    purged: Vec<TrashEntry>,
+}
+
+async fn empty_trash(State(state): State<AppState>, Query(q): Query<EmptyTrashQuery>) -> Result<Json<PurgeResponse>, ApiError> {
+    let days = match (q.older_than_days, q.all) {
+        (Some(days), false) => days,
+        (None, true) => 0,
+        _ => return Err(ApiError::bad_request("pass either older_than_days=N or all=true", None)),
+    };
+    let age = Duration::from_secs(days * 86_400);
+    state.store.purge_older_than(age).map(|purged| Json(PurgeResponse { purged })).map_err(ApiError::from_store)
+}
+
+// ---- history ----
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    items: Vec<RevisionMeta>,
+}
+
+async fn list_history(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<Json<HistoryResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let items = state.store.history(kind, &profile).map_err(ApiError::from_store)?;
+    Ok(Json(HistoryResponse { items }))
+}
+
+async fn get_revision(State(state): State<AppState>, Path((kind, profile, rev)): Path<(String, String, u64)>) -> Result<Json<Revision>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    state.store.revision(kind, &profile, rev).map(Json).map_err(ApiError::from_store)
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: u64,
+    /// Defaults to the latest revision.
+    to: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DiffResponse {
+    from: u64,
+    to: u64,
+    changes: Vec<Change>,
+}
+
+async fn diff_revisions(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<DiffQuery>) -> Result<Json<DiffResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let to = match q.to {
+        Some(to) => to,
+        None => state
+            .store
+            .history(kind, &profile)
+            .map_err(ApiError::from_store)?
+            .last()
+            .map(|m| m.revision)
+            .ok_or_else(|| ApiError::not_found("profile has no revisions"))?,
+    };
+    let a = state.store.revision(kind, &profile, q.from).map_err(ApiError::from_store)?;
+    let b = state.store.revision(kind, &profile, to).map_err(ApiError::from_store)?;
+    Ok(Json(DiffResponse { from: q.from, to, changes: model::diff(&a.profile, &b.profile) }))
+}
+
+async fn rollback_revision(State(state): State<AppState>, Path((kind, profile, rev)): Path<(String, String, u64)>, headers: HeaderMap) -> Result<Json<RevisionMeta>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    state.store.rollback(kind, &profile, rev, origin("api", &headers)).map(Json).map_err(ApiError::from_store)
+}
+
+// ---- profile diff ----
+
+/// One side of a diff: `<kind>/<name>[@<revision>]` or an uploaded profile.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiffSide {
+    Ref(String),
+    Profile(Profile),
+}
+
+#[derive(Deserialize)]
+struct DiffBody {
+    left: DiffSide,
+    right: DiffSide,
+}
+
+#[derive(Deserialize)]
+struct DiffRefs {
+    left: String,
+    right: String,
+}
+
+#[derive(Serialize)]
+struct ProfileDiffResponse {
+    left: String,
+    right: String,
+    changed: usize,
+    added: usize,
+    removed: usize,
+    joints: Vec<JointDiff>,
+    /// Top-level fields that differ.
+    extra: Vec<ExtraDiff>,
+}
+
+fn resolve_side(state: &AppState, side: DiffSide) -> Result<(String, Profile), ApiError> {
+    match side {
+        DiffSide::Profile(p) => Ok(("upload".into(), p)),
+        DiffSide::Ref(r) => {
+            let (kind, name, rev) = parse_revision_ref(&r)
+                .ok_or_else(|| ApiError::bad_request("invalid profile reference (expected <kind>/<name>[@<revision>])", Some(serde_json::json!({"ref": r}))))?;
+            let profile = state.store.read_at(kind, &name, rev).map_err(ApiError::from_store)?;
+            Ok((r, profile))
+        }
+    }
+}
+
+fn profile_diff(state: &AppState, left: DiffSide, right: DiffSide) -> Result<Json<ProfileDiffResponse>, ApiError> {
+    let (left, a) = resolve_side(state, left)?;
+    let (right, b) = resolve_side(state, right)?;
+    let joints = model::compare(&a, &b);
+    let count = |s: JointStatus| joints.iter().filter(|j| j.status == s).count();
+    let (changed, added, removed) = (count(JointStatus::Changed), count(JointStatus::Added), count(JointStatus::Removed));
+    let extra = model::compare_extra(&a.1, &b.1);
+    Ok(Json(ProfileDiffResponse { left, right, changed, added, removed, joints, extra }))
+}
+
+async fn diff_profiles_query(State(state): State<AppState>, Query(q): Query<DiffRefs>) -> Result<Json<ProfileDiffResponse>, ApiError> {
+    profile_diff(&state, DiffSide::Ref(q.left), DiffSide::Ref(q.right))
+}
+
+async fn diff_profiles(State(state): State<AppState>, Json(body): Json<DiffBody>) -> Result<Json<ProfileDiffResponse>, ApiError> {
+    profile_diff(&state, body.left, body.right)
+}
+
+// ---- range recording ----
+
+#[derive(Deserialize, Default)]
+struct StartRecordingBody {
+    #[serde(default)]
+    interval_ms: Option<u64>,
+}
+
+async fn start_recording(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, body: Option<Json<StartRecordingBody>>) -> Result<StatusCode, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let bus = require_bus(&state)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let interval = Duration::from_millis(body.and_then(|b| b.0.interval_ms).unwrap_or(50).max(5));
+    let key = record::key(&state.store, kind, &profile).map_err(ApiError::from_store)?;
+    if state.recorder.snapshot(&key).is_some() {
+        return Err(ApiError::conflict("recording already running"));
+    }
+    // take the first sample up front so a missing servo fails the request
+    let mut session = RecordingSession::new(&current);
+    let session = crate::bus::run(&bus, move |b| session.sample(b).map(|_| session)).await.map_err(ApiError::from_bus)?;
+    if !state.recorder.start(key, session, bus, interval) {
+        return Err(ApiError::conflict("recording already running"));
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn recording_status(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<Json<RecordingSession>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let key = record::key(&state.store, kind, &profile).map_err(ApiError::from_store)?;
+    state
+        .recorder
+        .snapshot(&key)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("no recording running"))
+}
+
+#[derive(Deserialize, Default)]
+struct StopRecordingBody {
+    #[serde(default)]
+    commit: bool,
+}
+
+#[derive(Serialize)]
+struct StopRecordingResponse {
+    session: RecordingSession,
+    committed: Vec<String>,
+}
+
+async fn stop_recording(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, body: Option<Json<StopRecordingBody>>) -> Result<Json<StopRecordingResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let key = record::key(&state.store, kind, &profile).map_err(ApiError::from_store)?;
+    let session = state
+        .recorder
+        .stop(&key)
+        .await
+        .ok_or_else(|| ApiError::not_found("no recording running"))?;
+    let committed = if body.is_some_and(|b| b.0.commit) {
+        record::commit(&state.store, kind, &profile, &session).map_err(ApiError::from_store)?
+    } else {
+        Vec::new()
+    };
+    Ok(Json(StopRecordingResponse { session, committed }))
+}
+
+// ---- homing ----
+
+#[derive(Serialize)]
+struct HomingResponse {
+    joints: Vec<HomingEntry>,
+    /// Profile version the offsets were computed against; send it back as
+    /// `If-Match` when applying.
+    version: String,
+}
+
+async fn read_homing(state: &AppState, kind: Kind, profile: &str) -> Result<HomingResponse, ApiError> {
+    let bus = require_bus(state)?;
+    let (current, version) = state.store.read_profile_versioned(kind, profile).map_err(ApiError::from_store)?;
+    let joints = crate::bus::run(&bus, move |b| homing::preview(b, &current)).await.map_err(ApiError::from_bus)?;
+    Ok(HomingResponse { joints, version })
+}
+
+async fn homing_preview(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Result<Json<HomingResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    read_homing(&state, kind, &profile).await.map(Json)
+}
+
+#[derive(Deserialize)]
+struct HomingApplyBody {
+    /// The previewed offsets, as the preview returned them.
+    joints: Vec<HomingEntry>,
+    /// Version from the preview; `If-Match` takes precedence.
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Stores the previewed offsets; the response lists exactly what was
+/// written. The pose is read again first and must still match the preview
+/// (409 otherwise), and the profile must still be at the previewed version
+/// (412 otherwise).
+async fn homing_apply(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, headers: HeaderMap, Json(body): Json<HomingApplyBody>) -> Result<Json<HomingResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = read_homing(&state, kind, &profile).await?;
+    let drift = homing::drift(&body.joints, &current.joints, homing::POSE_TOLERANCE);
+    if !drift.is_empty() {
+        return Err(ApiError {
+            status: StatusCode::CONFLICT,
+            message: "the arm moved since the preview; preview again".into(),
+            details: Some(serde_json::json!({"drift": drift, "tolerance": homing::POSE_TOLERANCE})),
+        });
+    }
+    let expected = if_match(&headers).or(body.version.as_deref()).unwrap_or(&current.version);
+    let (_, version) = homing::apply(&state.store, kind, &profile, &body.joints, expected).map_err(ApiError::from_store)?;
+    Ok(Json(HomingResponse { joints: body.joints, version }))
+}
+
+// ---- push calibration to hardware ----
+
+#[derive(Deserialize)]
+struct HardwareApplyQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct HardwareApplyResponse {
+    dry_run: bool,
+    model: &'static str,
+    writes: Vec<RegisterWrite>,
+    warnings: Vec<String>,
+    joints: Vec<JointVerification>,
+}
+
+/// Writes the profile into servo EEPROM and verifies it. With `?dry_run=true`
+/// only the planned register writes are returned; that also works without a
+/// bus, assuming STS3215 servos.
+async fn hardware_apply(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<HardwareApplyQuery>) -> Result<Json<HardwareApplyResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    if q.dry_run {
+        let table = match &state.bus {
+            Some(bus) => crate::bus::run(bus, |b| Ok(b.control_table())).await.map_err(ApiError::from_bus)?,
+            None => &crate::bus::feetech::STS3215,
+        };
+        let plan = eeprom::plan(table, &current);
+        return Ok(Json(HardwareApplyResponse { dry_run: true, model: table.model, writes: plan.writes, warnings: plan.warnings, joints: Vec::new() }));
+    }
+    let bus = require_bus(&state)?;
+    let (model, plan, joints) = crate::bus::run(&bus, move |b| {
+        let (plan, joints) = eeprom::plan_and_apply(b, &current);
+        Ok((b.control_table().model, plan, joints))
+    })
+    .await
+    .map_err(ApiError::from_bus)?;
+    Ok(Json(HardwareApplyResponse { dry_run: false, model, writes: plan.writes, warnings: plan.warnings, joints }))
+}
+
+// ---- read calibration back from hardware ----
+
+#[derive(Deserialize, Default)]
+struct ReadbackBody {
+    #[serde(default)]
+    max_id: Option<u8>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Serialize)]
+struct ReadbackResponse {
+    profile: Profile,
+    missing: Vec<MissingJoint>,
+    unexpected: Vec<u8>,
+}
+
+/// Scans the bus and saves what the SO-101 joints report as a new profile.
+async fn hardware_readback(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, body: Option<Json<ReadbackBody>>) -> Result<(StatusCode, Json<ReadbackResponse>), ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let body = body.map(|b| b.0).unwrap_or_default();
+    if !body.overwrite && state.store.exists(kind, &profile) {
+        return Err(ApiError::conflict("profile already exists; pass overwrite=true to replace it"));
+    }
+    let bus = require_bus(&state)?;
+    let max_id = body.max_id.unwrap_or(10);
+    let joints = registry::type_of(kind, &profile).unwrap_or(registry::default_for(kind)).joint_ids();
+    let mut report = crate::bus::run(&bus, move |b| Ok(readback::read_back(b, &joints, max_id)))
+        .await
+        .map_err(ApiError::from_bus)?;
+    if report.profile.0.is_empty() {
+        return Err(ApiError {
+            status: StatusCode::BAD_GATEWAY,
+            message: "no expected servo answered".into(),
+            details: Some(serde_json::json!({"missing": report.missing, "unexpected": report.unexpected})),
+        });
+    }
+    if let Ok(old) = state.store.read_profile(kind, &profile) {
+        report.profile.keep_extra_from(&old);
+    }
+    state.store.write_profile_from(kind, &profile, &report.profile, true, Origin::new("readback")).map_err(ApiError::from_store)?;
+    Ok((StatusCode::CREATED, Json(ReadbackResponse { profile: report.profile, missing: report.missing, unexpected: report.unexpected })))
+}
+
+#[derive(Deserialize, Default)]
+struct ScanBody {
+    /// Baud rates to try; empty means every rate the servos support.
+    #[serde(default)]
+    bauds: Vec<u32>,
+}
+
+#[derive(Serialize)]
+struct ScanResponse { devices: Vec<Device> }
+
+async fn scan_ids(State(state): State<AppState>, body: Option<Json<ScanBody>>) -> Result<Json<ScanResponse>, ApiError> {
+    let bauds = body.map(|b| b.0).unwrap_or_default().bauds;
+    let bus = require_bus(&state)?;
+    let devices = crate::bus::run(&bus, move |b| ids::scan(b, &bauds)).await.map_err(ApiError::from_bus)?;
+    Ok(Json(ScanResponse { devices }))
+}
+
+#[derive(Deserialize)]
+struct AssignBody {
+    joint: String,
+    #[serde(default)]
+    bauds: Vec<u32>,
+}
+
+async fn assign_id(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Json(body): Json<AssignBody>) -> Result<Json<Assignment>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let bus = require_bus(&state)?;
+    let assignment = crate::bus::run(&bus, move |b| Ok(ids::assign(b, &current, &body.joint, &body.bauds)))
+        .await
+        .map_err(ApiError::from_bus)?
+        .map_err(ApiError::from_assign)?;
+    Ok(Json(assignment))
+}
+
+#[derive(Deserialize)]
+struct StreamQuery {
+    /// Frames per second, 1..=50.
+    rate_hz: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct PositionFrame {
+    /// Milliseconds since the stream started.
+    t_ms: u64,
+    /// `bus`, or `sim` when no hardware is attached.
+    source: &'static str,
+    joints: Vec<JointSample>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Server-sent events with one `PositionFrame` per tick until the client
+/// disconnects.
+async fn stream_positions(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<StreamQuery>) -> Result<impl IntoResponse, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let rate = q.rate_hz.unwrap_or(10).clamp(1, 50);
+    let (source, sim, bus) = match state.bus.clone() {
+        Some(bus) => ("bus", None, bus),
+        None => {
+            let arm = SimulatedArm::new(&current);
+            let bus = arm.bus();
+            ("sim", Some(arm), bus)
+        }
+    };
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, std::convert::Infallible>>(4);
+    tokio::spawn(async move {
+        let started = std::time::Instant::now();
+        let mut tick = tokio::time::interval(Duration::from_millis(1000 / rate as u64));
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            tick.tick().await;
+            if let Some(arm) = &sim {
+                arm.advance();
+            }
+            let profile = current.clone();
+            let mut frame = PositionFrame { t_ms: started.elapsed().as_millis() as u64, source, joints: Vec::new(), error: None };
+            match crate::bus::run(&bus, move |b| live::sample(b, &profile)).await {
+                Ok(joints) => frame.joints = joints,
+                Err(e) => frame.error = Some(e.to_string()),
+            }
+            let event = Event::default().json_data(&frame).unwrap_or_else(|e| Event::default().event("error").data(e.to_string()));
+            if tx.send(Ok(event)).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(Sse::new(tokio_stream::wrappers::ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct TorqueBody {
+    id: u8,
+    enabled: bool,
+}
+
+#[derive(Serialize)]
+struct TorqueResponse {
+    joint: String,
+    id: u8,
+    enabled: bool,
+}
+
+async fn set_torque(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Json(body): Json<TorqueBody>) -> Result<Json<TorqueResponse>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let (joint, _) = control::joint_by_id(&current, body.id).ok_or_else(|| ApiError::not_found("servo id not in profile"))?;
+    let joint = joint.clone();
+    let bus = require_bus(&state)?;
+    let TorqueBody { id, enabled } = body;
+    crate::bus::run(&bus, move |b| control::set_torque(b, id, enabled)).await.map_err(ApiError::from_bus)?;
+    Ok(Json(TorqueResponse { joint, id, enabled }))
+}
+
+#[derive(Deserialize)]
+struct GoalBody {
+    id: u8,
+    position: i32,
+}
+
+/// Drives one joint to `position`, clamped to the stored range.
+async fn set_goal(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Json(body): Json<GoalBody>) -> Result<Json<GoalResult>, ApiError> {
+    let KindParam(kind) = KindParam::from_str(&kind)?;
+    let current = state.store.read_profile(kind, &profile).map_err(ApiError::from_store)?;
+    let (name, joint) = control::joint_by_id(&current, body.id).ok_or_else(|| ApiError::not_found("servo id not in profile"))?;
+    let (name, joint) = (name.clone(), joint.clone());
+    let bus = require_bus(&state)?;
+    let result = crate::bus::run(&bus, move |b| control::move_to(b, &name, &joint, body.position)).await.map_err(ApiError::from_bus)?;
+    Ok(Json(result))
+}
+
+#[derive(Serialize)]
+struct TorqueOffResponse { ok: bool }
+
+/// Emergency stop; works without a profile.
+async fn torque_off_all(State(state): State<AppState>) -> Result<Json<TorqueOffResponse>, ApiError> {
+    let bus = require_bus(&state)?;
+    crate::bus::run(&bus, control::torque_off_all).await.map_err(ApiError::from_bus)?;
+    Ok(Json(TorqueOffResponse { ok: true }))
+}
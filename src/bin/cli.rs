@@ -0,0 +1,7 @@
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = lerobot_servo_adjust::cli::run(&args) {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,378 @@
+//! Dynamixel Protocol 2.0.
+//!
+//! Instruction: `FF FF FD 00 id len_l len_h instr params.. crc_l crc_h`,
+//! status: the same framing with instruction `0x55` followed by an error
+//! byte. `len` counts everything after itself, the CRC-16 (poly 0x8005)
+//! covers the whole packet before it, and any `FF FF FD` inside the
+//! instruction/parameter field is stuffed to `FF FF FD FD`.
+
+use super::{read_error, BusError, ControlTable, Encoding, HomingMode, Port, Register, RegisterSpec, ServoBus, BROADCAST_ID};
+
+pub const INST_PING: u8 = 0x01;
+pub const INST_READ: u8 = 0x02;
+pub const INST_WRITE: u8 = 0x03;
+pub const INST_STATUS: u8 = 0x55;
+pub const INST_SYNC_READ: u8 = 0x82;
+pub const INST_SYNC_WRITE: u8 = 0x83;
+
+pub const ERR_ACCESS: u8 = 0x07;
+
+const HEADER: [u8; 4] = [0xFF, 0xFF, 0xFD, 0x00];
+
+/// Control table shared by the X series (XL330, XL430, ...).
+const X_SERIES: &[(Register, RegisterSpec)] = &[
+    (Register::ModelNumber, RegisterSpec { addr: 0, len: 2, encoding: Encoding::Unsigned }),
+    (Register::FirmwareMajor, RegisterSpec { addr: 6, len: 1, encoding: Encoding::Unsigned }),
+    (Register::Id, RegisterSpec { addr: 7, len: 1, encoding: Encoding::Unsigned }),
+    (Register::BaudRate, RegisterSpec { addr: 8, len: 1, encoding: Encoding::Unsigned }),
+    (Register::DriveMode, RegisterSpec { addr: 10, len: 1, encoding: Encoding::Unsigned }),
+    (Register::HomingOffset, RegisterSpec { addr: 20, len: 4, encoding: Encoding::Signed }),
+    (Register::MaxPositionLimit, RegisterSpec { addr: 48, len: 4, encoding: Encoding::Unsigned }),
+    (Register::MinPositionLimit, RegisterSpec { addr: 52, len: 4, encoding: Encoding::Unsigned }),
+    (Register::TorqueEnable, RegisterSpec { addr: 64, len: 1, encoding: Encoding::Unsigned }),
+    (Register::GoalPosition, RegisterSpec { addr: 116, len: 4, encoding: Encoding::Signed }),
+    (Register::PresentPosition, RegisterSpec { addr: 132, len: 4, encoding: Encoding::Signed }),
+];
+
+/// XL330-M288, used on the Koch v1.1 wrist and gripper.
+pub static XL330_M288: ControlTable = ControlTable {
+    model: "xl330-m288",
+    model_number: 1200,
+    resolution: 4096,
+    homing: HomingMode::Add,
+    baud_rates: &BAUD_RATES,
+    registers: X_SERIES,
+};
+
+/// XL330-M077, used throughout the Koch v1.1 leader arm.
+pub static XL330_M077: ControlTable = ControlTable {
+    model: "xl330-m077",
+    model_number: 1190,
+    resolution: 4096,
+    homing: HomingMode::Add,
+    baud_rates: &BAUD_RATES,
+    registers: X_SERIES,
+};
+
+/// XL430-W250, used on the Koch v1.1 shoulder.
+pub static XL430_W250: ControlTable = ControlTable {
+    model: "xl430-w250",
+    model_number: 1060,
+    resolution: 4096,
+    homing: HomingMode::Add,
+    baud_rates: &BAUD_RATES,
+    registers: X_SERIES,
+};
+
+/// First RAM address; EEPROM below it is writable only with torque off.
+pub const EEPROM_END: u16 = 64;
+
+/// Baud rates selectable through the `BaudRate` register, by register value.
+pub const BAUD_RATES: [u32; 8] = [9_600, 57_600, 115_200, 1_000_000, 2_000_000, 3_000_000, 4_000_000, 4_500_000];
+
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for b in data {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Inserts an extra `FD` after every `FF FF FD`.
+pub fn stuff(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 2);
+    for b in data {
+        out.push(*b);
+        if out.ends_with(&[0xFF, 0xFF, 0xFD]) {
+            out.push(0xFD);
+        }
+    }
+    out
+}
+
+/// Removes the `FD` that follows every `FF FF FD`.
+pub fn destuff(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        out.push(data[i]);
+        if out.ends_with(&[0xFF, 0xFF, 0xFD]) && data.get(i + 1) == Some(&0xFD) {
+            i += 1;
+        }
+        i += 1;
+    }
+    out
+}
+
+fn encode(id: u8, payload: &[u8]) -> Vec<u8> {
+    let body = stuff(payload);
+    let len = (body.len() + 2) as u16;
+    let mut pkt = Vec::with_capacity(body.len() + 9);
+    pkt.extend_from_slice(&HEADER);
+    pkt.push(id);
+    pkt.extend_from_slice(&len.to_le_bytes());
+    pkt.extend_from_slice(&body);
+    let crc = crc16(&pkt);
+    pkt.extend_from_slice(&crc.to_le_bytes());
+    pkt
+}
+
+/// Builds a complete instruction packet.
+pub fn encode_instruction(id: u8, instr: u8, params: &[u8]) -> Vec<u8> {
+    let mut payload = vec![instr];
+    payload.extend_from_slice(params);
+    encode(id, &payload)
+}
+
+/// Builds a complete status packet (used by the simulator).
+pub fn encode_status(id: u8, error: u8, params: &[u8]) -> Vec<u8> {
+    let mut payload = vec![INST_STATUS, error];
+    payload.extend_from_slice(params);
+    encode(id, &payload)
+}
+
+/// Splits a packet whose framing is already known to be complete into
+/// `(id, destuffed payload)`, checking the CRC.
+pub fn decode(pkt: &[u8]) -> Result<(u8, Vec<u8>), BusError> {
+    if pkt.len() < 10 || pkt[..4] != HEADER {
+        return Err(BusError::Corrupt("bad dynamixel header".into()));
+    }
+    let (body, crc) = pkt.split_at(pkt.len() - 2);
+    let expected = crc16(body);
+    let actual = u16::from_le_bytes([crc[0], crc[1]]);
+    if expected != actual {
+        return Err(BusError::Checksum { expected, actual });
+    }
+    Ok((pkt[4], destuff(&body[7..])))
+}
+
+/// Human-readable description of a status error byte.
+pub fn describe_error(code: u8) -> String {
+    let kind = match code & 0x7F {
+        0 => "",
+        1 => "result fail",
+        2 => "instruction error",
+        3 => "crc error",
+        4 => "data range error",
+        5 => "data length error",
+        6 => "data limit error",
+        7 => "access error",
+        _ => "unknown error",
+    };
+    match (code & 0x80 != 0, kind.is_empty()) {
+        (true, true) => "hardware alert".into(),
+        (true, false) => format!("{} (hardware alert)", kind),
+        (false, _) => kind.into(),
+    }
+}
+
+pub struct DynamixelBus<T> {
+    port: T,
+    table: &'static ControlTable,
+}
+
+impl<T: Port> DynamixelBus<T> {
+    /// Bus of X-series servos; their shared control table is used for all ids.
+    pub fn new(port: T) -> Self {
+        Self { port, table: &XL330_M288 }
+    }
+
+    pub fn with_table(port: T, table: &'static ControlTable) -> Self {
+        Self { port, table }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.port
+    }
+
+    fn send(&mut self, id: u8, instr: u8, params: &[u8]) -> Result<(), BusError> {
+        self.port.write_all(&encode_instruction(id, instr, params))?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    fn read_byte(&mut self, id: u8) -> Result<u8, BusError> {
+        let mut b = [0u8; 1];
+        self.port.read_exact(&mut b).map_err(|e| read_error(id, e))?;
+        Ok(b[0])
+    }
+
+    /// Reads one status packet from servo `id` and returns its parameters.
+    fn receive(&mut self, id: u8) -> Result<Vec<u8>, BusError> {
+        let (rid, payload) = self.receive_any(id)?;
+        if rid != id {
+            return Err(BusError::Corrupt(format!("expected status from {}, got {}", id, rid)));
+        }
+        if payload[1] != 0 {
+            return Err(BusError::Status { id, code: payload[1], message: describe_error(payload[1]) });
+        }
+        Ok(payload[2..].to_vec())
+    }
+
+    /// Reads one status packet from any servo as `(id, payload)`; `id` only
+    /// labels timeouts.
+    fn receive_any(&mut self, id: u8) -> Result<(u8, Vec<u8>), BusError> {
+        let mut pkt = Vec::with_capacity(16);
+        while !pkt.ends_with(&HEADER) {
+            pkt.push(self.read_byte(id)?);
+        }
+        pkt.drain(..pkt.len() - 4);
+        for _ in 0..3 {
+            pkt.push(self.read_byte(id)?);
+        }
+        let len = u16::from_le_bytes([pkt[5], pkt[6]]) as usize;
+        if len < 4 {
+            return Err(BusError::Corrupt(format!("status length {} too short", len)));
+        }
+        let start = pkt.len();
+        pkt.resize(start + len, 0);
+        self.port.read_exact(&mut pkt[start..]).map_err(|e| read_error(id, e))?;
+        let (rid, payload) = decode(&pkt)?;
+        if payload.len() < 2 || payload[0] != INST_STATUS {
+            return Err(BusError::Corrupt("not a status packet".into()));
+        }
+        Ok((rid, payload))
+    }
+
+    fn expect_data(&mut self, id: u8, len: u16) -> Result<Vec<u8>, BusError> {
+        let params = self.receive(id)?;
+        if params.len() != len as usize {
+            return Err(BusError::Corrupt(format!("servo {} returned {} bytes, wanted {}", id, params.len(), len)));
+        }
+        Ok(params)
+    }
+}
+
+impl<T: Port> ServoBus for DynamixelBus<T> {
+    fn control_table(&self) -> &'static ControlTable {
+        self.table
+    }
+
+    fn baud_rate(&self) -> Result<u32, BusError> {
+        Ok(self.port.baud_rate()?)
+    }
+
+    fn set_baud_rate(&mut self, baud: u32) -> Result<(), BusError> {
+        Ok(self.port.set_baud_rate(baud)?)
+    }
+
+    fn ping(&mut self, id: u8) -> Result<(), BusError> {
+        self.send(id, INST_PING, &[])?;
+        self.receive(id).map(|_| ())
+    }
+
+    fn broadcast_ping(&mut self) -> Result<Vec<u8>, BusError> {
+        self.send(BROADCAST_ID, INST_PING, &[])?;
+        let mut ids = Vec::new();
+        loop {
+            match self.receive_any(BROADCAST_ID) {
+                Ok((id, _)) => ids.push(id),
+                Err(BusError::Timeout(_)) => return Ok(ids),
+                // replies may collide; keep listening for the rest
+                Err(BusError::Checksum { .. } | BusError::Corrupt(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn read(&mut self, id: u8, addr: u16, len: u16) -> Result<Vec<u8>, BusError> {
+        let mut params = addr.to_le_bytes().to_vec();
+        params.extend_from_slice(&len.to_le_bytes());
+        self.send(id, INST_READ, &params)?;
+        self.expect_data(id, len)
+    }
+
+    fn write(&mut self, id: u8, addr: u16, data: &[u8]) -> Result<(), BusError> {
+        let mut params = addr.to_le_bytes().to_vec();
+        params.extend_from_slice(data);
+        self.send(id, INST_WRITE, &params)?;
+        if id == BROADCAST_ID {
+            return Ok(());
+        }
+        self.receive(id).map(|_| ())
+    }
+
+    fn sync_read(&mut self, ids: &[u8], addr: u16, len: u16) -> Result<Vec<Vec<u8>>, BusError> {
+        let mut params = addr.to_le_bytes().to_vec();
+        params.extend_from_slice(&len.to_le_bytes());
+        params.extend_from_slice(ids);
+        self.send(BROADCAST_ID, INST_SYNC_READ, &params)?;
+        ids.iter().map(|id| self.expect_data(*id, len)).collect()
+    }
+
+    fn sync_write(&mut self, addr: u16, len: u16, data: &[(u8, Vec<u8>)]) -> Result<(), BusError> {
+        let mut params = addr.to_le_bytes().to_vec();
+        params.extend_from_slice(&len.to_le_bytes());
+        for (id, bytes) in data {
+            if bytes.len() != len as usize {
+                return Err(BusError::Corrupt(format!("sync write block for {} has {} bytes, wanted {}", id, bytes.len(), len)));
+            }
+            params.push(*id);
+            params.extend_from_slice(bytes);
+        }
+        self.send(BROADCAST_ID, INST_SYNC_WRITE, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::sim::{SimBus, SimServo};
+
+    #[test]
+    fn packet_encoding_matches_reference() {
+        // examples from the ROBOTIS Protocol 2.0 e-manual
+        assert_eq!(encode_instruction(1, INST_PING, &[]), vec![0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x03, 0x00, 0x01, 0x19, 0x4E]);
+        assert_eq!(
+            encode_instruction(1, INST_READ, &[0x84, 0x00, 0x04, 0x00]),
+            vec![0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x02, 0x84, 0x00, 0x04, 0x00, 0x1D, 0x15]
+        );
+    }
+
+    #[test]
+    fn byte_stuffing_roundtrip() {
+        let raw = [0x03, 0xFF, 0xFF, 0xFD, 0x10, 0xFF, 0xFF, 0xFD];
+        let stuffed = stuff(&raw);
+        assert_eq!(stuffed, vec![0x03, 0xFF, 0xFF, 0xFD, 0xFD, 0x10, 0xFF, 0xFF, 0xFD, 0xFD]);
+        assert_eq!(destuff(&stuffed), raw.to_vec());
+
+        let pkt = encode_instruction(2, INST_WRITE, &[20, 0, 0xFF, 0xFF, 0xFD, 0x00]);
+        assert_eq!(u16::from_le_bytes([pkt[5], pkt[6]]), 10);
+        let (id, payload) = decode(&pkt).unwrap();
+        assert_eq!((id, payload), (2, vec![INST_WRITE, 20, 0, 0xFF, 0xFF, 0xFD, 0x00]));
+    }
+
+    #[test]
+    fn read_write_against_sim() {
+        let sim = SimBus::dynamixel(vec![SimServo::xl330(1), SimServo::xl330(2)]);
+        let handle = sim.handle();
+        handle.set_raw_position(1, 1000);
+        let mut bus = DynamixelBus::new(sim);
+
+        bus.ping(2).unwrap();
+        assert!(matches!(bus.ping(7), Err(BusError::Timeout(7))));
+        assert_eq!(bus.read_register(1, Register::ModelNumber).unwrap(), 1200);
+
+        // offsets whose encoding needs stuffing (0xFFFDFFFF) survive the round trip
+        bus.write_register(1, Register::HomingOffset, -131_073).unwrap();
+        assert_eq!(bus.read_register(1, Register::HomingOffset).unwrap(), -131_073);
+        bus.write_register(1, Register::HomingOffset, 1047).unwrap();
+        assert_eq!(bus.sync_read_register(&[1, 2], Register::PresentPosition).unwrap(), vec![2047, 2047]);
+
+        // EEPROM is read-only while torque is on
+        bus.write_register(2, Register::TorqueEnable, 1).unwrap();
+        match bus.write_register(2, Register::DriveMode, 1) {
+            Err(BusError::Status { code: ERR_ACCESS, message, .. }) => assert_eq!(message, "access error"),
+            other => panic!("unexpected {:?}", other),
+        }
+        bus.sync_write_register(Register::TorqueEnable, &[(1, 0), (2, 0)]).unwrap();
+        bus.write_register(2, Register::DriveMode, 1).unwrap();
+        assert_eq!(handle.register(2, Register::DriveMode), 1);
+
+        handle.corrupt_next_response();
+        assert!(matches!(bus.ping(1), Err(BusError::Checksum { .. })));
+    }
+}
@@ -0,0 +1,287 @@
+//! Feetech SCS/STS packet protocol.
+//!
+//! Instruction: `FF FF id len instr params.. checksum`, status:
+//! `FF FF id len error params.. checksum`, where `len` counts the bytes after
+//! itself and the checksum is the inverted low byte of the sum from `id` on.
+
+use super::{read_error, BusError, ControlTable, Encoding, HomingMode, Port, Register, RegisterSpec, ServoBus};
+
+pub const INST_PING: u8 = 0x01;
+pub const INST_READ: u8 = 0x02;
+pub const INST_WRITE: u8 = 0x03;
+pub const INST_SYNC_READ: u8 = 0x82;
+pub const INST_SYNC_WRITE: u8 = 0x83;
+
+const HEADER: [u8; 2] = [0xFF, 0xFF];
+
+/// STS3215, the servo used by SO-100/SO-101 arms.
+pub static STS3215: ControlTable = ControlTable {
+    model: "sts3215",
+    model_number: 777,
+    resolution: 4096,
+    homing: HomingMode::Subtract,
+    baud_rates: &BAUD_RATES,
+    registers: &[
+        (Register::FirmwareMajor, RegisterSpec { addr: 0, len: 1, encoding: Encoding::Unsigned }),
+        (Register::FirmwareMinor, RegisterSpec { addr: 1, len: 1, encoding: Encoding::Unsigned }),
+        (Register::ModelNumber, RegisterSpec { addr: 3, len: 2, encoding: Encoding::Unsigned }),
+        (Register::Id, RegisterSpec { addr: 5, len: 1, encoding: Encoding::Unsigned }),
+        (Register::BaudRate, RegisterSpec { addr: 6, len: 1, encoding: Encoding::Unsigned }),
+        (Register::MinPositionLimit, RegisterSpec { addr: 9, len: 2, encoding: Encoding::Unsigned }),
+        (Register::MaxPositionLimit, RegisterSpec { addr: 11, len: 2, encoding: Encoding::Unsigned }),
+        (Register::HomingOffset, RegisterSpec { addr: 31, len: 2, encoding: Encoding::SignMagnitude(11) }),
+        (Register::TorqueEnable, RegisterSpec { addr: 40, len: 1, encoding: Encoding::Unsigned }),
+        (Register::GoalPosition, RegisterSpec { addr: 42, len: 2, encoding: Encoding::SignMagnitude(15) }),
+        (Register::Lock, RegisterSpec { addr: 55, len: 1, encoding: Encoding::Unsigned }),
+        (Register::PresentPosition, RegisterSpec { addr: 56, len: 2, encoding: Encoding::SignMagnitude(15) }),
+    ],
+};
+
+/// Baud rates selectable through the `BaudRate` register, by register value.
+pub const BAUD_RATES: [u32; 8] = [1_000_000, 500_000, 250_000, 128_000, 115_200, 57_600, 38_400, 19_200];
+
+pub fn checksum(body: &[u8]) -> u8 {
+    !body.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+/// Builds a complete instruction packet.
+pub fn encode_instruction(id: u8, instr: u8, params: &[u8]) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(params.len() + 6);
+    pkt.extend_from_slice(&HEADER);
+    pkt.push(id);
+    pkt.push((params.len() + 2) as u8);
+    pkt.push(instr);
+    pkt.extend_from_slice(params);
+    pkt.push(checksum(&pkt[2..]));
+    pkt
+}
+
+/// Builds a complete status packet (used by the simulator).
+pub fn encode_status(id: u8, error: u8, params: &[u8]) -> Vec<u8> {
+    encode_instruction(id, error, params)
+}
+
+/// Human-readable names of the bits set in a status error byte.
+pub fn describe_error(code: u8) -> String {
+    const BITS: [(u8, &str); 6] = [
+        (0x01, "input voltage"),
+        (0x02, "angle sensor"),
+        (0x04, "overheat"),
+        (0x08, "overcurrent"),
+        (0x10, "angle limit"),
+        (0x20, "overload"),
+    ];
+    let names: Vec<&str> = BITS.iter().filter(|(b, _)| code & b != 0).map(|(_, n)| *n).collect();
+    if names.is_empty() { format!("error {:#04x}", code) } else { names.join(", ") }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPacket {
+    pub id: u8,
+    pub error: u8,
+    pub params: Vec<u8>,
+}
+
+pub struct FeetechBus<T> {
+    port: T,
+    table: &'static ControlTable,
+}
+
+impl<T: Port> FeetechBus<T> {
+    pub fn new(port: T) -> Self {
+        Self { port, table: &STS3215 }
+    }
+
+    pub fn with_table(port: T, table: &'static ControlTable) -> Self {
+        Self { port, table }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.port
+    }
+
+    fn send(&mut self, id: u8, instr: u8, params: &[u8]) -> Result<(), BusError> {
+        self.port.write_all(&encode_instruction(id, instr, params))?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    fn read_byte(&mut self, id: u8) -> Result<u8, BusError> {
+        let mut b = [0u8; 1];
+        self.port.read_exact(&mut b).map_err(|e| read_error(id, e))?;
+        Ok(b[0])
+    }
+
+    /// Reads one status packet from servo `id`.
+    fn receive(&mut self, id: u8) -> Result<StatusPacket, BusError> {
+        let pkt = self.receive_any(id)?;
+        if pkt.id != id {
+            return Err(BusError::Corrupt(format!("expected status from {}, got {}", id, pkt.id)));
+        }
+        if pkt.error != 0 {
+            return Err(BusError::Status { id, code: pkt.error, message: describe_error(pkt.error) });
+        }
+        Ok(pkt)
+    }
+
+    /// Reads one status packet from any servo, skipping any noise before the
+    /// header; `id` only labels timeouts.
+    fn receive_any(&mut self, id: u8) -> Result<StatusPacket, BusError> {
+        let mut prev = self.read_byte(id)?;
+        loop {
+            let b = self.read_byte(id)?;
+            if prev == 0xFF && b == 0xFF {
+                break;
+            }
+            prev = b;
+        }
+        let mut rid = self.read_byte(id)?;
+        // tolerate a third 0xFF before the id
+        if rid == 0xFF {
+            rid = self.read_byte(id)?;
+        }
+        let len = self.read_byte(id)? as usize;
+        if len < 2 {
+            return Err(BusError::Corrupt(format!("status length {} too short", len)));
+        }
+        let mut rest = vec![0u8; len];
+        self.port.read_exact(&mut rest).map_err(|e| read_error(id, e))?;
+        let mut body = vec![rid, len as u8];
+        body.extend_from_slice(&rest[..len - 1]);
+        let expected = checksum(&body);
+        let actual = rest[len - 1];
+        if expected != actual {
+            return Err(BusError::Checksum { expected: expected as u16, actual: actual as u16 });
+        }
+        Ok(StatusPacket { id: rid, error: rest[0], params: rest[1..len - 1].to_vec() })
+    }
+
+    fn expect_data(&mut self, id: u8, len: u16) -> Result<Vec<u8>, BusError> {
+        let pkt = self.receive(id)?;
+        if pkt.params.len() != len as usize {
+            return Err(BusError::Corrupt(format!("servo {} returned {} bytes, wanted {}", id, pkt.params.len(), len)));
+        }
+        Ok(pkt.params)
+    }
+}
+
+impl<T: Port> ServoBus for FeetechBus<T> {
+    fn control_table(&self) -> &'static ControlTable {
+        self.table
+    }
+
+    fn baud_rate(&self) -> Result<u32, BusError> {
+        Ok(self.port.baud_rate()?)
+    }
+
+    fn set_baud_rate(&mut self, baud: u32) -> Result<(), BusError> {
+        Ok(self.port.set_baud_rate(baud)?)
+    }
+
+    fn ping(&mut self, id: u8) -> Result<(), BusError> {
+        self.send(id, INST_PING, &[])?;
+        self.receive(id).map(|_| ())
+    }
+
+    fn broadcast_ping(&mut self) -> Result<Vec<u8>, BusError> {
+        self.send(super::BROADCAST_ID, INST_PING, &[])?;
+        let mut ids = Vec::new();
+        loop {
+            match self.receive_any(super::BROADCAST_ID) {
+                Ok(pkt) => ids.push(pkt.id),
+                Err(BusError::Timeout(_)) => return Ok(ids),
+                // replies may collide; keep listening for the rest
+                Err(BusError::Checksum { .. } | BusError::Corrupt(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn read(&mut self, id: u8, addr: u16, len: u16) -> Result<Vec<u8>, BusError> {
+        self.send(id, INST_READ, &[addr as u8, len as u8])?;
+        self.expect_data(id, len)
+    }
+
+    fn write(&mut self, id: u8, addr: u16, data: &[u8]) -> Result<(), BusError> {
+        let mut params = vec![addr as u8];
+        params.extend_from_slice(data);
+        self.send(id, INST_WRITE, &params)?;
+        if id == super::BROADCAST_ID {
+            return Ok(());
+        }
+        self.receive(id).map(|_| ())
+    }
+
+    fn sync_read(&mut self, ids: &[u8], addr: u16, len: u16) -> Result<Vec<Vec<u8>>, BusError> {
+        let mut params = vec![addr as u8, len as u8];
+        params.extend_from_slice(ids);
+        self.send(super::BROADCAST_ID, INST_SYNC_READ, &params)?;
+        ids.iter().map(|id| self.expect_data(*id, len)).collect()
+    }
+
+    fn sync_write(&mut self, addr: u16, len: u16, data: &[(u8, Vec<u8>)]) -> Result<(), BusError> {
+        let mut params = vec![addr as u8, len as u8];
+        for (id, bytes) in data {
+            if bytes.len() != len as usize {
+                return Err(BusError::Corrupt(format!("sync write block for {} has {} bytes, wanted {}", id, bytes.len(), len)));
+            }
+            params.push(*id);
+            params.extend_from_slice(bytes);
+        }
+        self.send(super::BROADCAST_ID, INST_SYNC_WRITE, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::sim::{SimBus, SimServo};
+
+    #[test]
+    fn packet_encoding() {
+        // read Present_Position (56, 2 bytes) from id 1
+        assert_eq!(encode_instruction(1, INST_READ, &[56, 2]), vec![0xFF, 0xFF, 0x01, 0x04, 0x02, 0x38, 0x02, 0xBE]);
+        assert_eq!(describe_error(0x24), "overheat, overload");
+    }
+
+    #[test]
+    fn read_write_against_sim() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        handle.set_raw_position(2, 3000);
+        let mut bus = FeetechBus::new(sim);
+
+        bus.ping(1).unwrap();
+        assert!(matches!(bus.ping(9), Err(BusError::Timeout(9))));
+        assert_eq!(bus.read_register(1, Register::ModelNumber).unwrap(), 777);
+
+        bus.write_register(2, Register::Lock, 0).unwrap();
+        bus.write_register(2, Register::HomingOffset, -1908).unwrap();
+        assert_eq!(bus.read_register(2, Register::HomingOffset).unwrap(), -1908);
+        assert_eq!(handle.register(2, Register::HomingOffset), -1908);
+
+        let pos = bus.sync_read_register(&[1, 2], Register::PresentPosition).unwrap();
+        assert_eq!(pos, vec![2047, 3000 + 1908 - 4096]);
+
+        bus.sync_write_register(Register::TorqueEnable, &[(1, 1), (2, 1)]).unwrap();
+        assert_eq!(handle.register(1, Register::TorqueEnable), 1);
+    }
+
+    #[test]
+    fn status_errors_and_corruption() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(3)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+
+        handle.set_error(3, 0x04);
+        match bus.ping(3) {
+            Err(BusError::Status { id: 3, code: 0x04, message }) => assert_eq!(message, "overheat"),
+            other => panic!("unexpected {:?}", other),
+        }
+        handle.set_error(3, 0);
+
+        handle.corrupt_next_response();
+        assert!(matches!(bus.read_register(3, Register::Id), Err(BusError::Checksum { .. })));
+        assert_eq!(bus.read_register(3, Register::Id).unwrap(), 3);
+    }
+}
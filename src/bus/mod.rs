@@ -0,0 +1,370 @@
+//! Servo bus access.
+//!
+//! `ServoBus` is the vendor-neutral interface used by the rest of the crate;
+//! each protocol (Feetech SCS/STS, Dynamixel 2.0) implements it on top of any
+//! `Read + Write` transport. Registers are addressed by name through a
+//! per-model `ControlTable` so callers never deal with raw addresses.
+
+pub mod dynamixel;
+pub mod feetech;
+pub mod sim;
+
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use thiserror::Error;
+
+/// ID addressing every servo on the bus.
+pub const BROADCAST_ID: u8 = 0xFE;
+
+/// Wire protocol spoken on a bus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Feetech SCS/STS (SO-100, SO-101).
+    #[default]
+    Feetech,
+    /// Dynamixel Protocol 2.0 (Koch).
+    Dynamixel,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Feetech => "feetech",
+            Protocol::Dynamixel => "dynamixel",
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "feetech" => Ok(Protocol::Feetech),
+            "dynamixel" => Ok(Protocol::Dynamixel),
+            other => Err(format!("unknown protocol `{}`", other)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BusError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("no response from servo {0}")]
+    Timeout(u8),
+    #[error("corrupt packet: {0}")]
+    Corrupt(String),
+    #[error("checksum mismatch: expected {expected:#06x}, got {actual:#06x}")]
+    Checksum { expected: u16, actual: u16 },
+    #[error("servo {id} reported error: {message}")]
+    Status { id: u8, code: u8, message: String },
+    #[error("register {0:?} not available on this bus")]
+    Unsupported(Register),
+}
+
+/// Named registers shared by all supported servo families.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    FirmwareMajor,
+    FirmwareMinor,
+    ModelNumber,
+    Id,
+    BaudRate,
+    MinPositionLimit,
+    MaxPositionLimit,
+    HomingOffset,
+    DriveMode,
+    TorqueEnable,
+    GoalPosition,
+    Lock,
+    PresentPosition,
+}
+
+/// How a register value is laid out in memory (always little-endian).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Unsigned,
+    /// Magnitude in the low bits, sign in the given bit (Feetech).
+    SignMagnitude(u8),
+    /// Two's complement over the register width (Dynamixel).
+    Signed,
+}
+
+/// How the homing offset combines with the encoder reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomingMode {
+    /// `present = raw - offset` (Feetech).
+    Subtract,
+    /// `present = raw + offset` (Dynamixel).
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSpec {
+    pub addr: u16,
+    pub len: u8,
+    pub encoding: Encoding,
+}
+
+impl RegisterSpec {
+    pub fn encode(&self, value: i32) -> Vec<u8> {
+        let raw: u32 = match self.encoding {
+            Encoding::Unsigned | Encoding::Signed => value as u32,
+            Encoding::SignMagnitude(bit) => {
+                let magnitude = value.unsigned_abs() & ((1u32 << bit) - 1);
+                if value < 0 { magnitude | (1u32 << bit) } else { magnitude }
+            }
+        };
+        raw.to_le_bytes()[..self.len as usize].to_vec()
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> i32 {
+        let mut buf = [0u8; 4];
+        let n = bytes.len().min(self.len as usize).min(4);
+        buf[..n].copy_from_slice(&bytes[..n]);
+        let raw = u32::from_le_bytes(buf);
+        match self.encoding {
+            Encoding::Unsigned => raw as i32,
+            Encoding::Signed => {
+                let shift = 32 - 8 * (self.len as u32).clamp(1, 4);
+                ((raw << shift) as i32) >> shift
+            }
+            Encoding::SignMagnitude(bit) => {
+                let magnitude = (raw & ((1u32 << bit) - 1)) as i32;
+                if raw & (1u32 << bit) != 0 { -magnitude } else { magnitude }
+            }
+        }
+    }
+}
+
+/// Register layout of one servo model.
+#[derive(Debug)]
+pub struct ControlTable {
+    pub model: &'static str,
+    pub model_number: u16,
+    /// Ticks per full turn.
+    pub resolution: u32,
+    pub homing: HomingMode,
+    /// Baud rate selected by each value of the `BaudRate` register.
+    pub baud_rates: &'static [u32],
+    pub registers: &'static [(Register, RegisterSpec)],
+}
+
+impl ControlTable {
+    pub fn get(&self, reg: Register) -> Option<RegisterSpec> {
+        self.registers.iter().find(|(r, _)| *r == reg).map(|(_, s)| *s)
+    }
+
+    pub fn spec(&self, reg: Register) -> Result<RegisterSpec, BusError> {
+        self.get(reg).ok_or(BusError::Unsupported(reg))
+    }
+
+    /// `BaudRate` register value selecting `baud`.
+    pub fn baud_index(&self, baud: u32) -> Option<i32> {
+        self.baud_rates.iter().position(|b| *b == baud).map(|i| i as i32)
+    }
+
+    /// Position reported for encoder reading `raw` under `offset`.
+    pub fn present(&self, raw: i32, offset: i32) -> i32 {
+        let p = match self.homing {
+            HomingMode::Subtract => raw - offset,
+            HomingMode::Add => raw + offset,
+        };
+        p.rem_euclid(self.resolution as i32)
+    }
+
+    /// Encoder reading behind a reported `present` position.
+    pub fn raw(&self, present: i32, offset: i32) -> i32 {
+        let r = match self.homing {
+            HomingMode::Subtract => present + offset,
+            HomingMode::Add => present - offset,
+        };
+        r.rem_euclid(self.resolution as i32)
+    }
+
+    /// Offset under which encoder reading `raw` reports as `present`.
+    pub fn offset_for(&self, raw: i32, present: i32) -> i32 {
+        match self.homing {
+            HomingMode::Subtract => raw - present,
+            HomingMode::Add => present - raw,
+        }
+    }
+}
+
+/// Every servo model this crate knows, for identifying pinged devices.
+pub static KNOWN_MODELS: [&ControlTable; 4] = [&feetech::STS3215, &dynamixel::XL330_M077, &dynamixel::XL330_M288, &dynamixel::XL430_W250];
+
+/// Looks up a model by the number it reports in `ModelNumber`.
+pub fn model_by_number(number: u16) -> Option<&'static ControlTable> {
+    KNOWN_MODELS.iter().copied().find(|t| t.model_number == number)
+}
+
+/// Byte transport under a protocol implementation.
+pub trait Port: Read + Write + Send {
+    fn baud_rate(&self) -> io::Result<u32>;
+
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()>;
+}
+
+impl Port for Box<dyn serialport::SerialPort> {
+    fn baud_rate(&self) -> io::Result<u32> {
+        serialport::SerialPort::baud_rate(self.as_ref()).map_err(io::Error::from)
+    }
+
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        serialport::SerialPort::set_baud_rate(self.as_mut(), baud).map_err(io::Error::from)
+    }
+}
+
+/// A half-duplex servo bus speaking one packet protocol.
+pub trait ServoBus: Send {
+    fn control_table(&self) -> &'static ControlTable;
+
+    fn baud_rate(&self) -> Result<u32, BusError>;
+
+    fn set_baud_rate(&mut self, baud: u32) -> Result<(), BusError>;
+
+    fn ping(&mut self, id: u8) -> Result<(), BusError>;
+
+    /// Pings every servo at once and returns the ids that answered.
+    fn broadcast_ping(&mut self) -> Result<Vec<u8>, BusError>;
+
+    fn read(&mut self, id: u8, addr: u16, len: u16) -> Result<Vec<u8>, BusError>;
+
+    fn write(&mut self, id: u8, addr: u16, data: &[u8]) -> Result<(), BusError>;
+
+    /// Reads the same block from several servos; results follow `ids` order.
+    fn sync_read(&mut self, ids: &[u8], addr: u16, len: u16) -> Result<Vec<Vec<u8>>, BusError>;
+
+    /// Writes one block per servo in a single packet; no status is returned.
+    fn sync_write(&mut self, addr: u16, len: u16, data: &[(u8, Vec<u8>)]) -> Result<(), BusError>;
+
+    fn read_register(&mut self, id: u8, reg: Register) -> Result<i32, BusError> {
+        let spec = self.control_table().spec(reg)?;
+        let bytes = self.read(id, spec.addr, spec.len as u16)?;
+        Ok(spec.decode(&bytes))
+    }
+
+    fn write_register(&mut self, id: u8, reg: Register, value: i32) -> Result<(), BusError> {
+        let spec = self.control_table().spec(reg)?;
+        self.write(id, spec.addr, &spec.encode(value))
+    }
+
+    fn sync_read_register(&mut self, ids: &[u8], reg: Register) -> Result<Vec<i32>, BusError> {
+        let spec = self.control_table().spec(reg)?;
+        let blocks = self.sync_read(ids, spec.addr, spec.len as u16)?;
+        Ok(blocks.iter().map(|b| spec.decode(b)).collect())
+    }
+
+    fn sync_write_register(&mut self, reg: Register, values: &[(u8, i32)]) -> Result<(), BusError> {
+        let spec = self.control_table().spec(reg)?;
+        let data: Vec<(u8, Vec<u8>)> = values.iter().map(|(id, v)| (*id, spec.encode(*v))).collect();
+        self.sync_write(spec.addr, spec.len as u16, &data)
+    }
+}
+
+/// Bus handle shared between request handlers; lock it from blocking code only.
+pub type SharedBus = Arc<Mutex<Box<dyn ServoBus>>>;
+
+/// Opens the bus named by `port`: a serial device path, or `sim` for an
+/// in-process six-servo arm with ids 1..=6 (STS3215 for Feetech, XL330 for
+/// Dynamixel).
+pub fn open(port: &str, baud: u32, protocol: Protocol) -> Result<Box<dyn ServoBus>, BusError> {
+    open_with_sim(port, baud, protocol, &sim::SimConfig::default(), None)
+}
+
+/// Like `open`, and also accepts `sim-pty`: the simulated arm served on a
+/// pseudo-terminal and opened like a real port. The simulated arm follows
+/// `profile` when one is given and `config` adds its imperfections.
+pub fn open_with_sim(
+    port: &str,
+    baud: u32,
+    protocol: Protocol,
+    config: &sim::SimConfig,
+    profile: Option<&crate::model::Profile>,
+) -> Result<Box<dyn ServoBus>, BusError> {
+    let simulated = || {
+        let servos = sim::arm(protocol, profile).into_iter().map(|s| s.with_baud_rate(baud)).collect();
+        sim::SimBus::with_servos(protocol, servos, baud).configure(config.clone())
+    };
+    let serial: Box<dyn serialport::SerialPort> = match port {
+        "sim" => {
+            return Ok(match protocol {
+                Protocol::Feetech => Box::new(feetech::FeetechBus::new(simulated())),
+                Protocol::Dynamixel => Box::new(dynamixel::DynamixelBus::new(simulated())),
+            });
+        }
+        #[cfg(unix)]
+        "sim-pty" => {
+            let path = sim::serve_pty(simulated())?;
+            tracing::info!(%path, "simulated arm serving on pty");
+            serialport::new(path, baud).timeout(Duration::from_millis(50)).open().map_err(io::Error::from)?
+        }
+        _ => serialport::new(port, baud).timeout(Duration::from_millis(50)).open().map_err(io::Error::from)?,
+    };
+    Ok(match protocol {
+        Protocol::Feetech => Box::new(feetech::FeetechBus::new(serial)),
+        Protocol::Dynamixel => Box::new(dynamixel::DynamixelBus::new(serial)),
+    })
+}
+
+pub fn shared(bus: Box<dyn ServoBus>) -> SharedBus {
+    Arc::new(Mutex::new(bus))
+}
+
+/// Runs `f` with exclusive access to the bus on the blocking thread pool.
+pub async fn run<R, F>(bus: &SharedBus, f: F) -> Result<R, BusError>
+where
+    R: Send + 'static,
+    F: FnOnce(&mut dyn ServoBus) -> Result<R, BusError> + Send + 'static,
+{
+    let bus = bus.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut guard = bus.lock().unwrap_or_else(|e| e.into_inner());
+        f(guard.as_mut())
+    })
+    .await
+    .map_err(|e| BusError::Io(io::Error::other(e)))?
+}
+
+/// Maps transport read failures that mean "nobody answered" to `Timeout`.
+pub(crate) fn read_error(id: u8, e: io::Error) -> BusError {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::UnexpectedEof => BusError::Timeout(id),
+        _ => BusError::Io(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_magnitude_roundtrip() {
+        let spec = RegisterSpec { addr: 31, len: 2, encoding: Encoding::SignMagnitude(11) };
+        assert_eq!(spec.encode(-1908), vec![0x74, 0x0F]);
+        for v in [-2047, -1908, -1, 0, 1, 1155, 2047] {
+            assert_eq!(spec.decode(&spec.encode(v)), v);
+        }
+    }
+
+    #[test]
+    fn signed_roundtrip() {
+        let spec = RegisterSpec { addr: 20, len: 4, encoding: Encoding::Signed };
+        assert_eq!(spec.encode(-2), vec![0xFE, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(spec.decode(&spec.encode(-1024)), -1024);
+        let short = RegisterSpec { addr: 0, len: 2, encoding: Encoding::Signed };
+        assert_eq!(short.decode(&[0xFF, 0xFF]), -1);
+    }
+
+    #[test]
+    fn unsigned_truncates_to_len() {
+        let spec = RegisterSpec { addr: 5, len: 1, encoding: Encoding::Unsigned };
+        assert_eq!(spec.encode(6), vec![6]);
+        assert_eq!(spec.decode(&[253]), 253);
+    }
+}
@@ -0,0 +1,675 @@
+//! In-memory simulated servo bus.
+//!
+//! `SimBus` is a `Read + Write` transport that decodes instruction packets,
+//! applies them to a set of simulated servos and queues the status packets a
+//! real bus would send back, so protocol code can be tested without hardware.
+//! A `SimHandle` gives tests access to the servos while the bus is in use.
+//! Servos only hear packets sent at the baud rate their `BaudRate` register
+//! selects, so scans across baud rates behave as on a real bus.
+//!
+//! `SimConfig` adds the imperfections of real hardware (position noise, reply
+//! latency, lost replies) from a seeded generator so failures reproduce, and
+//! `serve_pty` exposes a simulated bus as a Linux pseudo-terminal so the real
+//! serial port code can be exercised end to end.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::dynamixel::{self, XL330_M288, XL430_W250};
+use super::feetech::{self, STS3215};
+use super::{ControlTable, Port, Protocol, Register, BROADCAST_ID};
+use crate::model::Profile;
+
+/// First RAM address on the STS3215; everything below is EEPROM.
+const STS_EEPROM_END: u16 = 40;
+
+#[derive(Debug, Clone)]
+pub struct SimServo {
+    table: &'static ControlTable,
+    memory: Vec<u8>,
+    eeprom_end: u16,
+    /// Physical encoder position before the homing offset is applied.
+    raw_position: i32,
+    error: u8,
+    /// Motor fault: goal positions are accepted but the shaft does not move.
+    stalled: bool,
+}
+
+/// Imperfections of a simulated bus.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimConfig {
+    /// Up to this many ticks of noise, either way, on every position read.
+    pub noise: i32,
+    /// Delay before replies become readable.
+    pub latency: Duration,
+    /// Probability (0..=1) that a reply is lost.
+    pub drop_rate: f64,
+    /// Seed of the noise and drop generator.
+    pub seed: u64,
+}
+
+impl SimServo {
+    pub fn sts3215(id: u8) -> Self {
+        let mut s = Self { table: &STS3215, memory: vec![0; 256], eeprom_end: STS_EEPROM_END, raw_position: 2047, error: 0, stalled: false };
+        s.set(Register::FirmwareMajor, 3);
+        s.set(Register::FirmwareMinor, 10);
+        s.set(Register::ModelNumber, STS3215.model_number as i32);
+        s.set(Register::Id, id as i32);
+        s.set(Register::MinPositionLimit, 0);
+        s.set(Register::MaxPositionLimit, 4095);
+        s.set(Register::Lock, 1);
+        s
+    }
+
+    pub fn xl330(id: u8) -> Self {
+        Self::x_series(&XL330_M288, id)
+    }
+
+    pub fn xl430(id: u8) -> Self {
+        Self::x_series(&XL430_W250, id)
+    }
+
+    fn x_series(table: &'static ControlTable, id: u8) -> Self {
+        let mut s = Self { table, memory: vec![0; 256], eeprom_end: dynamixel::EEPROM_END, raw_position: 2047, error: 0, stalled: false };
+        s.set(Register::ModelNumber, table.model_number as i32);
+        s.set(Register::FirmwareMajor, 52);
+        s.set(Register::Id, id as i32);
+        s.set(Register::BaudRate, 3);
+        s.set(Register::MinPositionLimit, 0);
+        s.set(Register::MaxPositionLimit, 4095);
+        s
+    }
+
+    pub fn id(&self) -> u8 {
+        self.get(Register::Id) as u8
+    }
+
+    pub fn baud_rate(&self) -> u32 {
+        let index = self.get(Register::BaudRate) as usize;
+        self.table.baud_rates.get(index).copied().unwrap_or(0)
+    }
+
+    pub fn with_baud_rate(mut self, baud: u32) -> Self {
+        if let Some(index) = self.table.baud_index(baud) {
+            self.set(Register::BaudRate, index);
+        }
+        self
+    }
+
+    pub fn get(&self, reg: Register) -> i32 {
+        match self.table.get(reg) {
+            Some(spec) => {
+                let start = spec.addr as usize;
+                spec.decode(&self.memory[start..start + spec.len as usize])
+            }
+            None => 0,
+        }
+    }
+
+    pub fn set(&mut self, reg: Register, value: i32) {
+        if let Some(spec) = self.table.get(reg) {
+            let start = spec.addr as usize;
+            self.memory[start..start + spec.len as usize].copy_from_slice(&spec.encode(value));
+        }
+    }
+
+    fn refresh_present(&mut self, jitter: i32) {
+        let present = self.table.present(self.raw_position + jitter, self.get(Register::HomingOffset));
+        self.set(Register::PresentPosition, present);
+    }
+
+    fn read_mem(&mut self, addr: u16, len: u16, jitter: i32) -> Vec<u8> {
+        self.refresh_present(jitter);
+        let start = (addr as usize).min(self.memory.len());
+        let end = (start + len as usize).min(self.memory.len());
+        let mut out = self.memory[start..end].to_vec();
+        out.resize(len as usize, 0);
+        out
+    }
+
+    /// Applies a write and returns the status error it produces. Feetech
+    /// EEPROM ignores writes while `Lock` is set; Dynamixel EEPROM rejects
+    /// them with an access error while torque is on.
+    fn write_mem(&mut self, addr: u16, data: &[u8]) -> u8 {
+        let eeprom = addr < self.eeprom_end;
+        if eeprom && self.table.get(Register::Lock).is_none() && self.get(Register::TorqueEnable) != 0 {
+            return dynamixel::ERR_ACCESS;
+        }
+        let locked = self.get(Register::Lock) != 0;
+        for (i, b) in data.iter().enumerate() {
+            let a = addr as usize + i;
+            if a >= self.memory.len() || (locked && (a as u16) < self.eeprom_end) {
+                continue;
+            }
+            self.memory[a] = *b;
+        }
+        // with torque on, the servo heads for Goal_Position whenever either is written
+        let touched = |reg| self.table.get(reg).is_some_and(|s| s.addr >= addr && s.addr < addr + data.len() as u16);
+        let moved = touched(Register::GoalPosition) || touched(Register::TorqueEnable);
+        if moved && !self.stalled && self.get(Register::TorqueEnable) != 0 {
+            let min = self.get(Register::MinPositionLimit);
+            let max = self.get(Register::MaxPositionLimit);
+            let target = self.get(Register::GoalPosition).clamp(min, max);
+            self.raw_position = self.table.raw(target, self.get(Register::HomingOffset));
+        }
+        0
+    }
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    protocol: Protocol,
+    config: SimConfig,
+    rng: u64,
+    baud: u32,
+    servos: Vec<SimServo>,
+    inbound: Vec<u8>,
+    outbound: VecDeque<u8>,
+    corrupt_next: bool,
+}
+
+impl SimState {
+    fn servo_mut(&mut self, id: u8) -> Option<&mut SimServo> {
+        self.servos.iter_mut().find(|s| s.id() == id)
+    }
+
+    /// Servos listening at the current baud rate.
+    fn on_bus(&mut self) -> impl Iterator<Item = &mut SimServo> {
+        let baud = self.baud;
+        self.servos.iter_mut().filter(move |s| s.baud_rate() == baud)
+    }
+
+    fn reachable_mut(&mut self, id: u8) -> Option<&mut SimServo> {
+        self.on_bus().find(|s| s.id() == id)
+    }
+
+    /// splitmix64, uniform in 0..1.
+    fn random(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as f64 / u64::MAX as f64
+    }
+
+    fn jitter(&mut self) -> i32 {
+        let noise = self.config.noise;
+        if noise <= 0 {
+            return 0;
+        }
+        (self.random() * (2 * noise + 1) as f64).floor() as i32 - noise
+    }
+
+    /// Reads from a reachable servo, returning its error byte and the data.
+    fn read_servo(&mut self, id: u8, addr: u16, len: u16) -> Option<(u8, Vec<u8>)> {
+        let jitter = self.jitter();
+        let s = self.reachable_mut(id)?;
+        Some((s.error, s.read_mem(addr, len, jitter)))
+    }
+
+    fn respond(&mut self, mut pkt: Vec<u8>) {
+        if self.config.drop_rate > 0.0 && self.random() < self.config.drop_rate {
+            return;
+        }
+        if self.corrupt_next {
+            self.corrupt_next = false;
+            if let Some(last) = pkt.last_mut() {
+                *last = last.wrapping_add(1);
+            }
+        }
+        self.outbound.extend(pkt);
+    }
+
+    /// Consumes every complete instruction packet in the inbound buffer.
+    fn process(&mut self) {
+        match self.protocol {
+            Protocol::Feetech => self.process_feetech(),
+            Protocol::Dynamixel => self.process_dynamixel(),
+        }
+    }
+
+    fn process_feetech(&mut self) {
+        loop {
+            let Some(start) = self.inbound.windows(2).position(|w| w == [0xFF, 0xFF]) else {
+                // keep a trailing 0xFF that may start the next header
+                let keep = usize::from(self.inbound.last() == Some(&0xFF));
+                self.inbound.drain(..self.inbound.len() - keep);
+                return;
+            };
+            self.inbound.drain(..start);
+            if self.inbound.len() < 4 {
+                return;
+            }
+            let len = self.inbound[3] as usize;
+            if self.inbound.len() < 4 + len {
+                return;
+            }
+            let pkt: Vec<u8> = self.inbound.drain(..4 + len).collect();
+            if len < 2 || feetech::checksum(&pkt[2..pkt.len() - 1]) != pkt[pkt.len() - 1] {
+                // real servos ignore packets that fail the checksum
+                continue;
+            }
+            self.handle_feetech(pkt[2], pkt[4], &pkt[5..pkt.len() - 1]);
+        }
+    }
+
+    fn handle_feetech(&mut self, id: u8, instr: u8, params: &[u8]) {
+        match instr {
+            feetech::INST_PING => {
+                let targets: Vec<(u8, u8)> = self
+                    .on_bus()
+                    .filter(|s| id == BROADCAST_ID || s.id() == id)
+                    .map(|s| (s.id(), s.error))
+                    .collect();
+                for (sid, err) in targets {
+                    self.respond(feetech::encode_status(sid, err, &[]));
+                }
+            }
+            feetech::INST_READ if params.len() == 2 => {
+                if let Some((err, data)) = self.read_servo(id, params[0] as u16, params[1] as u16) {
+                    self.respond(feetech::encode_status(id, err, &data));
+                }
+            }
+            feetech::INST_WRITE if !params.is_empty() => {
+                if id == BROADCAST_ID {
+                    for s in self.on_bus() {
+                        s.write_mem(params[0] as u16, &params[1..]);
+                    }
+                } else if let Some(s) = self.reachable_mut(id) {
+                    let err = s.error;
+                    s.write_mem(params[0] as u16, &params[1..]);
+                    // an id change takes effect after the reply
+                    self.respond(feetech::encode_status(id, err, &[]));
+                }
+            }
+            feetech::INST_SYNC_READ if params.len() >= 2 => {
+                let (addr, len) = (params[0] as u16, params[1] as u16);
+                for sid in &params[2..] {
+                    if let Some((err, data)) = self.read_servo(*sid, addr, len) {
+                        self.respond(feetech::encode_status(*sid, err, &data));
+                    }
+                }
+            }
+            feetech::INST_SYNC_WRITE if params.len() >= 2 => {
+                let (addr, len) = (params[0] as u16, params[1] as usize);
+                for block in params[2..].chunks(len + 1) {
+                    if block.len() == len + 1
+                        && let Some(s) = self.reachable_mut(block[0])
+                    {
+                        s.write_mem(addr, &block[1..]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn process_dynamixel(&mut self) {
+        const HEADER: [u8; 4] = [0xFF, 0xFF, 0xFD, 0x00];
+        loop {
+            let Some(start) = self.inbound.windows(4).position(|w| w == HEADER) else {
+                // keep a trailing partial header
+                let keep = self.inbound.len().min(3);
+                self.inbound.drain(..self.inbound.len() - keep);
+                return;
+            };
+            self.inbound.drain(..start);
+            if self.inbound.len() < 7 {
+                return;
+            }
+            let len = u16::from_le_bytes([self.inbound[5], self.inbound[6]]) as usize;
+            if self.inbound.len() < 7 + len {
+                return;
+            }
+            let pkt: Vec<u8> = self.inbound.drain(..7 + len).collect();
+            // real servos ignore packets that fail the CRC
+            if let Ok((id, payload)) = dynamixel::decode(&pkt)
+                && let Some((instr, params)) = payload.split_first()
+            {
+                self.handle_dynamixel(id, *instr, params);
+            }
+        }
+    }
+
+    fn handle_dynamixel(&mut self, id: u8, instr: u8, params: &[u8]) {
+        let word = |i: usize| u16::from_le_bytes([params[i], params[i + 1]]);
+        match instr {
+            dynamixel::INST_PING => {
+                let targets: Vec<(u8, u8, Vec<u8>)> = self
+                    .on_bus()
+                    .filter(|s| id == BROADCAST_ID || s.id() == id)
+                    .map(|s| {
+                        let mut info = (s.get(Register::ModelNumber) as u16).to_le_bytes().to_vec();
+                        info.push(s.get(Register::FirmwareMajor) as u8);
+                        (s.id(), s.error, info)
+                    })
+                    .collect();
+                for (sid, err, info) in targets {
+                    self.respond(dynamixel::encode_status(sid, err, &info));
+                }
+            }
+            dynamixel::INST_READ if params.len() == 4 => {
+                if let Some((err, data)) = self.read_servo(id, word(0), word(2)) {
+                    self.respond(dynamixel::encode_status(id, err, &data));
+                }
+            }
+            dynamixel::INST_WRITE if params.len() > 2 => {
+                if id == BROADCAST_ID {
+                    for s in self.on_bus() {
+                        s.write_mem(word(0), &params[2..]);
+                    }
+                } else if let Some(s) = self.reachable_mut(id) {
+                    let err = s.error | s.write_mem(word(0), &params[2..]);
+                    self.respond(dynamixel::encode_status(id, err, &[]));
+                }
+            }
+            dynamixel::INST_SYNC_READ if params.len() >= 4 => {
+                let (addr, len) = (word(0), word(2));
+                for sid in &params[4..] {
+                    if let Some((err, data)) = self.read_servo(*sid, addr, len) {
+                        self.respond(dynamixel::encode_status(*sid, err, &data));
+                    }
+                }
+            }
+            dynamixel::INST_SYNC_WRITE if params.len() >= 4 => {
+                let (addr, len) = (word(0), word(2) as usize);
+                for block in params[4..].chunks(len + 1) {
+                    if block.len() == len + 1
+                        && let Some(s) = self.reachable_mut(block[0])
+                    {
+                        s.write_mem(addr, &block[1..]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Six servos standing in for one arm, with ids 1..=6, or shaped after
+/// `profile`: its ids, homing offsets, position limits and drive mode (where
+/// the model stores one), each resting in the middle of its range.
+pub fn arm(protocol: Protocol, profile: Option<&Profile>) -> Vec<SimServo> {
+    let make = match protocol {
+        Protocol::Feetech => SimServo::sts3215,
+        Protocol::Dynamixel => SimServo::xl330,
+    };
+    let Some(profile) = profile else {
+        return (1..=6).map(make).collect();
+    };
+    let mut joints: Vec<_> = profile.0.values().collect();
+    joints.sort_by_key(|j| j.id);
+    joints
+        .into_iter()
+        .map(|j| {
+            let mut s = make(j.id as u8);
+            s.set(Register::HomingOffset, j.homing_offset);
+            s.set(Register::MinPositionLimit, j.range_min);
+            s.set(Register::MaxPositionLimit, j.range_max);
+            s.set(Register::DriveMode, j.drive_mode);
+            s.raw_position = s.table.raw((j.range_min + j.range_max) / 2, j.homing_offset);
+            s
+        })
+        .collect()
+}
+
+/// Simulated bus transport; pass it to the matching protocol implementation,
+/// `FeetechBus::new` or `DynamixelBus::new`.
+pub struct SimBus {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl SimBus {
+    pub fn feetech(servos: Vec<SimServo>) -> Self {
+        Self::new(Protocol::Feetech, servos)
+    }
+
+    pub fn dynamixel(servos: Vec<SimServo>) -> Self {
+        Self::new(Protocol::Dynamixel, servos)
+    }
+
+    fn new(protocol: Protocol, servos: Vec<SimServo>) -> Self {
+        let state = SimState { protocol, baud: 1_000_000, servos, ..Default::default() };
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Simulated bus for `protocol` at `baud`, e.g. an arm from `arm`.
+    pub fn with_servos(protocol: Protocol, servos: Vec<SimServo>, baud: u32) -> Self {
+        let bus = Self::new(protocol, servos);
+        lock(&bus.state).baud = baud;
+        bus
+    }
+
+    pub fn configure(self, config: SimConfig) -> Self {
+        self.handle().set_config(config);
+        self
+    }
+
+    pub fn handle(&self) -> SimHandle {
+        SimHandle { state: self.state.clone() }
+    }
+}
+
+fn lock(state: &Mutex<SimState>) -> MutexGuard<'_, SimState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Port for SimBus {
+    fn baud_rate(&self) -> io::Result<u32> {
+        Ok(lock(&self.state).baud)
+    }
+
+    fn set_baud_rate(&mut self, baud: u32) -> io::Result<()> {
+        let mut st = lock(&self.state);
+        st.baud = baud;
+        st.inbound.clear();
+        Ok(())
+    }
+}
+
+impl Read for SimBus {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut st = lock(&self.state);
+        let n = buf.len().min(st.outbound.len());
+        for (dst, src) in buf.iter_mut().zip(st.outbound.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SimBus {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut st = lock(&self.state);
+        let queued = st.outbound.len();
+        st.inbound.extend_from_slice(buf);
+        st.process();
+        let latency = if st.outbound.len() > queued { st.config.latency } else { Duration::ZERO };
+        drop(st);
+        if !latency.is_zero() {
+            std::thread::sleep(latency);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Shared access to the servos behind a `SimBus`.
+///
+/// Methods taking an `id` panic if no simulated servo currently has that id.
+#[derive(Clone)]
+pub struct SimHandle {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl SimHandle {
+    fn with_servo<R>(&self, id: u8, f: impl FnOnce(&mut SimServo) -> R) -> R {
+        let mut st = lock(&self.state);
+        let servo = st.servo_mut(id).unwrap_or_else(|| panic!("no simulated servo with id {}", id));
+        f(servo)
+    }
+
+    pub fn set_raw_position(&self, id: u8, pos: i32) {
+        self.with_servo(id, |s| s.raw_position = pos);
+    }
+
+    pub fn raw_position(&self, id: u8) -> i32 {
+        self.with_servo(id, |s| s.raw_position)
+    }
+
+    pub fn register(&self, id: u8, reg: Register) -> i32 {
+        self.with_servo(id, |s| {
+            s.refresh_present(0);
+            s.get(reg)
+        })
+    }
+
+    pub fn set_register(&self, id: u8, reg: Register, value: i32) {
+        self.with_servo(id, |s| s.set(reg, value));
+    }
+
+    /// Error byte reported in every status packet from this servo.
+    pub fn set_error(&self, id: u8, code: u8) {
+        self.with_servo(id, |s| s.error = code);
+    }
+
+    /// Motor fault: the servo keeps answering but no longer moves.
+    pub fn set_stalled(&self, id: u8, stalled: bool) {
+        self.with_servo(id, |s| s.stalled = stalled);
+    }
+
+    /// Replaces the noise/latency/drop settings and reseeds the generator.
+    pub fn set_config(&self, config: SimConfig) {
+        let mut st = lock(&self.state);
+        st.rng = config.seed;
+        st.config = config;
+    }
+
+    /// Breaks the checksum of the next status packet sent by any servo.
+    pub fn corrupt_next_response(&self) {
+        lock(&self.state).corrupt_next = true;
+    }
+
+    /// Connects another servo, e.g. to plug motors in one at a time.
+    pub fn connect(&self, servo: SimServo) {
+        lock(&self.state).servos.push(servo);
+    }
+
+    /// Unplugs every servo with `id`.
+    pub fn disconnect(&self, id: u8) {
+        lock(&self.state).servos.retain(|s| s.id() != id);
+    }
+
+    pub fn ids(&self) -> Vec<u8> {
+        lock(&self.state).servos.iter().map(SimServo::id).collect()
+    }
+}
+
+/// Serves `sim` on a new pseudo-terminal and returns the path of its slave
+/// side (e.g. `/dev/pts/3`), which any serial port client can open. A
+/// background thread shuttles bytes for the life of the process and follows
+/// baud rate changes the client makes on the port.
+#[cfg(unix)]
+pub fn serve_pty(mut sim: SimBus) -> io::Result<String> {
+    use serialport::{SerialPort, TTYPort};
+
+    let (mut master, slave) = TTYPort::pair().map_err(io::Error::from)?;
+    let path = slave.name().ok_or_else(|| io::Error::other("pty has no name"))?;
+    master.set_timeout(Duration::from_millis(20)).map_err(io::Error::from)?;
+    std::thread::Builder::new().name("sim-pty".into()).spawn(move || {
+        // keeping the slave open stops the master from failing while no
+        // client is attached
+        let slave = slave;
+        let mut buf = [0u8; 256];
+        loop {
+            match master.read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    if let Ok(baud) = slave.baud_rate()
+                        && lock(&sim.state).baud != baud
+                    {
+                        let _ = sim.set_baud_rate(baud);
+                    }
+                    let _ = sim.write_all(&buf[..n]);
+                    let mut out = Vec::new();
+                    let _ = sim.read_to_end(&mut out);
+                    if !out.is_empty() && master.write_all(&out).is_err() {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::{BusError, ServoBus};
+    use crate::model::Joint;
+
+    fn profile() -> Profile {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("shoulder_pan".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: -1921, range_min: 767, range_max: 3201, ..Default::default() });
+        map.insert("gripper".to_string(), Joint { id: 6, drive_mode: 0, homing_offset: 1155, range_min: 2038, range_max: 3538, ..Default::default() });
+        Profile::new(map)
+    }
+
+    #[test]
+    fn arm_is_seeded_from_profile() {
+        let mut bus = FeetechBus::new(SimBus::feetech(arm(Protocol::Feetech, Some(&profile()))));
+        assert_eq!(bus.read_register(6, Register::MaxPositionLimit).unwrap(), 3538);
+        assert_eq!(bus.read_register(1, Register::HomingOffset).unwrap(), -1921);
+        assert_eq!(bus.read_register(1, Register::PresentPosition).unwrap(), (767 + 3201) / 2);
+        assert_eq!(arm(Protocol::Dynamixel, None).len(), 6);
+    }
+
+    #[test]
+    fn noise_drops_and_faults_are_reproducible() {
+        let config = SimConfig { noise: 5, drop_rate: 0.3, seed: 42, ..Default::default() };
+        let run = || {
+            let sim = SimBus::feetech(arm(Protocol::Feetech, None)).configure(config.clone());
+            let mut bus = FeetechBus::new(sim);
+            (0..50).map(|_| bus.read_register(1, Register::PresentPosition).ok()).collect::<Vec<_>>()
+        };
+        let reads = run();
+        assert_eq!(reads, run());
+        let ok: Vec<i32> = reads.iter().flatten().copied().collect();
+        assert!(ok.len() > 20 && ok.len() < 50, "{} of 50 replies", ok.len());
+        assert!(ok.iter().all(|p| (2042..=2052).contains(p)));
+        assert!(ok.iter().any(|p| *p != 2047));
+
+        let sim = SimBus::feetech(arm(Protocol::Feetech, None));
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        handle.set_stalled(2, true);
+        bus.write_register(2, Register::GoalPosition, 3000).unwrap();
+        bus.write_register(2, Register::TorqueEnable, 1).unwrap();
+        assert_eq!(handle.raw_position(2), 2047);
+        handle.set_error(3, 0x20);
+        assert!(matches!(bus.ping(3), Err(BusError::Status { code: 0x20, .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pty_carries_real_serial_traffic() {
+        let path = serve_pty(SimBus::feetech(arm(Protocol::Feetech, Some(&profile())))).unwrap();
+        let port = serialport::new(&path, 1_000_000).timeout(Duration::from_millis(200)).open().unwrap();
+        let mut bus = FeetechBus::new(port);
+        bus.ping(6).unwrap();
+        assert_eq!(bus.read_register(6, Register::MinPositionLimit).unwrap(), 2038);
+        assert!(matches!(bus.ping(4), Err(BusError::Timeout(4))));
+        // the simulator follows the port's baud rate
+        bus.set_baud_rate(115_200).unwrap();
+        assert!(matches!(bus.ping(6), Err(BusError::Timeout(6))));
+    }
+}
@@ -0,0 +1,80 @@
+//! Direct joint control: torque on/off and goal positions.
+//!
+//! Goals are clamped to the joint's `range_min..=range_max` from the stored
+//! profile, so a motor is never commanded outside its calibrated range.
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::bus::{BusError, Register, ServoBus, BROADCAST_ID};
+use crate::model::{Joint, Profile};
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct GoalResult {
+    pub joint: String,
+    pub id: u8,
+    pub requested: i32,
+    /// Position actually sent, after clamping.
+    pub goal: i32,
+    pub clamped: bool,
+}
+
+/// The joint of `profile` driven by servo `id`.
+pub fn joint_by_id(profile: &Profile, id: u8) -> Option<(&String, &Joint)> {
+    profile.0.iter().find(|(_, j)| j.id == id as i32)
+}
+
+pub fn set_torque(bus: &mut dyn ServoBus, id: u8, enabled: bool) -> Result<(), BusError> {
+    bus.write_register(id, Register::TorqueEnable, enabled as i32)?;
+    info!(id, enabled, "torque set");
+    Ok(())
+}
+
+/// Sends `position`, clamped to the joint's range, then enables torque so
+/// the servo moves there.
+pub fn move_to(bus: &mut dyn ServoBus, name: &str, joint: &Joint, position: i32) -> Result<GoalResult, BusError> {
+    let (lo, hi) = (joint.range_min.min(joint.range_max), joint.range_max.max(joint.range_min));
+    let goal = position.clamp(lo, hi);
+    let id = joint.id as u8;
+    bus.write_register(id, Register::GoalPosition, goal)?;
+    bus.write_register(id, Register::TorqueEnable, 1)?;
+    info!(joint = name, id, requested = position, goal, "goal position sent");
+    Ok(GoalResult { joint: name.to_string(), id, requested: position, goal, clamped: goal != position })
+}
+
+/// Emergency stop: one broadcast write disabling torque on every servo on
+/// the bus, whichever profile it belongs to. There is no reply to check.
+pub fn torque_off_all(bus: &mut dyn ServoBus) -> Result<(), BusError> {
+    bus.write_register(BROADCAST_ID, Register::TorqueEnable, 0)?;
+    warn!("torque disabled on all servos");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::sim::{SimBus, SimServo};
+
+    #[test]
+    fn goal_is_clamped_to_range() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        let joint = Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1000, range_max: 3000, ..Default::default() };
+
+        let r = move_to(&mut bus, "elbow_flex", &joint, 3500).unwrap();
+        assert_eq!((r.goal, r.clamped), (3000, true));
+        assert_eq!(handle.register(1, Register::TorqueEnable), 1);
+        assert_eq!(handle.register(1, Register::PresentPosition), 3000);
+
+        let r = move_to(&mut bus, "elbow_flex", &joint, 1200).unwrap();
+        assert!(!r.clamped);
+        assert_eq!(handle.register(1, Register::PresentPosition), 1200);
+
+        set_torque(&mut bus, 2, true).unwrap();
+        torque_off_all(&mut bus).unwrap();
+        assert_eq!(handle.register(1, Register::TorqueEnable), 0);
+        assert_eq!(handle.register(2, Register::TorqueEnable), 0);
+    }
+}
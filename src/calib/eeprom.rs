@@ -0,0 +1,266 @@
+//! Pushing a profile's calibration into servo EEPROM.
+//!
+//! `plan` turns a profile into the exact register writes (unlock, offset,
+//! limits, drive mode where the model has one, relock; Dynamixel servos have
+//! no lock and get a torque-off instead) without touching the
+//! bus, which doubles as the dry run. `apply` sends them joint by joint and
+//! reads everything back to verify.
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::bus::{ControlTable, Register, ServoBus};
+use crate::model::Profile;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RegisterWrite {
+    pub joint: String,
+    pub id: u8,
+    pub register: String,
+    pub addr: u16,
+    pub value: i32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct Plan {
+    pub writes: Vec<RegisterWrite>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Mismatch {
+    pub field: &'static str,
+    pub expected: i32,
+    pub actual: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JointVerification {
+    pub joint: String,
+    pub id: u8,
+    pub ok: bool,
+    pub mismatches: Vec<Mismatch>,
+    /// Non-default fields the servo model has no register for.
+    pub not_written: Vec<&'static str>,
+    pub error: Option<String>,
+    /// Set when relocking (or restoring torque) after the writes failed.
+    pub relock_error: Option<String>,
+}
+
+/// Calibration fields and the register each one lives in.
+fn fields(profile_joint: &crate::model::Joint) -> [(&'static str, Register, i32); 4] {
+    [
+        ("homing_offset", Register::HomingOffset, profile_joint.homing_offset),
+        ("range_min", Register::MinPositionLimit, profile_joint.range_min),
+        ("range_max", Register::MaxPositionLimit, profile_joint.range_max),
+        ("drive_mode", Register::DriveMode, profile_joint.drive_mode),
+    ]
+}
+
+fn sorted_joints(profile: &Profile) -> Vec<(&String, &crate::model::Joint)> {
+    let mut joints: Vec<_> = profile.0.iter().collect();
+    joints.sort_by_key(|(_, j)| j.id);
+    joints
+}
+
+pub fn plan(table: &ControlTable, profile: &Profile) -> Plan {
+    let mut plan = Plan::default();
+    let lock = table.get(Register::Lock);
+    for (name, joint) in sorted_joints(profile) {
+        let id = joint.id as u8;
+        let mut push = |register: Register, value: i32| {
+            if let Some(spec) = table.get(register) {
+                plan.writes.push(RegisterWrite {
+                    joint: name.clone(),
+                    id,
+                    register: format!("{:?}", register),
+                    addr: spec.addr,
+                    value,
+                    bytes: spec.encode(value),
+                });
+            }
+        };
+        if lock.is_some() {
+            push(Register::Lock, 0);
+        } else {
+            // EEPROM without a lock register is only writable with torque off
+            push(Register::TorqueEnable, 0);
+        }
+        for (field, register, value) in fields(joint) {
+            if table.get(register).is_some() {
+                push(register, value);
+            } else if value != 0 {
+                plan.warnings.push(format!("{}: {} is not stored on {} servos; value {} not written", name, field, table.model, value));
+            }
+        }
+        if lock.is_some() {
+            push(Register::Lock, 1);
+        }
+    }
+    plan
+}
+
+/// Executes `plan` then verifies every joint against `profile`. A joint whose
+/// writes fail is reported and its remaining calibration writes are skipped,
+/// but it is still relocked (and torque restored on Dynamixel) so a failure
+/// never leaves the EEPROM open; a failed relock is reported on its own.
+pub fn apply(bus: &mut dyn ServoBus, profile: &Profile, plan: &Plan) -> Vec<JointVerification> {
+    let mut failed: Vec<(u8, String)> = Vec::new();
+    let mut relock_failed: Vec<(u8, String)> = Vec::new();
+    let mut torque_on: Vec<u8> = Vec::new();
+    for w in &plan.writes {
+        let relock = w.register == "Lock" && w.value == 1;
+        if !relock && failed.iter().any(|(id, _)| *id == w.id) {
+            continue;
+        }
+        if w.register == "TorqueEnable" && w.value == 0 && matches!(bus.read_register(w.id, Register::TorqueEnable), Ok(t) if t != 0) {
+            torque_on.push(w.id);
+        }
+        if let Err(e) = bus.write(w.id, w.addr, &w.bytes) {
+            warn!(id = w.id, register = %w.register, error = %e, "eeprom write failed");
+            if relock { relock_failed.push((w.id, e.to_string())) } else { failed.push((w.id, e.to_string())) }
+        }
+    }
+    for id in torque_on {
+        if let Err(e) = bus.write_register(id, Register::TorqueEnable, 1) {
+            warn!(id, error = %e, "torque restore failed");
+            relock_failed.push((id, e.to_string()));
+        }
+    }
+    let table = bus.control_table();
+    let report: Vec<JointVerification> = sorted_joints(profile)
+        .into_iter()
+        .map(|(name, joint)| {
+            let id = joint.id as u8;
+            let mut v = JointVerification { joint: name.clone(), id, ok: false, mismatches: Vec::new(), not_written: Vec::new(), error: None, relock_error: None };
+            v.relock_error = relock_failed.iter().find(|(fid, _)| *fid == id).map(|(_, e)| e.clone());
+            if let Some((_, e)) = failed.iter().find(|(fid, _)| *fid == id) {
+                v.error = Some(e.clone());
+                return v;
+            }
+            for (field, register, expected) in fields(joint) {
+                if table.get(register).is_none() {
+                    if expected != 0 {
+                        v.not_written.push(field);
+                    }
+                    continue;
+                }
+                match bus.read_register(id, register) {
+                    Ok(actual) if actual == expected => {}
+                    Ok(actual) => v.mismatches.push(Mismatch { field, expected, actual }),
+                    Err(e) => {
+                        v.error = Some(e.to_string());
+                        break;
+                    }
+                }
+            }
+            v.ok = v.error.is_none() && v.relock_error.is_none() && v.mismatches.is_empty() && v.not_written.is_empty();
+            v
+        })
+        .collect();
+    info!(joints = report.len(), ok = report.iter().filter(|v| v.ok).count(), "eeprom apply finished");
+    report
+}
+
+/// Convenience for callers that do not need to inspect the plan first.
+pub fn plan_and_apply(bus: &mut dyn ServoBus, profile: &Profile) -> (Plan, Vec<JointVerification>) {
+    let plan = plan(bus.control_table(), profile);
+    let report = apply(bus, profile, &plan);
+    (plan, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::dynamixel::DynamixelBus;
+    use crate::bus::feetech::{FeetechBus, STS3215};
+    use crate::bus::sim::{SimBus, SimServo};
+    use crate::model::Joint;
+
+    fn profile() -> Profile {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("shoulder_pan".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: -1921, range_min: 767, range_max: 3201, ..Default::default() });
+        map.insert("gripper".to_string(), Joint { id: 2, drive_mode: 1, homing_offset: 1155, range_min: 2038, range_max: 3538, ..Default::default() });
+        Profile::new(map)
+    }
+
+    #[test]
+    fn dry_run_lists_writes() {
+        let plan = plan(&STS3215, &profile());
+        // unlock + offset + min + max + relock per joint; no drive mode register on STS
+        assert_eq!(plan.writes.len(), 10);
+        assert_eq!(plan.writes[0].register, "Lock");
+        assert_eq!(plan.writes[1].register, "HomingOffset");
+        assert_eq!(plan.writes[1].bytes, vec![0x81, 0x0F]);
+        assert_eq!(plan.writes[4].value, 1);
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].starts_with("gripper: drive_mode"));
+    }
+
+    #[test]
+    fn apply_writes_and_verifies() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        let (_, report) = plan_and_apply(&mut bus, &profile());
+        assert!(report[0].ok, "{:?}", report);
+        // STS servos have no drive mode register, so the gripper is not fully applied
+        assert!(!report[1].ok);
+        assert_eq!(report[1].not_written, vec!["drive_mode"]);
+        assert!(report[1].mismatches.is_empty() && report[1].error.is_none());
+        assert_eq!(handle.register(1, Register::HomingOffset), -1921);
+        assert_eq!(handle.register(2, Register::MaxPositionLimit), 3538);
+        assert_eq!(handle.register(2, Register::Lock), 1);
+    }
+
+    #[test]
+    fn apply_reports_missing_and_locked_servos() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1)]);
+        let mut bus = FeetechBus::new(sim);
+        let p = profile();
+        // skip the unlock so the EEPROM keeps its old values
+        let mut plan = plan(&STS3215, &p);
+        plan.writes.retain(|w| !(w.register == "Lock" && w.value == 0));
+        let report = apply(&mut bus, &p, &plan);
+        assert!(!report[0].ok);
+        assert_eq!(report[0].mismatches[0], Mismatch { field: "homing_offset", expected: -1921, actual: 0 });
+        assert!(report[1].error.as_deref().unwrap().contains("servo 2"));
+        assert!(report[1].relock_error.is_some());
+    }
+
+    #[test]
+    fn failed_write_still_relocks() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        // the unlock reaches servo 1 but its reply is garbled
+        handle.corrupt_next_response();
+        let (_, report) = plan_and_apply(&mut bus, &profile());
+        assert!(report[0].error.is_some());
+        assert!(report[0].relock_error.is_none());
+        // the calibration writes were skipped but the servo was locked again
+        assert_eq!(handle.register(1, Register::HomingOffset), 0);
+        assert_eq!(handle.register(1, Register::Lock), 1);
+        assert_eq!(handle.register(2, Register::HomingOffset), 1155);
+    }
+
+    #[test]
+    fn dynamixel_plan_disables_torque() {
+        let sim = SimBus::dynamixel(vec![SimServo::xl330(1), SimServo::xl330(2)]);
+        let handle = sim.handle();
+        handle.set_register(2, Register::TorqueEnable, 1);
+        let mut bus = DynamixelBus::new(sim);
+        let (plan, report) = plan_and_apply(&mut bus, &profile());
+        // torque off + offset + min + max + drive mode per joint
+        assert_eq!(plan.writes.len(), 10);
+        assert_eq!(plan.writes[0].register, "TorqueEnable");
+        assert!(plan.warnings.is_empty());
+        assert!(report.iter().all(|v| v.ok), "{:?}", report);
+        assert_eq!(handle.register(2, Register::DriveMode), 1);
+        assert_eq!(handle.register(1, Register::HomingOffset), -1921);
+        // torque comes back on only where it was on before
+        assert_eq!(handle.register(2, Register::TorqueEnable), 1);
+        assert_eq!(handle.register(1, Register::TorqueEnable), 0);
+    }
+}
@@ -0,0 +1,173 @@
+//! Homing offset computation.
+//!
+//! With the arm held in its middle pose, every joint should read half a turn
+//! (2047 on a 4096-tick servo). As in LeRobot, the offset is chosen so the
+//! raw encoder position of that pose reports as the half turn: `raw - 2047`
+//! on Feetech, which subtracts the offset, and `2047 - raw` on Dynamixel.
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::bus::{BusError, Register, ServoBus};
+use crate::model::Profile;
+use crate::store::{Kind, Origin, Store, StoreError};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HomingEntry {
+    pub name: String,
+    pub id: u8,
+    /// Encoder position with no offset applied.
+    pub raw: i32,
+    /// Offset stored in the profile today.
+    pub stored_offset: i32,
+    pub new_offset: i32,
+    pub delta: i32,
+}
+
+/// How far, in ticks, a joint may move between preview and apply.
+pub const POSE_TOLERANCE: i32 = 20;
+
+/// A joint whose pose no longer matches the preview; `None` where a side
+/// lacks the joint.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Drift {
+    pub name: String,
+    pub previewed: Option<i32>,
+    pub current: Option<i32>,
+}
+
+pub fn half_turn(resolution: u32) -> i32 {
+    resolution as i32 / 2 - 1
+}
+
+/// Reads the current pose and computes new offsets for every joint of
+/// `profile`, ordered by servo id. Nothing is written to the servos.
+pub fn preview(bus: &mut dyn ServoBus, profile: &Profile) -> Result<Vec<HomingEntry>, BusError> {
+    let table = bus.control_table();
+    let half = half_turn(table.resolution);
+    let mut joints: Vec<(&String, u8, i32)> = profile.0.iter().map(|(n, j)| (n, j.id as u8, j.homing_offset)).collect();
+    joints.sort_by_key(|(_, id, _)| *id);
+    let ids: Vec<u8> = joints.iter().map(|(_, id, _)| *id).collect();
+    let present = bus.sync_read_register(&ids, Register::PresentPosition)?;
+    let hw_offsets = bus.sync_read_register(&ids, Register::HomingOffset)?;
+    Ok(joints
+        .into_iter()
+        .zip(present.into_iter().zip(hw_offsets))
+        .map(|((name, id, stored), (pos, hw))| {
+            let raw = table.raw(pos, hw);
+            let new_offset = table.offset_for(raw, half);
+            HomingEntry { name: name.clone(), id, raw, stored_offset: stored, new_offset, delta: new_offset - stored }
+        })
+        .collect())
+}
+
+/// Joints of `previewed` whose raw position in `current` moved more than
+/// `tolerance` ticks, or that are missing from either side.
+pub fn drift(previewed: &[HomingEntry], current: &[HomingEntry], tolerance: i32) -> Vec<Drift> {
+    let mut drifted = Vec::new();
+    for p in previewed {
+        match current.iter().find(|c| c.name == p.name) {
+            Some(c) if c.id == p.id && (c.raw - p.raw).abs() <= tolerance => {}
+            c => drifted.push(Drift { name: p.name.clone(), previewed: Some(p.raw), current: c.map(|c| c.raw) }),
+        }
+    }
+    for c in current.iter().filter(|c| !previewed.iter().any(|p| p.name == c.name)) {
+        drifted.push(Drift { name: c.name.clone(), previewed: None, current: Some(c.raw) });
+    }
+    drifted
+}
+
+/// Writes the previewed offsets into the stored profile in a single save,
+/// provided it is still at `version`, the one the preview was computed from.
+/// Returns the saved profile and its new version.
+pub fn apply(store: &Store, kind: Kind, name: &str, entries: &[HomingEntry], version: &str) -> Result<(Profile, String), StoreError> {
+    let (mut profile, _) = store.read_profile_versioned(kind, name)?;
+    for e in entries {
+        let joint = profile
+            .0
+            .get_mut(&e.name)
+            .ok_or_else(|| StoreError::Validation(format!("joint `{}` not in profile", e.name)))?;
+        joint.homing_offset = e.new_offset;
+    }
+    let (_, version) = store.write_profile_if(kind, name, &profile, true, Origin::new("homing"), Some(version))?;
+    info!(kind = kind.as_str(), name, joints = entries.len(), "homing offsets applied");
+    Ok((profile, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::dynamixel::DynamixelBus;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::sim::{SimBus, SimServo};
+    use crate::model::Joint;
+    use crate::store::StoreError;
+
+    #[test]
+    fn preview_uses_raw_position() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        handle.set_raw_position(1, 139);
+        handle.set_raw_position(2, 3202);
+        // servo 2 already carries an offset; the preview must see through it
+        handle.set_register(2, Register::HomingOffset, 500);
+        let mut bus = FeetechBus::new(sim);
+
+        let mut map = indexmap::IndexMap::new();
+        map.insert("shoulder_pan".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: -1921, range_min: 767, range_max: 3201, ..Default::default() });
+        map.insert("gripper".to_string(), Joint { id: 2, drive_mode: 0, homing_offset: 1155, range_min: 2038, range_max: 3538, ..Default::default() });
+        let entries = preview(&mut bus, &Profile::new(map)).unwrap();
+
+        assert_eq!(entries[0].name, "shoulder_pan");
+        assert_eq!((entries[0].raw, entries[0].new_offset, entries[0].delta), (139, -1908, 13));
+        assert_eq!((entries[1].raw, entries[1].new_offset), (3202, 1155));
+        assert_eq!(entries[1].delta, 0);
+    }
+
+    #[test]
+    fn preview_on_dynamixel_adds_offset() {
+        let sim = SimBus::dynamixel(vec![SimServo::xl330(1)]);
+        let handle = sim.handle();
+        handle.set_raw_position(1, 1000);
+        handle.set_register(1, Register::HomingOffset, 300);
+        let mut bus = DynamixelBus::new(sim);
+
+        let mut map = indexmap::IndexMap::new();
+        map.insert("wrist_roll".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() });
+        let entries = preview(&mut bus, &Profile::new(map)).unwrap();
+        assert_eq!((entries[0].raw, entries[0].new_offset), (1000, 1047));
+    }
+
+    #[test]
+    fn drift_flags_moved_and_missing_joints() {
+        let entry = |name: &str, id, raw| HomingEntry { name: name.into(), id, raw, stored_offset: 0, new_offset: raw - 2047, delta: raw - 2047 };
+        let previewed = [entry("pan", 1, 2000), entry("lift", 2, 2100), entry("gripper", 6, 1500)];
+        let current = [entry("pan", 1, 2000 + POSE_TOLERANCE), entry("lift", 2, 2300), entry("wrist", 5, 2047)];
+        let drifted = drift(&previewed, &current, POSE_TOLERANCE);
+        let names: Vec<(&str, Option<i32>, Option<i32>)> = drifted.iter().map(|d| (d.name.as_str(), d.previewed, d.current)).collect();
+        assert_eq!(names, [("lift", Some(2100), Some(2300)), ("gripper", Some(1500), None), ("wrist", None, Some(2047))]);
+        assert!(drift(&previewed, &previewed, 0).is_empty());
+    }
+
+    #[test]
+    fn apply_refuses_a_profile_changed_since_the_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let mut map = indexmap::IndexMap::new();
+        map.insert("gripper".to_string(), Joint { id: 1, range_max: 4095, ..Default::default() });
+        let mut profile = Profile::new(map);
+        store.write_profile(Kind::Robots, "arm", &profile, false).unwrap();
+        let (_, version) = store.read_profile_versioned(Kind::Robots, "arm").unwrap();
+        let entries = [HomingEntry { name: "gripper".into(), id: 1, raw: 100, stored_offset: 0, new_offset: -1947, delta: -1947 }];
+
+        profile.0.get_mut("gripper").unwrap().range_min = 10;
+        store.write_profile(Kind::Robots, "arm", &profile, false).unwrap();
+        assert!(matches!(apply(&store, Kind::Robots, "arm", &entries, &version), Err(StoreError::VersionMismatch { .. })));
+        assert_eq!(store.read_profile(Kind::Robots, "arm").unwrap().0["gripper"].homing_offset, 0);
+
+        let (_, version) = store.read_profile_versioned(Kind::Robots, "arm").unwrap();
+        let (applied, new_version) = apply(&store, Kind::Robots, "arm", &entries, &version).unwrap();
+        assert_eq!(applied.0["gripper"].homing_offset, -1947);
+        assert_eq!(store.version(Kind::Robots, "arm").unwrap(), Some(new_version));
+    }
+}
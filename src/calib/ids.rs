@@ -0,0 +1,199 @@
+//! Servo discovery and id assignment.
+//!
+//! New servos all ship with the same id (1 on STS3215 and Dynamixel X), so
+//! they are set up one at a time: connect a single motor, `assign` finds it
+//! at whatever baud rate it uses and moves it to the id the profile expects
+//! for that joint, at the bus's own baud rate.
+
+use serde::Serialize;
+use thiserror::Error;
+use tracing::info;
+
+use crate::bus::{self, BusError, Register, ServoBus};
+use crate::model::Profile;
+
+/// A servo that answered a scan.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Device {
+    pub baud: u32,
+    pub id: u8,
+    pub model_number: u16,
+    /// Known model name for `model_number`.
+    pub model: Option<&'static str>,
+    pub firmware: i32,
+    /// Set when the servo answered the ping but its info could not be read.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Assignment {
+    pub joint: String,
+    pub device: Device,
+    pub id: u8,
+    pub baud: u32,
+    /// False when the servo already had the right id and baud rate.
+    pub changed: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum AssignError {
+    #[error("{0}")]
+    Bus(#[from] BusError),
+    #[error("joint `{0}` not in profile")]
+    UnknownJoint(String),
+    #[error("joint `{joint}` has id {id}, which cannot be assigned (0..=252)")]
+    InvalidId { joint: String, id: i32 },
+    #[error("no servo found; connect the `{0}` motor")]
+    NoDevice(String),
+    #[error("{0} servos found; connect only one motor at a time")]
+    Multiple(usize),
+    #[error("servo did not answer as id {id} at {baud} baud after the change")]
+    NotVerified { id: u8, baud: u32 },
+}
+
+fn identify(bus: &mut dyn ServoBus, baud: u32, id: u8) -> Device {
+    let mut device = Device { baud, id, model_number: 0, model: None, firmware: 0, error: None };
+    let info = bus
+        .read_register(id, Register::ModelNumber)
+        .and_then(|m| Ok((m, bus.read_register(id, Register::FirmwareMajor)?)));
+    match info {
+        Ok((model, firmware)) => {
+            device.model_number = model as u16;
+            device.model = bus::model_by_number(model as u16).map(|t| t.model);
+            device.firmware = firmware;
+        }
+        Err(e) => device.error = Some(e.to_string()),
+    }
+    device
+}
+
+/// Broadcast-pings at every rate in `bauds` (every rate the bus's model
+/// supports when empty) and identifies each servo that answers. The bus is
+/// switched back to its original rate afterwards.
+pub fn scan(bus: &mut dyn ServoBus, bauds: &[u32]) -> Result<Vec<Device>, BusError> {
+    let original = bus.baud_rate()?;
+    let bauds = if bauds.is_empty() { bus.control_table().baud_rates } else { bauds };
+    let mut found = Vec::new();
+    let result = bauds.iter().try_for_each(|baud| {
+        bus.set_baud_rate(*baud)?;
+        let mut ids = bus.broadcast_ping()?;
+        ids.sort_unstable();
+        ids.dedup();
+        found.extend(ids.into_iter().map(|id| identify(bus, *baud, id)));
+        Ok(())
+    });
+    bus.set_baud_rate(original)?;
+    result.map(|_| found)
+}
+
+/// Finds the single connected servo and gives it the id `profile` expects
+/// for `joint`, also moving it to the bus's current baud rate.
+pub fn assign(bus: &mut dyn ServoBus, profile: &Profile, joint: &str, bauds: &[u32]) -> Result<Assignment, AssignError> {
+    let expected = profile.0.get(joint).ok_or_else(|| AssignError::UnknownJoint(joint.to_string()))?.id;
+    let id = u8::try_from(expected)
+        .ok()
+        .filter(|id| *id < bus::BROADCAST_ID - 1)
+        .ok_or_else(|| AssignError::InvalidId { joint: joint.to_string(), id: expected })?;
+    let target_baud = bus.baud_rate()?;
+    let mut devices = scan(bus, bauds)?;
+    let device = match devices.len() {
+        0 => return Err(AssignError::NoDevice(joint.to_string())),
+        1 => devices.remove(0),
+        n => return Err(AssignError::Multiple(n)),
+    };
+    let changed = device.id != id || device.baud != target_baud;
+    if changed {
+        let table = bus.control_table();
+        bus.set_baud_rate(device.baud)?;
+        let written = (|| {
+            let unlock = if table.get(Register::Lock).is_some() { Register::Lock } else { Register::TorqueEnable };
+            bus.write_register(device.id, unlock, 0)?;
+            bus.write_register(device.id, Register::Id, id as i32)?;
+            if device.baud != target_baud {
+                let index = table.baud_index(target_baud).ok_or(BusError::Unsupported(Register::BaudRate))?;
+                // the reply may already come at the new rate and be lost
+                match bus.write_register(id, Register::BaudRate, index) {
+                    Ok(()) | Err(BusError::Timeout(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if unlock == Register::Lock {
+                bus.set_baud_rate(target_baud)?;
+                bus.write_register(id, Register::Lock, 1)?;
+            }
+            Ok::<_, BusError>(())
+        })();
+        bus.set_baud_rate(target_baud)?;
+        written?;
+        if bus.ping(id).is_err() {
+            return Err(AssignError::NotVerified { id, baud: target_baud });
+        }
+    }
+    info!(joint, from = device.id, from_baud = device.baud, id, baud = target_baud, changed, "servo id assigned");
+    Ok(Assignment { joint: joint.to_string(), device, id, baud: target_baud, changed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::dynamixel::DynamixelBus;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::sim::{SimBus, SimServo};
+    use crate::model::Joint;
+
+    fn profile() -> Profile {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("shoulder_pan".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() });
+        map.insert("elbow_flex".to_string(), Joint { id: 3, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() });
+        Profile::new(map)
+    }
+
+    #[test]
+    fn scan_finds_devices_across_baud_rates() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(4).with_baud_rate(115_200)]);
+        let mut bus = FeetechBus::new(sim);
+        let found = scan(&mut bus, &[]).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].baud, found[0].id, found[0].model), (1_000_000, 1, Some("sts3215")));
+        assert_eq!((found[1].baud, found[1].id, found[1].firmware), (115_200, 4, 3));
+        // the bus is back at its own rate
+        assert_eq!(bus.baud_rate().unwrap(), 1_000_000);
+        assert!(scan(&mut bus, &[57_600]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn assign_one_motor_at_a_time() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1).with_baud_rate(115_200)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        let p = profile();
+
+        let a = assign(&mut bus, &p, "elbow_flex", &[]).unwrap();
+        assert!(a.changed);
+        assert_eq!((a.device.id, a.device.baud, a.id, a.baud), (1, 115_200, 3, 1_000_000));
+        assert_eq!(handle.register(3, Register::Lock), 1);
+        bus.ping(3).unwrap();
+
+        // a second factory-fresh motor alongside the first is ambiguous
+        handle.connect(SimServo::sts3215(1));
+        assert!(matches!(assign(&mut bus, &p, "shoulder_pan", &[]), Err(AssignError::Multiple(2))));
+        handle.disconnect(3);
+        let a = assign(&mut bus, &p, "shoulder_pan", &[]).unwrap();
+        assert!(!a.changed);
+
+        handle.disconnect(1);
+        assert!(matches!(assign(&mut bus, &p, "shoulder_pan", &[]), Err(AssignError::NoDevice(_))));
+        assert!(matches!(assign(&mut bus, &p, "gripper", &[]), Err(AssignError::UnknownJoint(_))));
+    }
+
+    #[test]
+    fn assign_on_dynamixel_turns_torque_off() {
+        let sim = SimBus::dynamixel(vec![SimServo::xl330(1).with_baud_rate(57_600)]);
+        let handle = sim.handle();
+        handle.set_register(1, Register::TorqueEnable, 1);
+        let mut bus = DynamixelBus::new(sim);
+        let a = assign(&mut bus, &profile(), "elbow_flex", &[57_600]).unwrap();
+        assert_eq!((a.device.model, a.id), (Some("xl330-m288"), 3));
+        assert_eq!(handle.register(3, Register::BaudRate), 3);
+    }
+}
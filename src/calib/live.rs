@@ -0,0 +1,145 @@
+//! Live joint positions.
+//!
+//! `sample` reads `Present_Position` for every joint of a profile and
+//! normalizes it the way LeRobot does: -100..100 across
+//! `range_min..range_max` for body joints, 0..100 for the gripper, mirrored
+//! when `drive_mode` is 1. Values outside the calibrated range are not
+//! clamped so callers can flag them. Without hardware, `SimulatedArm` sweeps
+//! a simulated bus through each joint's range, slightly past both ends.
+
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::bus::feetech::FeetechBus;
+use crate::bus::sim::{self, SimBus, SimHandle};
+use crate::bus::{self, BusError, Protocol, Register, ServoBus, SharedBus};
+use crate::model::{Joint, Profile};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct JointSample {
+    pub name: String,
+    pub id: u8,
+    /// `Present_Position` as reported by the servo.
+    pub raw: i32,
+    pub normalized: f64,
+    pub range_min: i32,
+    pub range_max: i32,
+    pub in_range: bool,
+}
+
+pub fn normalize(name: &str, joint: &Joint, raw: i32) -> f64 {
+    let span = (joint.range_max - joint.range_min).max(1) as f64;
+    let offset = (raw - joint.range_min) as f64;
+    let inverted = joint.drive_mode == 1;
+    if name == "gripper" {
+        let v = offset * 100.0 / span;
+        if inverted { 100.0 - v } else { v }
+    } else {
+        let v = offset * 200.0 / span - 100.0;
+        if inverted { -v } else { v }
+    }
+}
+
+fn sorted_joints(profile: &Profile) -> Vec<(&String, &Joint)> {
+    let mut joints: Vec<_> = profile.0.iter().collect();
+    joints.sort_by_key(|(_, j)| j.id);
+    joints
+}
+
+/// Reads every joint of `profile` in one sync read, ordered by servo id.
+pub fn sample(bus: &mut dyn ServoBus, profile: &Profile) -> Result<Vec<JointSample>, BusError> {
+    let joints = sorted_joints(profile);
+    let ids: Vec<u8> = joints.iter().map(|(_, j)| j.id as u8).collect();
+    let positions = bus.sync_read_register(&ids, Register::PresentPosition)?;
+    Ok(joints
+        .into_iter()
+        .zip(positions)
+        .map(|((name, joint), raw)| JointSample {
+            name: name.clone(),
+            id: joint.id as u8,
+            raw,
+            normalized: normalize(name, joint, raw),
+            range_min: joint.range_min,
+            range_max: joint.range_max,
+            in_range: (joint.range_min..=joint.range_max).contains(&raw),
+        })
+        .collect())
+}
+
+/// Simulated STS3215 arm carrying a profile's ids and homing offsets.
+pub struct SimulatedArm {
+    bus: SharedBus,
+    handle: SimHandle,
+    joints: Vec<(u8, Joint)>,
+    started: Instant,
+}
+
+impl SimulatedArm {
+    pub fn new(profile: &Profile) -> Self {
+        let joints: Vec<(u8, Joint)> = sorted_joints(profile).into_iter().map(|(_, j)| (j.id as u8, j.clone())).collect();
+        let sim = SimBus::feetech(sim::arm(Protocol::Feetech, Some(profile)));
+        let handle = sim.handle();
+        Self { bus: bus::shared(Box::new(FeetechBus::new(sim))), handle, joints, started: Instant::now() }
+    }
+
+    pub fn bus(&self) -> SharedBus {
+        self.bus.clone()
+    }
+
+    /// Moves every joint to its place in the sweep at the current time.
+    pub fn advance(&self) {
+        self.advance_to(self.started.elapsed().as_secs_f64());
+    }
+
+    /// Moves every joint to its place `t` seconds into the sweep. Each joint
+    /// has its own period and overshoots its range by 10% at the ends.
+    pub fn advance_to(&self, t: f64) {
+        let table = &crate::bus::feetech::STS3215;
+        for (i, (id, j)) in self.joints.iter().enumerate() {
+            let mid = (j.range_min + j.range_max) as f64 / 2.0;
+            let half = (j.range_max - j.range_min) as f64 / 2.0 * 1.1;
+            let period = 4.0 + i as f64;
+            let present = (mid + half * (t * std::f64::consts::TAU / period).sin()).round() as i32;
+            self.handle.set_raw_position(*id, table.raw(present, j.homing_offset));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joint(drive_mode: i32) -> Joint {
+        Joint { id: 1, drive_mode, homing_offset: 0, range_min: 1000, range_max: 3000, ..Default::default() }
+    }
+
+    #[test]
+    fn normalization_matches_lerobot() {
+        assert_eq!(normalize("elbow_flex", &joint(0), 1000), -100.0);
+        assert_eq!(normalize("elbow_flex", &joint(0), 2500), 50.0);
+        assert_eq!(normalize("elbow_flex", &joint(1), 2500), -50.0);
+        assert_eq!(normalize("gripper", &joint(0), 1500), 25.0);
+        assert_eq!(normalize("gripper", &joint(1), 1500), 75.0);
+        // not clamped outside the range
+        assert_eq!(normalize("elbow_flex", &joint(0), 3200), 120.0);
+    }
+
+    #[test]
+    fn simulated_arm_sweeps_past_range() {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("elbow_flex".to_string(), Joint { id: 3, drive_mode: 0, homing_offset: -700, range_min: 800, range_max: 3200, ..Default::default() });
+        let profile = Profile::new(map);
+        let arm = SimulatedArm::new(&profile);
+        let bus = arm.bus();
+        let read = || sample(bus.lock().unwrap().as_mut(), &profile).unwrap().remove(0);
+
+        arm.advance_to(0.0);
+        let s = read();
+        assert_eq!((s.id, s.raw, s.normalized, s.in_range), (3, 2000, 0.0, true));
+        // a quarter period later the joint is 10% past range_max
+        arm.advance_to(1.0);
+        let s = read();
+        assert_eq!((s.raw, s.normalized, s.in_range), (3320, 110.0, false));
+    }
+}
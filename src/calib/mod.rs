@@ -0,0 +1,9 @@
+//! Calibration workflows that combine a servo bus with stored profiles.
+
+pub mod control;
+pub mod eeprom;
+pub mod homing;
+pub mod ids;
+pub mod live;
+pub mod readback;
+pub mod record;
@@ -0,0 +1,88 @@
+//! Rebuilding a profile from the calibration stored in the servos.
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::bus::{BusError, Register, ServoBus};
+use crate::model::{Joint, Profile};
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct MissingJoint {
+    pub joint: String,
+    pub id: u8,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadbackReport {
+    pub profile: Profile,
+    /// Expected joints whose servo did not answer.
+    pub missing: Vec<MissingJoint>,
+    /// Servos that answered but are not in the joint table.
+    pub unexpected: Vec<u8>,
+}
+
+/// Pings ids `1..=max_id` and returns those that answered.
+pub fn scan(bus: &mut dyn ServoBus, max_id: u8) -> Vec<u8> {
+    (1..=max_id).filter(|id| bus.ping(*id).is_ok()).collect()
+}
+
+fn read_joint(bus: &mut dyn ServoBus, id: u8) -> Result<Joint, BusError> {
+    let drive_mode = match bus.control_table().get(Register::DriveMode) {
+        Some(_) => bus.read_register(id, Register::DriveMode)?,
+        None => 0,
+    };
+    Ok(Joint {
+        id: id as i32,
+        drive_mode,
+        homing_offset: bus.read_register(id, Register::HomingOffset)?,
+        range_min: bus.read_register(id, Register::MinPositionLimit)?,
+        range_max: bus.read_register(id, Register::MaxPositionLimit)?,
+        ..Default::default()
+    })
+}
+
+/// Scans ids up to `max_id` and reads the calibration registers of every
+/// servo listed in `joints`.
+pub fn read_back(bus: &mut dyn ServoBus, joints: &[(&str, u8)], max_id: u8) -> ReadbackReport {
+    let found = scan(bus, max_id);
+    let mut report = ReadbackReport { profile: Profile::default(), missing: Vec::new(), unexpected: Vec::new() };
+    for (name, id) in joints {
+        let result = if found.contains(id) { read_joint(bus, *id) } else { Err(BusError::Timeout(*id)) };
+        match result {
+            Ok(j) => {
+                report.profile.0.insert(name.to_string(), j);
+            }
+            Err(e) => report.missing.push(MissingJoint { joint: name.to_string(), id: *id, error: e.to_string() }),
+        }
+    }
+    report.unexpected = found.into_iter().filter(|id| !joints.iter().any(|(_, j)| j == id)).collect();
+    info!(read = report.profile.0.len(), missing = report.missing.len(), unexpected = ?report.unexpected, "calibration read back");
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::sim::{SimBus, SimServo};
+    use crate::model::registry;
+
+    #[test]
+    fn read_back_reports_missing_and_unexpected() {
+        let sim = SimBus::feetech([1, 2, 3, 4, 6, 9].into_iter().map(SimServo::sts3215).collect());
+        let handle = sim.handle();
+        handle.set_register(1, Register::HomingOffset, -1921);
+        handle.set_register(1, Register::MinPositionLimit, 767);
+        handle.set_register(1, Register::MaxPositionLimit, 3201);
+        let mut bus = FeetechBus::new(sim);
+
+        let joints = registry::lookup("so101_follower").unwrap().joint_ids();
+        let report = read_back(&mut bus, &joints, 10);
+        assert_eq!(report.profile.0.len(), 5);
+        assert_eq!(report.profile.0["shoulder_pan"], Joint { id: 1, drive_mode: 0, homing_offset: -1921, range_min: 767, range_max: 3201, ..Default::default() });
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!((report.missing[0].joint.as_str(), report.missing[0].id), ("wrist_roll", 5));
+        assert_eq!(report.unexpected, vec![9]);
+    }
+}
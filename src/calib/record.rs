@@ -0,0 +1,216 @@
+//! Range-of-motion recording.
+//!
+//! While the operator sweeps every joint through its full travel, a session
+//! polls `PresentPosition` and keeps the per-joint minimum and maximum; on
+//! commit those become the profile's `range_min`/`range_max`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::bus::{BusError, Register, ServoBus, SharedBus};
+use crate::model::Profile;
+use crate::store::{Kind, Origin, Store, StoreError};
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct JointRange {
+    pub name: String,
+    pub id: u8,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+    pub last: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingSession {
+    pub joints: Vec<JointRange>,
+    pub samples: u64,
+    pub last_error: Option<String>,
+}
+
+impl RecordingSession {
+    /// Tracks every joint of `profile`, ordered by servo id.
+    pub fn new(profile: &Profile) -> Self {
+        let mut joints: Vec<JointRange> = profile
+            .0
+            .iter()
+            .map(|(name, j)| JointRange { name: name.clone(), id: j.id as u8, min: None, max: None, last: None })
+            .collect();
+        joints.sort_by_key(|j| j.id);
+        Self { joints, samples: 0, last_error: None }
+    }
+
+    /// Reads all positions in one sync read and widens the tracked ranges.
+    pub fn sample(&mut self, bus: &mut dyn ServoBus) -> Result<(), BusError> {
+        let ids: Vec<u8> = self.joints.iter().map(|j| j.id).collect();
+        let positions = bus.sync_read_register(&ids, Register::PresentPosition)?;
+        for (joint, pos) in self.joints.iter_mut().zip(positions) {
+            joint.min = Some(joint.min.map_or(pos, |m| m.min(pos)));
+            joint.max = Some(joint.max.map_or(pos, |m| m.max(pos)));
+            joint.last = Some(pos);
+        }
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Copies recorded ranges into `profile`; returns the joints updated.
+    /// Joints that never moved (min == max) are left untouched.
+    pub fn apply_to(&self, profile: &mut Profile) -> Vec<String> {
+        let mut updated = Vec::new();
+        for r in &self.joints {
+            let (Some(min), Some(max)) = (r.min, r.max) else { continue };
+            if min >= max {
+                continue;
+            }
+            if let Some(j) = profile.0.get_mut(&r.name) {
+                j.range_min = min;
+                j.range_max = max;
+                updated.push(r.name.clone());
+            }
+        }
+        updated
+    }
+}
+
+/// Writes a finished session into the stored profile.
+pub fn commit(store: &Store, kind: Kind, name: &str, session: &RecordingSession) -> Result<Vec<String>, StoreError> {
+    let mut profile = store.read_profile(kind, name)?;
+    let updated = session.apply_to(&mut profile);
+    if !updated.is_empty() {
+        store.write_profile_from(kind, name, &profile, true, Origin::new("recording"))?;
+    }
+    info!(kind = kind.as_str(), name, ?updated, "recording committed");
+    Ok(updated)
+}
+
+struct Running {
+    session: Arc<Mutex<RecordingSession>>,
+    stop: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Background recording sessions, at most one per profile.
+#[derive(Default)]
+pub struct Recorder {
+    running: Mutex<HashMap<String, Running>>,
+}
+
+/// Session key for a profile, built from its canonical name so every alias
+/// (`arm`, `so101_follower/arm`) reaches the same session.
+pub fn key(store: &Store, kind: Kind, name: &str) -> Result<String, StoreError> {
+    Ok(format!("{}/{}", kind.as_str(), store.canonical_name(kind, name)?))
+}
+
+impl Recorder {
+    /// Starts polling `bus` every `interval`; returns false if a session for
+    /// `key` is already running.
+    pub fn start(&self, key: String, session: RecordingSession, bus: SharedBus, interval: Duration) -> bool {
+        let mut running = lock(&self.running);
+        if running.contains_key(&key) {
+            return false;
+        }
+        let session = Arc::new(Mutex::new(session));
+        let stop = Arc::new(AtomicBool::new(false));
+        let task = {
+            let (session, stop, key) = (session.clone(), stop.clone(), key.clone());
+            tokio::spawn(async move {
+                let mut tick = tokio::time::interval(interval);
+                while !stop.load(Ordering::Relaxed) {
+                    tick.tick().await;
+                    let session = session.clone();
+                    let result = crate::bus::run(&bus, move |b| {
+                        let mut s = lock(&session);
+                        let r = s.sample(b);
+                        s.last_error = r.as_ref().err().map(|e| e.to_string());
+                        r
+                    })
+                    .await;
+                    if let Err(e) = result {
+                        warn!(%key, error = %e, "recording sample failed");
+                    }
+                }
+            })
+        };
+        info!(%key, ?interval, "recording started");
+        running.insert(key, Running { session, stop, task });
+        true
+    }
+
+    pub fn snapshot(&self, key: &str) -> Option<RecordingSession> {
+        lock(&self.running).get(key).map(|r| lock(&r.session).clone())
+    }
+
+    /// Stops the session and returns its final state.
+    pub async fn stop(&self, key: &str) -> Option<RecordingSession> {
+        let running = lock(&self.running).remove(key)?;
+        running.stop.store(true, Ordering::Relaxed);
+        let _ = running.task.await;
+        info!(%key, "recording stopped");
+        let session = lock(&running.session).clone();
+        Some(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::feetech::FeetechBus;
+    use crate::bus::sim::{SimBus, SimServo};
+    use crate::model::Joint;
+
+    fn profile() -> Profile {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("shoulder_pan".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() });
+        map.insert("gripper".to_string(), Joint { id: 2, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() });
+        Profile::new(map)
+    }
+
+    #[test]
+    fn sample_tracks_min_max() {
+        let sim = SimBus::feetech(vec![SimServo::sts3215(1), SimServo::sts3215(2)]);
+        let handle = sim.handle();
+        let mut bus = FeetechBus::new(sim);
+        let mut session = RecordingSession::new(&profile());
+
+        for (a, b) in [(1000, 2000), (3000, 2100), (2000, 2050)] {
+            handle.set_raw_position(1, a);
+            handle.set_raw_position(2, b);
+            session.sample(&mut bus).unwrap();
+        }
+        assert_eq!(session.samples, 3);
+        assert_eq!((session.joints[0].min, session.joints[0].max), (Some(1000), Some(3000)));
+        assert_eq!((session.joints[1].min, session.joints[1].max), (Some(2000), Some(2100)));
+
+        let mut p = profile();
+        assert_eq!(session.apply_to(&mut p).len(), 2);
+        assert_eq!(p.0["shoulder_pan"].range_min, 1000);
+        assert_eq!(p.0["gripper"].range_max, 2100);
+    }
+
+    #[test]
+    fn aliases_share_a_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        store.write_profile(Kind::Robots, "my_arm/arm", &profile(), false).unwrap();
+        let full = key(&store, Kind::Robots, "my_arm/arm").unwrap();
+        assert_eq!(full, "robots/my_arm/arm");
+        assert_eq!(key(&store, Kind::Robots, "arm").unwrap(), full);
+    }
+
+    #[test]
+    fn missing_servo_is_an_error() {
+        let mut bus = FeetechBus::new(SimBus::feetech(vec![SimServo::sts3215(1)]));
+        let mut session = RecordingSession::new(&profile());
+        assert!(matches!(session.sample(&mut bus), Err(BusError::Timeout(2))));
+        assert_eq!(session.samples, 0);
+    }
+}
@@ -0,0 +1,381 @@
+//! `lerobot-servo-cli`: offline commands built on the same store and bus code
+//! as the web service.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::bus::{self, sim};
+use crate::calib::readback;
+use crate::config::Config;
+use crate::model::{legacy::{self, Legacy}, registry, Profile};
+use crate::store::archive::{Action, Archive, ArchiveFilter, Conflict};
+use crate::store::{parse_profile_ref, Kind, Origin, Store};
+
+pub const USAGE: &str = "\
+usage: lerobot-servo-cli <command> [options]
+
+commands:
+  readback --kind <robots|teleoperators> --name <profile> [--port <dev|sim|sim-pty>] [--baud <n>] [--protocol <feetech|dynamixel>] [--max-id <n>] [--overwrite]
+      scan the bus and save the servos' calibration as a new profile
+  import --file <path> --kind <robots|teleoperators> --name <profile> [--robot-type <type>] [--overwrite] [--dry-run]
+      save a calibration file as a profile; legacy LeRobot files (parallel arrays) are converted and lossy steps listed
+  export-legacy --kind <robots|teleoperators> --name <profile> [--out <path>]
+      write a profile in the legacy parallel-array layout (stdout by default)
+  export-archive --out <path> [--format <tar|zip>] [--kind <robots|teleoperators>] [--robot-type <type>]
+      pack every profile (or a kind / robot type) with a manifest
+  import-archive --file <path> [--conflict <skip|overwrite|fail>] [--dry-run]
+      unpack an archive into the calibration root; --dry-run only prints what would happen
+  fmt [--kind <robots|teleoperators>] [--check]
+      rewrite profile files in LeRobot's layout (4-space indent, joints in kinematic order); --check only lists them
+  simulate [--sim-profile <kind>/<name>] [--protocol <p>] [--baud <n>] [--noise <ticks>] [--latency-ms <n>] [--drop-rate <0..1>] [--seed <n>]
+      serve a simulated arm on a pseudo-terminal and print its path; runs until interrupted
+
+common options:
+  --root <dir>   calibration root (default: $CALIB_ROOT or huggingface/lerobot/calibration)
+  --port/--baud/--protocol  default to $SERVO_PORT / $SERVO_BAUD / $SERVO_PROTOCOL
+  --sim-profile and the simulator flags default to $SIM_PROFILE, $SIM_NOISE, $SIM_LATENCY_MS, $SIM_DROP_RATE, $SIM_SEED";
+
+/// Parsed `--key value` options; a key without a value is a switch.
+#[derive(Debug, Default)]
+pub struct Flags {
+    values: HashMap<String, String>,
+}
+
+impl Flags {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                bail!("unexpected argument `{}`", arg);
+            };
+            if let Some((k, v)) = key.split_once('=') {
+                values.insert(k.to_string(), v.to_string());
+            } else if it.peek().is_some_and(|next| !next.starts_with("--")) {
+                values.insert(key.to_string(), it.next().cloned().unwrap_or_default());
+            } else {
+                values.insert(key.to_string(), String::new());
+            }
+        }
+        Ok(Self { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn require(&self, key: &str) -> Result<&str> {
+        self.get(key).filter(|v| !v.is_empty()).ok_or_else(|| anyhow!("missing --{}", key))
+    }
+
+    pub fn switch(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub fn parse_or<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T> {
+        match self.get(key) {
+            Some(v) => v.parse().map_err(|_| anyhow!("invalid value for --{}: `{}`", key, v)),
+            None => Ok(default),
+        }
+    }
+}
+
+fn parse_kind(s: &str) -> Result<Kind> {
+    match s {
+        "robots" => Ok(Kind::Robots),
+        "teleoperators" => Ok(Kind::Teleoperators),
+        _ => bail!("invalid kind `{}` (expected robots or teleoperators)", s),
+    }
+}
+
+fn store(flags: &Flags, cfg: &Config) -> Store {
+    let root = flags.get("root").map(PathBuf::from).unwrap_or_else(|| cfg.calib_root.clone());
+    Store::new(root).with_retention(cfg.history.clone()).with_trash_max_age(cfg.trash_max_age).with_format(cfg.profile_format)
+}
+
+/// Simulator settings and the profile to seed it from.
+fn sim_setup(flags: &Flags, cfg: &Config) -> Result<(sim::SimConfig, Option<Profile>)> {
+    let sim = sim::SimConfig {
+        noise: flags.parse_or("noise", cfg.sim.noise)?,
+        latency: std::time::Duration::from_millis(flags.parse_or("latency-ms", cfg.sim.latency.as_millis() as u64)?),
+        drop_rate: flags.parse_or("drop-rate", cfg.sim.drop_rate)?,
+        seed: flags.parse_or("seed", cfg.sim.seed)?,
+    };
+    let profile_ref = match flags.get("sim-profile") {
+        Some(v) => Some(parse_profile_ref(v).ok_or_else(|| anyhow!("invalid --sim-profile `{}` (expected <kind>/<name>)", v))?),
+        None => cfg.sim_profile.clone(),
+    };
+    let profile = match profile_ref {
+        Some((kind, name)) => Some(store(flags, cfg).read_profile(kind, &name).with_context(|| format!("loading sim profile {}/{}", kind.as_str(), name))?),
+        None => None,
+    };
+    Ok((sim, profile))
+}
+
+fn open_bus(flags: &Flags, cfg: &Config) -> Result<Box<dyn bus::ServoBus>> {
+    let port = flags
+        .get("port")
+        .map(str::to_string)
+        .or_else(|| cfg.servo_port.clone())
+        .ok_or_else(|| anyhow!("missing --port (or SERVO_PORT)"))?;
+    let baud = flags.parse_or("baud", cfg.servo_baud)?;
+    let protocol = flags.parse_or("protocol", cfg.servo_protocol)?;
+    let (sim, profile) = if port.starts_with("sim") { sim_setup(flags, cfg)? } else { Default::default() };
+    bus::open_with_sim(&port, baud, protocol, &sim, profile.as_ref()).with_context(|| format!("opening servo bus {}", port))
+}
+
+pub fn run(args: &[String]) -> Result<()> {
+    let Some((cmd, rest)) = args.split_first() else {
+        bail!("{}", USAGE);
+    };
+    let flags = Flags::parse(rest)?;
+    let cfg = Config::from_env();
+    match cmd.as_str() {
+        "readback" => cmd_readback(&flags, &cfg),
+        "simulate" => cmd_simulate(&flags, &cfg),
+        "fmt" => cmd_fmt(&flags, &cfg),
+        "import" => cmd_import(&flags, &cfg),
+        "export-legacy" => cmd_export_legacy(&flags, &cfg),
+        "export-archive" => cmd_export_archive(&flags, &cfg),
+        "import-archive" => cmd_import_archive(&flags, &cfg),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => bail!("unknown command `{}`\n\n{}", other, USAGE),
+    }
+}
+
+fn cmd_import(flags: &Flags, cfg: &Config) -> Result<()> {
+    let file = flags.require("file")?;
+    let kind = parse_kind(flags.require("kind")?)?;
+    let mut name = flags.require("name")?.to_string();
+    let ty = match flags.get("robot-type") {
+        Some(t) => {
+            let ty = registry::lookup(t).filter(|ty| ty.kind == kind).ok_or_else(|| anyhow!("unknown {} type `{}`", kind.as_str(), t))?;
+            name = crate::store::ProfileName::parse(&name)?.with_type(ty.name)?.to_string();
+            Some(ty)
+        }
+        None => registry::type_of(kind, &name),
+    };
+    let value: serde_json::Value = serde_json::from_slice(&std::fs::read(file).with_context(|| format!("reading {}", file))?).with_context(|| format!("parsing {}", file))?;
+    let import = legacy::import(value, ty)?;
+    for loss in &import.losses {
+        eprintln!("lossy: {}", loss);
+    }
+    if flags.switch("dry-run") {
+        println!("{}", serde_json::to_string_pretty(&import.profile)?);
+        return Ok(());
+    }
+    let store = store(flags, cfg);
+    if !flags.switch("overwrite") && store.exists(kind, &name) {
+        bail!("{}/{} already exists; pass --overwrite to replace it", kind.as_str(), name);
+    }
+    let summary = match import.layout {
+        legacy::Layout::Legacy => format!("imported from legacy layout ({} lossy)", import.losses.len()),
+        legacy::Layout::Current => format!("imported from {}", file),
+    };
+    let (path, _) = store.write_profile_from(kind, &name, &import.profile, true, Origin::new("cli").summary(Some(summary)))?;
+    println!("saved {} joints to {}", import.profile.0.len(), path.display());
+    Ok(())
+}
+
+fn cmd_export_legacy(flags: &Flags, cfg: &Config) -> Result<()> {
+    let kind = parse_kind(flags.require("kind")?)?;
+    let name = flags.require("name")?;
+    let profile = store(flags, cfg).read_profile(kind, name)?;
+    let (legacy, losses) = Legacy::from_profile(&profile);
+    for loss in &losses {
+        eprintln!("lossy: {}", loss);
+    }
+    let text = serde_json::to_string_pretty(&legacy)?;
+    match flags.get("out") {
+        Some(out) => std::fs::write(out, text).with_context(|| format!("writing {}", out))?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+fn cmd_export_archive(flags: &Flags, cfg: &Config) -> Result<()> {
+    let out = flags.require("out")?;
+    let filter = ArchiveFilter { kind: flags.get("kind").map(parse_kind).transpose()?, robot_type: flags.get("robot-type").map(str::to_string) };
+    let bytes = store(flags, cfg).export_archive(&filter, flags.parse_or("format", Default::default())?)?;
+    std::fs::write(out, &bytes).with_context(|| format!("writing {}", out))?;
+    println!("wrote {} bytes to {}", bytes.len(), out);
+    Ok(())
+}
+
+fn cmd_import_archive(flags: &Flags, cfg: &Config) -> Result<()> {
+    let file = flags.require("file")?;
+    let archive = Archive::read(&std::fs::read(file).with_context(|| format!("reading {}", file))?)?;
+    let conflict: Conflict = flags.parse_or("conflict", Conflict::Skip)?;
+    let store = store(flags, cfg);
+    let plan = if flags.switch("dry-run") {
+        store.preview_archive(&archive, conflict)?
+    } else {
+        store.import_archive(&archive, conflict, Origin::new("cli").summary(Some(format!("imported from {}", file))))?
+    };
+    for item in &plan.items {
+        println!("{:<10} {}/{}{}", item.action.as_str(), item.kind.as_str(), item.name, item.reason.as_deref().map(|r| format!(" ({})", r)).unwrap_or_default());
+    }
+    for path in &plan.ignored {
+        println!("{:<10} {}", "ignored", path);
+    }
+    if plan.count(Action::Conflict) > 0 {
+        bail!("{} profile(s) already exist; pass --conflict skip or overwrite", plan.count(Action::Conflict));
+    }
+    Ok(())
+}
+
+fn cmd_fmt(flags: &Flags, cfg: &Config) -> Result<()> {
+    let kinds = match flags.get("kind") {
+        Some(k) => vec![parse_kind(k)?],
+        None => vec![Kind::Robots, Kind::Teleoperators],
+    };
+    let check = flags.switch("check");
+    let changed = store(flags, cfg).canonicalize_files(&kinds, check)?;
+    for (kind, name) in &changed {
+        println!("{}{}/{}", if check { "would reformat " } else { "reformatted " }, kind.as_str(), name);
+    }
+    if check && !changed.is_empty() {
+        bail!("{} profile(s) not in canonical format", changed.len());
+    }
+    Ok(())
+}
+
+fn cmd_readback(flags: &Flags, cfg: &Config) -> Result<()> {
+    let kind = parse_kind(flags.require("kind")?)?;
+    let name = flags.require("name")?;
+    let store = store(flags, cfg);
+    if !flags.switch("overwrite") && store.exists(kind, name) {
+        bail!("{}/{} already exists; pass --overwrite to replace it", kind.as_str(), name);
+    }
+    let mut bus = open_bus(flags, cfg)?;
+    let ty = registry::type_of(kind, name).unwrap_or(registry::default_for(kind));
+    let mut report = readback::read_back(bus.as_mut(), &ty.joint_ids(), flags.parse_or("max-id", 10u8)?);
+    for m in &report.missing {
+        eprintln!("missing: {} (id {}): {}", m.joint, m.id, m.error);
+    }
+    for id in &report.unexpected {
+        eprintln!("unexpected servo id {} (not in the {} joint table)", id, ty.name);
+    }
+    if report.profile.0.is_empty() {
+        bail!("no expected servo answered");
+    }
+    if let Ok(old) = store.read_profile(kind, name) {
+        report.profile.keep_extra_from(&old);
+    }
+    let (path, _) = store.write_profile_from(kind, name, &report.profile, true, Origin::new("cli").summary(Some("read back from servos".into())))?;
+    println!("saved {} joints to {}", report.profile.0.len(), path.display());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn cmd_simulate(flags: &Flags, cfg: &Config) -> Result<()> {
+    let baud = flags.parse_or("baud", cfg.servo_baud)?;
+    let protocol = flags.parse_or("protocol", cfg.servo_protocol)?;
+    let (sim_cfg, profile) = sim_setup(flags, cfg)?;
+    let servos: Vec<sim::SimServo> = sim::arm(protocol, profile.as_ref()).into_iter().map(|s| s.with_baud_rate(baud)).collect();
+    let ids: Vec<u8> = servos.iter().map(sim::SimServo::id).collect();
+    let path = sim::serve_pty(sim::SimBus::with_servos(protocol, servos, baud).configure(sim_cfg))?;
+    println!("{}", path);
+    eprintln!("simulated {} arm, servo ids {:?} at {} baud; press Ctrl-C to stop", protocol.as_str(), ids, baud);
+    loop {
+        std::thread::park();
+    }
+}
+
+#[cfg(not(unix))]
+fn cmd_simulate(_: &Flags, _: &Config) -> Result<()> {
+    bail!("simulate needs pseudo-terminals and is only available on Unix; use --port sim instead")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn flags_parse() {
+        let f = Flags::parse(&args("--kind robots --overwrite --max-id=8 --name arm")).unwrap();
+        assert_eq!(f.get("kind"), Some("robots"));
+        assert!(f.switch("overwrite"));
+        assert_eq!(f.parse_or("max-id", 10u8).unwrap(), 8);
+        assert_eq!(f.require("name").unwrap(), "arm");
+        assert!(f.require("port").is_err());
+        assert!(Flags::parse(&args("stray")).is_err());
+    }
+
+    #[test]
+    fn readback_against_sim() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        run(&args(&format!("readback --port sim --kind robots --name arm --root {}", root))).unwrap();
+        let p = Store::new(dir.path().to_path_buf()).read_profile(Kind::Robots, "arm").unwrap();
+        assert_eq!(p.0.len(), 6);
+        assert_eq!(p.0["gripper"].id, 6);
+        // refuses to clobber without --overwrite
+        assert!(run(&args(&format!("readback --port sim --kind robots --name arm --root {}", root))).is_err());
+    }
+
+    #[test]
+    fn legacy_import_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let file = dir.path().join("main_follower.json");
+        let legacy = serde_json::json!({
+            "homing_offset": [0, 0, 0, 0, 0, 0],
+            "drive_mode": [0, 0, 0, 0, 0, 1],
+            "start_pos": [100, 100, 100, 100, 100, 2000],
+            "end_pos": [3000, 3000, 3000, 3000, 3000, 3500],
+            "calib_mode": ["DEGREE", "DEGREE", "DEGREE", "DEGREE", "DEGREE", "LINEAR"],
+            "motor_names": ["shoulder_pan", "shoulder_lift", "elbow_flex", "wrist_flex", "wrist_roll", "gripper"]
+        });
+        std::fs::write(&file, legacy.to_string()).unwrap();
+        let import = format!("import --file {} --kind robots --name arm --robot-type so101_follower --root {}", file.display(), root);
+        run(&args(&import)).unwrap();
+        let p = Store::new(dir.path().to_path_buf()).read_profile(Kind::Robots, "so101_follower/arm").unwrap();
+        assert_eq!((p.0["gripper"].id, p.0["gripper"].range_min, p.0["gripper"].drive_mode), (6, 2000, 1));
+        assert!(run(&args(&import)).is_err());
+
+        let out = dir.path().join("back.json");
+        run(&args(&format!("export-legacy --kind robots --name arm --out {} --root {}", out.display(), root))).unwrap();
+        let back: Legacy = serde_json::from_slice(&std::fs::read(out).unwrap()).unwrap();
+        assert_eq!((back.motor_names.len(), back.start_pos[5], back.end_pos[5]), (6, 2000, 3500));
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        let p = registry::lookup("so101_leader").unwrap().template();
+        Store::new(src.path().to_path_buf()).write_profile(Kind::Teleoperators, "so101_leader/lead", &p, false).unwrap();
+        let out = src.path().join("all.zip");
+        run(&args(&format!("export-archive --out {} --format zip --root {}", out.display(), src.path().display()))).unwrap();
+        let import = format!("import-archive --file {} --root {}", out.display(), dst.path().display());
+        run(&args(&format!("{} --dry-run", import))).unwrap();
+        let target = Store::new(dst.path().to_path_buf());
+        assert!(!target.exists(Kind::Teleoperators, "lead"));
+        run(&args(&import)).unwrap();
+        assert_eq!(target.read_profile(Kind::Teleoperators, "lead").unwrap(), p);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn readback_from_seeded_pty_sim() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let mut src = Profile::default();
+        src.0.insert("gripper".into(), crate::model::Joint { id: 6, drive_mode: 0, homing_offset: 1155, range_min: 2038, range_max: 3538, ..Default::default() });
+        store.write_profile(Kind::Robots, "src", &src, false).unwrap();
+        run(&args(&format!("readback --port sim-pty --sim-profile robots/src --noise 3 --kind robots --name copy --root {}", root))).unwrap();
+        let p = store.read_profile(Kind::Robots, "copy").unwrap();
+        assert_eq!(p.0["gripper"], src.0["gripper"]);
+    }
+}
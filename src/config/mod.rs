@@ -1,8 +1,44 @@
 use std::path::PathBuf;
 
+use tracing::warn;
+
+use crate::bus::sim::SimConfig;
+use crate::bus::Protocol;
+use crate::store::{parse_profile_ref, Format, Kind, Retention};
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub calib_root: PathBuf,
+    /// Serial device of the servo bus (`SERVO_PORT`), or `sim`; unset means no hardware.
+    pub servo_port: Option<String>,
+    pub servo_baud: u32,
+    /// `SERVO_PROTOCOL`: `feetech` (default) or `dynamixel`.
+    pub servo_protocol: Protocol,
+    /// `SIM_NOISE`, `SIM_LATENCY_MS`, `SIM_DROP_RATE`, `SIM_SEED` for `SERVO_PORT=sim|sim-pty`.
+    pub sim: SimConfig,
+    /// `SIM_PROFILE=<robots|teleoperators>/<name>`: profile the simulated arm is seeded from.
+    pub sim_profile: Option<(Kind, String)>,
+    /// `HISTORY_MAX_REVISIONS` (0 keeps all) and `HISTORY_MAX_AGE_DAYS`.
+    pub history: Retention,
+    /// `TRASH_MAX_AGE_DAYS` (default 30, 0 keeps deleted profiles forever).
+    pub trash_max_age: Option<std::time::Duration>,
+    /// `PROFILE_FORMAT`: `preserve` (default) or `canonical`.
+    pub profile_format: Format,
+}
+
+/// Parses `key` if set; an unparsable value is logged and treated as unset.
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = std::env::var(key).ok()?;
+    match value.parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!(key, %value, error = %e, "invalid environment variable, using the default");
+            None
+        }
+    }
 }
 
 impl Config {
@@ -11,7 +47,37 @@ impl Config {
         let calib_root = root
             .map(PathBuf::from)
             .unwrap_or_else(|| PathBuf::from("huggingface/lerobot/calibration"));
-        Self { calib_root }
+        let servo_port = std::env::var("SERVO_PORT").ok().filter(|s| !s.is_empty());
+        let servo_baud = env_parse("SERVO_BAUD").unwrap_or(1_000_000);
+        let servo_protocol = env_parse("SERVO_PROTOCOL").unwrap_or_default();
+        let sim = SimConfig {
+            noise: env_parse("SIM_NOISE").unwrap_or(0),
+            latency: std::time::Duration::from_millis(env_parse("SIM_LATENCY_MS").unwrap_or(0)),
+            drop_rate: env_parse("SIM_DROP_RATE").unwrap_or(0.0),
+            seed: env_parse("SIM_SEED").unwrap_or(0),
+        };
+        let sim_profile = std::env::var("SIM_PROFILE").ok().and_then(|value| {
+            let parsed = parse_profile_ref(&value);
+            if parsed.is_none() {
+                warn!(key = "SIM_PROFILE", %value, "invalid environment variable, expected <kind>/<name>");
+            }
+            parsed
+        });
+        let history = Retention {
+            max_revisions: match env_parse::<usize>("HISTORY_MAX_REVISIONS") {
+                Some(0) => None,
+                Some(n) => Some(n),
+                None => Retention::default().max_revisions,
+            },
+            max_age: env_parse::<u64>("HISTORY_MAX_AGE_DAYS").map(|d| std::time::Duration::from_secs(d * 86_400)),
+        };
+        let trash_max_age = match env_parse::<u64>("TRASH_MAX_AGE_DAYS") {
+            Some(0) => None,
+            Some(d) => Some(std::time::Duration::from_secs(d * 86_400)),
+            None => Some(crate::store::trash::DEFAULT_MAX_AGE),
+        };
+        let profile_format = env_parse("PROFILE_FORMAT").unwrap_or_default();
+        Self { calib_root, servo_port, servo_baud, servo_protocol, sim, sim_profile, history, trash_max_age, profile_format }
     }
 
     pub fn robots_dir(&self) -> PathBuf {
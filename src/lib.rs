@@ -1,5 +1,8 @@
 pub mod api;
+pub mod bus;
+pub mod calib;
+pub mod cli;
 pub mod config;
 pub mod model;
 pub mod store;
-
+pub mod web;
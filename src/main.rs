@@ -5,11 +5,7 @@ use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{fmt, EnvFilter};
 
-mod api;
-mod config;
-mod model;
-mod store;
-mod web;
+use lerobot_servo_adjust::{api, bus, config, store, web};
 
 #[tokio::main]
 async fn main() {
@@ -29,9 +25,29 @@ async fn main() {
     let health = Router::new().route("/healthz", get(|| async { "ok" }));
     let cfg = config::Config::from_env();
     let _ = cfg.ensure_exists();
-    let store = Arc::new(store::Store::new(cfg.calib_root.clone()));
+    let store = Arc::new(store::Store::new(cfg.calib_root.clone()).with_retention(cfg.history.clone()).with_trash_max_age(cfg.trash_max_age).with_format(cfg.profile_format));
+    if let Err(e) = store.purge_expired() {
+        tracing::warn!(error = %e, "cannot purge old trash entries");
+    }
     let read_only = std::env::var("READ_ONLY").map(|v| matches!(&*v.to_lowercase(), "1" | "true" | "yes")).unwrap_or(false);
-    let state = api::AppState { store, base_url: Some(base_url), read_only };
+    let sim_profile = cfg.sim_profile.as_ref().and_then(|(kind, name)| match store.read_profile(*kind, name) {
+        Ok(p) => Some(p),
+        Err(e) => {
+            tracing::error!(profile = %name, error = %e, "cannot load SIM_PROFILE; simulating a default arm");
+            None
+        }
+    });
+    let servo_bus = cfg.servo_port.as_deref().and_then(|port| match bus::open_with_sim(port, cfg.servo_baud, cfg.servo_protocol, &cfg.sim, sim_profile.as_ref()) {
+        Ok(b) => {
+            tracing::info!(port, baud = cfg.servo_baud, protocol = cfg.servo_protocol.as_str(), "servo bus opened");
+            Some(bus::shared(b))
+        }
+        Err(e) => {
+            tracing::error!(port, error = %e, "cannot open servo bus; hardware features disabled");
+            None
+        }
+    });
+    let state = api::AppState { store, base_url: Some(base_url), read_only, bus: servo_bus, recorder: Arc::default() };
     let app = health
         .merge(api::router(state.clone()))
         .merge(web::router(state))
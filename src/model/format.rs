@@ -0,0 +1,112 @@
+//! JSON layout of profile files.
+//!
+//! LeRobot writes calibration files with Python's `json.dump(..., indent=4)`:
+//! four-space indentation and no trailing newline. Rewrites keep whatever
+//! layout a file already has so diffs only show changed values.
+
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+
+use super::Profile;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Style {
+    /// Indentation per level; `None` writes everything on one line.
+    pub indent: Option<String>,
+    pub trailing_newline: bool,
+}
+
+impl Style {
+    /// What LeRobot's `json.dump(indent=4)` produces.
+    pub fn lerobot() -> Self {
+        Self { indent: Some("    ".into()), trailing_newline: false }
+    }
+
+    /// Layout of an existing file; unrecognised files get LeRobot's.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let trailing_newline = text.ends_with('\n');
+        let body = text.trim_end();
+        if !body.starts_with('{') {
+            return Self::lerobot();
+        }
+        if !body.contains('\n') {
+            return Self { indent: None, trailing_newline };
+        }
+        // the first indented line is one level deep
+        let indent = body.lines().skip(1).map(|l| &l[..l.len() - l.trim_start().len()]).find(|w| !w.is_empty());
+        match indent {
+            Some(w) => Self { indent: Some(w.to_string()), trailing_newline },
+            None => Self::lerobot(),
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::lerobot()
+    }
+}
+
+/// Serializes `profile` in `style`, joints in their current order.
+pub fn to_vec(profile: &Profile, style: &Style) -> Vec<u8> {
+    let mut out = match &style.indent {
+        Some(indent) => {
+            let mut out = Vec::new();
+            let mut ser = Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+            profile.serialize(&mut ser).expect("profiles always serialize");
+            out
+        }
+        None => serde_json::to_vec(profile).expect("profiles always serialize"),
+    };
+    if style.trailing_newline {
+        out.push(b'\n');
+    }
+    out
+}
+
+/// `profile` in LeRobot's layout and kinematic joint order.
+pub fn canonical(profile: &Profile, ty: Option<&super::registry::RobotType>) -> Vec<u8> {
+    let mut p = profile.clone();
+    p.canonicalize(ty);
+    to_vec(&p, &Style::lerobot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::registry;
+
+    const LEROBOT: &str = "{\n    \"shoulder_pan\": {\n        \"id\": 1,\n        \"drive_mode\": 0,\n        \"homing_offset\": -1470,\n        \"range_min\": 758,\n        \"range_max\": 3292\n    },\n    \"gripper\": {\n        \"id\": 6,\n        \"drive_mode\": 0,\n        \"homing_offset\": 1407,\n        \"range_min\": 2031,\n        \"range_max\": 3476\n    }\n}";
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        let profile: Profile = serde_json::from_str(LEROBOT).unwrap();
+        assert_eq!(profile.0.keys().collect::<Vec<_>>(), ["shoulder_pan", "gripper"]);
+        assert_eq!(Style::detect(LEROBOT.as_bytes()), Style::lerobot());
+        assert_eq!(to_vec(&profile, &Style::detect(LEROBOT.as_bytes())), LEROBOT.as_bytes());
+
+        let two = serde_json::to_string_pretty(&profile).unwrap() + "\n";
+        let style = Style::detect(two.as_bytes());
+        assert_eq!(style, Style { indent: Some("  ".into()), trailing_newline: true });
+        assert_eq!(to_vec(&profile, &style), two.as_bytes());
+        let compact = serde_json::to_string(&profile).unwrap();
+        assert_eq!(to_vec(&profile, &Style::detect(compact.as_bytes())), compact.as_bytes());
+    }
+
+    #[test]
+    fn canonical_order_follows_the_robot_type() {
+        let ty = registry::lookup("so101_follower").unwrap();
+        let mut shuffled = ty.template();
+        shuffled.0.reverse();
+        let bytes = canonical(&shuffled, Some(ty));
+        let back: Profile = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(back.0.keys().map(String::as_str).collect::<Vec<_>>(), ty.joints.iter().map(|j| j.name).collect::<Vec<_>>());
+        assert_eq!(bytes, to_vec(&ty.template(), &Style::lerobot()));
+
+        let mut untyped = ty.template();
+        untyped.0.reverse();
+        untyped.canonicalize(None);
+        assert_eq!(untyped.0.values().map(|j| j.id).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+    }
+}
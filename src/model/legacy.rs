@@ -0,0 +1,290 @@
+//! LeRobot's legacy calibration layout.
+//!
+//! Older releases stored calibration as parallel arrays indexed by motor:
+//!
+//! ```json
+//! {"homing_offset": [...], "drive_mode": [...], "start_pos": [...], "end_pos": [...],
+//!  "calib_mode": ["DEGREE", ..., "LINEAR"], "motor_names": ["shoulder_pan", ..., "gripper"]}
+//! ```
+//!
+//! The layouts do not map one to one: legacy files carry no servo ids, and
+//! `start_pos`/`end_pos` are the two reference poses of the old calibration
+//! routine, which only bound the motion of `LINEAR` (gripper) joints.
+//! Conversions in both directions list what they could not carry over.
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::registry::RobotType;
+use super::rules::Limits;
+use super::{Joint, Profile};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CalibMode {
+    /// Rotating joint read in degrees around the reference poses.
+    Degree,
+    /// Joint read as 0..100 % between `start_pos` and `end_pos`.
+    Linear,
+}
+
+/// A legacy calibration file, field order as LeRobot wrote it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Legacy {
+    pub homing_offset: Vec<i32>,
+    pub drive_mode: Vec<i32>,
+    pub start_pos: Vec<i32>,
+    pub end_pos: Vec<i32>,
+    pub calib_mode: Vec<CalibMode>,
+    pub motor_names: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum LegacyError {
+    #[error("not a legacy calibration: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("`{field}` has {len} entries but `motor_names` has {expected}")]
+    Length { field: &'static str, len: usize, expected: usize },
+    #[error("motor `{0}` is listed twice")]
+    Duplicate(String),
+}
+
+/// Something a conversion could not carry over as is.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Loss {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub joint: Option<String>,
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl Loss {
+    fn new(joint: Option<&str>, field: &'static str, message: String) -> Self {
+        Self { joint: joint.map(str::to_string), field, message }
+    }
+}
+
+impl std::fmt::Display for Loss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.joint {
+            Some(joint) => write!(f, "joint `{}`: {}", joint, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Which layout an imported file was in.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    Current,
+    Legacy,
+}
+
+/// A file converted to a profile.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Import {
+    pub layout: Layout,
+    pub profile: Profile,
+    pub losses: Vec<Loss>,
+}
+
+/// Whether `value` looks like a legacy file rather than a profile.
+pub fn is_legacy(value: &serde_json::Value) -> bool {
+    value.get("motor_names").is_some_and(serde_json::Value::is_array)
+}
+
+/// Reads a calibration file in either layout; legacy files are converted
+/// with `Legacy::to_profile`.
+pub fn import(value: serde_json::Value, ty: Option<&RobotType>) -> Result<Import, LegacyError> {
+    if !is_legacy(&value) {
+        return Ok(Import { layout: Layout::Current, profile: serde_json::from_value(value)?, losses: Vec::new() });
+    }
+    let (profile, losses) = Legacy::from_value(value)?.to_profile(ty);
+    Ok(Import { layout: Layout::Legacy, profile, losses })
+}
+
+impl Legacy {
+    /// Parses and checks that every array has one entry per motor.
+    pub fn from_value(value: serde_json::Value) -> Result<Self, LegacyError> {
+        let legacy: Legacy = serde_json::from_value(value)?;
+        let expected = legacy.motor_names.len();
+        for (field, len) in [
+            ("homing_offset", legacy.homing_offset.len()),
+            ("drive_mode", legacy.drive_mode.len()),
+            ("start_pos", legacy.start_pos.len()),
+            ("end_pos", legacy.end_pos.len()),
+            ("calib_mode", legacy.calib_mode.len()),
+        ] {
+            if len != expected {
+                return Err(LegacyError::Length { field, len, expected });
+            }
+        }
+        for (i, name) in legacy.motor_names.iter().enumerate() {
+            if legacy.motor_names[..i].contains(name) {
+                return Err(LegacyError::Duplicate(name.clone()));
+            }
+        }
+        Ok(legacy)
+    }
+
+    /// Converts to a profile. Servo ids come from `ty`'s joint table, or
+    /// from the motor's position when there is no type.
+    pub fn to_profile(&self, ty: Option<&RobotType>) -> (Profile, Vec<Loss>) {
+        let limits = Limits::for_type(ty);
+        let top = limits.resolution - 1;
+        let mut losses = Vec::new();
+        if ty.is_none() {
+            losses.push(Loss::new(None, "id", format!("legacy files have no servo ids; numbered by position 1..={}", self.motor_names.len())));
+        }
+        let mut joints = IndexMap::new();
+        for (i, name) in self.motor_names.iter().enumerate() {
+            let at = Some(name.as_str());
+            let id = match ty.map(|t| t.joint(name)) {
+                Some(Some(spec)) => spec.id as i32,
+                Some(None) => {
+                    losses.push(Loss::new(at, "id", format!("not in the {} joint table; id set to its position {}", ty.map_or("", |t| t.name), i + 1)));
+                    i as i32 + 1
+                }
+                None => i as i32 + 1,
+            };
+            let mut homing_offset = self.homing_offset[i];
+            if homing_offset.abs() > limits.homing_max {
+                let clamped = homing_offset.clamp(-limits.homing_max, limits.homing_max);
+                losses.push(Loss::new(at, "homing_offset", format!("homing_offset {} clamped to {}", homing_offset, clamped)));
+                homing_offset = clamped;
+            }
+            let (start, end) = (self.start_pos[i], self.end_pos[i]);
+            let (range_min, range_max) = match self.calib_mode[i] {
+                CalibMode::Degree => {
+                    losses.push(Loss::new(at, "range", format!("DEGREE mode: start_pos {} / end_pos {} are reference poses, not limits; range set to 0..={}", start, end, top)));
+                    (0, top)
+                }
+                CalibMode::Linear => {
+                    if start > end {
+                        losses.push(Loss::new(at, "range", format!("start_pos {} is above end_pos {}; the direction is not kept", start, end)));
+                    }
+                    let (lo, hi) = (start.min(end), start.max(end));
+                    let (min, max) = (lo.clamp(0, top), hi.clamp(0, top));
+                    if (min, max) != (lo, hi) {
+                        losses.push(Loss::new(at, "range", format!("range {}..{} clamped to {}..{}", lo, hi, min, max)));
+                    }
+                    (min, max)
+                }
+            };
+            joints.insert(name.clone(), Joint { id, drive_mode: self.drive_mode[i], homing_offset, range_min, range_max, ..Default::default() });
+        }
+        (Profile::new(joints), losses)
+    }
+
+    /// Converts a profile back, joints in profile order. Joints named
+    /// `gripper` are `LINEAR`, all others `DEGREE`.
+    pub fn from_profile(profile: &Profile) -> (Self, Vec<Loss>) {
+        let mut losses = Vec::new();
+        if !profile.0.is_empty() {
+            losses.push(Loss::new(None, "id", "servo ids are not stored in the legacy layout".into()));
+        }
+        let mut legacy = Legacy { homing_offset: vec![], drive_mode: vec![], start_pos: vec![], end_pos: vec![], calib_mode: vec![], motor_names: vec![] };
+        for (name, joint) in &profile.0 {
+            let mode = if name == "gripper" { CalibMode::Linear } else { CalibMode::Degree };
+            if mode == CalibMode::Degree {
+                losses.push(Loss::new(Some(name), "range", "range_min/range_max become the DEGREE reference poses start_pos/end_pos".into()));
+            }
+            for key in joint.extra.keys() {
+                losses.push(Loss::new(Some(name), "extra", format!("unknown field `{}` dropped", key)));
+            }
+            legacy.motor_names.push(name.clone());
+            legacy.homing_offset.push(joint.homing_offset);
+            legacy.drive_mode.push(joint.drive_mode);
+            legacy.start_pos.push(joint.range_min);
+            legacy.end_pos.push(joint.range_max);
+            legacy.calib_mode.push(mode);
+        }
+        for key in profile.1.keys() {
+            losses.push(Loss::new(None, "extra", format!("unknown top-level field `{}` dropped", key)));
+        }
+        (legacy, losses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::registry;
+    use serde_json::json;
+
+    fn legacy() -> serde_json::Value {
+        json!({
+            "homing_offset": [2048, -3072, 1024],
+            "drive_mode": [0, 1, 0],
+            "start_pos": [1024, 2048, 2500],
+            "end_pos": [2048, 3072, 1400],
+            "calib_mode": ["DEGREE", "DEGREE", "LINEAR"],
+            "motor_names": ["shoulder_pan", "elbow_flex", "gripper"]
+        })
+    }
+
+    fn import_untyped() -> Import {
+        import(legacy(), None).unwrap()
+    }
+
+    #[test]
+    fn converts_legacy_files_and_reports_losses() {
+        let ty = registry::lookup("so101_follower").unwrap();
+        let import = import(legacy(), Some(ty)).unwrap();
+        assert_eq!(import.layout, Layout::Legacy);
+        let p = &import.profile;
+        assert_eq!(p.0.keys().collect::<Vec<_>>(), ["shoulder_pan", "elbow_flex", "gripper"]);
+        assert_eq!(p.0["gripper"], Joint { id: 6, drive_mode: 0, homing_offset: 1024, range_min: 1400, range_max: 2500, ..Default::default() });
+        assert_eq!((p.0["elbow_flex"].id, p.0["elbow_flex"].homing_offset, p.0["elbow_flex"].range_max), (3, -2047, 4095));
+        let lossy: Vec<(Option<&str>, &str)> = import.losses.iter().map(|l| (l.joint.as_deref(), l.field)).collect();
+        assert_eq!(
+            lossy,
+            [
+                (Some("shoulder_pan"), "homing_offset"),
+                (Some("shoulder_pan"), "range"),
+                (Some("elbow_flex"), "homing_offset"),
+                (Some("elbow_flex"), "range"),
+                (Some("gripper"), "range"),
+            ]
+        );
+        assert_eq!(import.losses[0].to_string(), "joint `shoulder_pan`: homing_offset 2048 clamped to 2047");
+
+        // without a type, ids follow the array order
+        let untyped = import_untyped();
+        assert_eq!(untyped.profile.0.values().map(|j| j.id).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(untyped.losses[0].field, "id");
+    }
+
+    #[test]
+    fn rejects_malformed_legacy_files() {
+        let mut v = legacy();
+        v["end_pos"] = json!([1, 2]);
+        assert!(matches!(import(v, None), Err(LegacyError::Length { field: "end_pos", len: 2, expected: 3 })));
+        let mut v = legacy();
+        v["motor_names"][1] = json!("shoulder_pan");
+        assert!(matches!(import(v, None), Err(LegacyError::Duplicate(_))));
+        let mut v = legacy();
+        v["calib_mode"][0] = json!("RADIAN");
+        assert!(matches!(import(v, None), Err(LegacyError::Parse(_))));
+        // the current layout passes through untouched
+        let p = registry::lookup("so101_leader").unwrap().template();
+        let import = import(serde_json::to_value(&p).unwrap(), None).unwrap();
+        assert_eq!((import.layout, import.profile, import.losses.len()), (Layout::Current, p, 0));
+    }
+
+    #[test]
+    fn exports_back_to_the_legacy_layout() {
+        let mut p = import_untyped().profile;
+        p.0.get_mut("gripper").unwrap().extra.insert("torque_limit".into(), json!(500));
+        let (back, losses) = Legacy::from_profile(&p);
+        assert_eq!(back.motor_names, ["shoulder_pan", "elbow_flex", "gripper"]);
+        assert_eq!(back.calib_mode, [CalibMode::Degree, CalibMode::Degree, CalibMode::Linear]);
+        assert_eq!((back.start_pos[2], back.end_pos[2], back.homing_offset[1]), (1400, 2500, -2047));
+        assert_eq!(losses.iter().map(|l| l.field).collect::<Vec<_>>(), ["id", "range", "range", "extra"]);
+        // what the export writes converts back to the same profile
+        let (again, _) = Legacy::from_value(serde_json::to_value(&back).unwrap()).unwrap().to_profile(None);
+        assert_eq!(again.0["gripper"], Joint { extra: Default::default(), ..p.0["gripper"].clone() });
+    }
+}
@@ -0,0 +1,286 @@
+//! CSV and YAML encodings of a profile, for spreadsheets and hand editing.
+//!
+//! CSV has one row per joint under a `joint,id,drive_mode,homing_offset,
+//! range_min,range_max` header; extra columns are the joint's unknown fields
+//! (cells holding JSON are read as JSON, anything else as text). Top-level
+//! fields have no place in a sheet and are left out. YAML maps one to one
+//! onto the JSON layout. JSON stays the on-disk format.
+//!
+//! Parsing records where every joint and field came from, so errors can
+//! point at a row (CSV, the header being row 1) or line (YAML) and column.
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::{Extra, Joint, Profile};
+
+/// Encodings a profile can be sent or fetched in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Media {
+    #[default]
+    Json,
+    Csv,
+    Yaml,
+}
+
+impl Media {
+    /// From a `Content-Type` or `Accept` media type, ignoring parameters.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime.split(';').next().unwrap_or("").trim().to_ascii_lowercase().as_str() {
+            "application/json" => Some(Self::Json),
+            "text/csv" => Some(Self::Csv),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// First supported type of an `Accept` header, in the order listed.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(Self::from_mime)
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Yaml => "yaml",
+        }
+    }
+}
+
+impl std::str::FromStr for Media {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(format!("unknown format `{}` (expected json, csv or yaml)", s)),
+        }
+    }
+}
+
+/// A 1-based row (or line) and column in the uploaded text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// Where each joint, and each field of it, was found.
+#[derive(Debug, Clone, Default)]
+pub struct Positions(HashMap<(String, String), Position>);
+
+impl Positions {
+    fn set(&mut self, joint: &str, field: &str, at: Position) {
+        self.0.entry((joint.to_string(), field.to_string())).or_insert(at);
+    }
+
+    /// Position of `field` of `joint`, or of the joint itself.
+    pub fn find(&self, joint: &str, field: Option<&str>) -> Option<Position> {
+        field.and_then(|f| self.0.get(&(joint.to_string(), f.to_string()))).or_else(|| self.0.get(&(joint.to_string(), String::new()))).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize)]
+#[error("row {row}, column {column}{}: {message}", field.as_deref().map(|f| format!(" ({})", f)).unwrap_or_default())]
+pub struct ParseError {
+    pub row: usize,
+    pub column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(row: usize, column: usize, field: Option<&str>, message: impl Into<String>) -> Self {
+        Self { row, column, field: field.map(str::to_string), message: message.into() }
+    }
+}
+
+const COLUMNS: [&str; 6] = ["joint", "id", "drive_mode", "homing_offset", "range_min", "range_max"];
+
+pub fn to_csv(profile: &Profile) -> Vec<u8> {
+    let mut extra: Vec<&str> = Vec::new();
+    for key in profile.0.values().flat_map(|j| j.extra.keys()) {
+        if !extra.contains(&key.as_str()) {
+            extra.push(key);
+        }
+    }
+    let mut w = csv::Writer::from_writer(Vec::new());
+    let header: Vec<&str> = COLUMNS.iter().copied().chain(extra.iter().copied()).collect();
+    // writing to a Vec cannot fail
+    w.write_record(&header).expect("in-memory write");
+    for (name, j) in &profile.0 {
+        let mut row = vec![name.clone(), j.id.to_string(), j.drive_mode.to_string(), j.homing_offset.to_string(), j.range_min.to_string(), j.range_max.to_string()];
+        row.extend(extra.iter().map(|k| match j.extra.get(*k) {
+            None => String::new(),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+        }));
+        w.write_record(&row).expect("in-memory write");
+    }
+    w.into_inner().expect("in-memory write")
+}
+
+pub fn from_csv(bytes: &[u8]) -> Result<(Profile, Positions), ParseError> {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let mut r = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(bytes);
+    let csv_error = |e: csv::Error| {
+        let row = e.position().map_or(0, |p| p.line() as usize);
+        ParseError::new(row, 0, None, e.to_string())
+    };
+    let header: Vec<String> = r.headers().map_err(csv_error)?.iter().map(str::to_string).collect();
+    let mut index = HashMap::new();
+    for (i, name) in header.iter().enumerate() {
+        if index.insert(name.as_str(), i).is_some() {
+            return Err(ParseError::new(1, i + 1, Some(name), "duplicate column"));
+        }
+    }
+    for col in COLUMNS {
+        if !index.contains_key(col) {
+            return Err(ParseError::new(1, header.len() + 1, Some(col), format!("missing column `{}`", col)));
+        }
+    }
+
+    let mut joints = IndexMap::new();
+    let mut positions = Positions::default();
+    for record in r.records() {
+        let record = record.map_err(csv_error)?;
+        let row = record.position().map_or(0, |p| p.line() as usize);
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let cell = |col: &str| (index[col] + 1, record.get(index[col]).unwrap_or(""));
+        let (column, name) = cell("joint");
+        if name.is_empty() {
+            return Err(ParseError::new(row, column, Some("joint"), "joint name is empty"));
+        }
+        if joints.contains_key(name) {
+            return Err(ParseError::new(row, column, Some("joint"), format!("joint `{}` is listed twice", name)));
+        }
+        positions.set(name, "", Position { row, column });
+        let mut values = [0i32; 5];
+        for (value, field) in values.iter_mut().zip(&COLUMNS[1..]) {
+            let (column, text) = cell(field);
+            positions.set(name, field, Position { row, column });
+            *value = text.parse().map_err(|_| ParseError::new(row, column, Some(field), format!("`{}` is not an integer", text)))?;
+        }
+        let mut extra = Extra::new();
+        for (i, key) in header.iter().enumerate().filter(|(_, k)| !COLUMNS.contains(&k.as_str())) {
+            let text = record.get(i).unwrap_or("");
+            if !text.is_empty() {
+                positions.set(name, key, Position { row, column: i + 1 });
+                extra.insert(key.clone(), serde_json::from_str(text).unwrap_or_else(|_| serde_json::Value::String(text.to_string())));
+            }
+        }
+        let [id, drive_mode, homing_offset, range_min, range_max] = values;
+        joints.insert(name.to_string(), Joint { id, drive_mode, homing_offset, range_min, range_max, extra });
+    }
+    Ok((Profile::new(joints), positions))
+}
+
+pub fn to_yaml(profile: &Profile) -> Vec<u8> {
+    serde_yaml::to_string(profile).expect("profiles serialize").into_bytes()
+}
+
+pub fn from_yaml(bytes: &[u8]) -> Result<(Profile, Positions), ParseError> {
+    let profile: Profile = serde_yaml::from_slice(bytes).map_err(|e| {
+        let (row, column) = e.location().map_or((0, 0), |l| (l.line(), l.column()));
+        ParseError::new(row, column, None, e.to_string())
+    })?;
+    Ok((profile, yaml_positions(&String::from_utf8_lossy(bytes))))
+}
+
+/// Lines of top-level keys and of the keys directly under them, for block
+/// style YAML; flow style (`{...}`) joints are only located by their key.
+fn yaml_positions(text: &str) -> Positions {
+    let mut positions = Positions::default();
+    let mut joint: Option<(String, Option<usize>)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+            continue;
+        }
+        let Some((key, _)) = content.split_once(':') else { continue };
+        let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+        let indent = line.len() - content.len();
+        let at = Position { row: i + 1, column: indent + 1 };
+        if indent == 0 {
+            positions.set(key, "", at);
+            joint = Some((key.to_string(), None));
+        } else if let Some((name, child)) = &mut joint
+            && *child.get_or_insert(indent) == indent
+        {
+            positions.set(name, key, at);
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::registry;
+
+    #[test]
+    fn csv_round_trips_and_keeps_extra_columns() {
+        let mut p = registry::lookup("so101_follower").unwrap().template();
+        p.0.get_mut("gripper").unwrap().extra.insert("torque_limit".into(), serde_json::json!(500));
+        p.0.get_mut("gripper").unwrap().extra.insert("note".into(), serde_json::json!("worn gear"));
+        let text = to_csv(&p);
+        let lines: Vec<&str> = std::str::from_utf8(&text).unwrap().lines().collect();
+        assert_eq!(lines[0], "joint,id,drive_mode,homing_offset,range_min,range_max,torque_limit,note");
+        assert_eq!(lines[1], "shoulder_pan,1,0,0,0,4095,,");
+        assert_eq!(lines[6], "gripper,6,0,0,0,4095,500,worn gear");
+        let (back, positions) = from_csv(&text).unwrap();
+        assert_eq!(back, p);
+        assert_eq!(positions.find("gripper", Some("range_max")), Some(Position { row: 7, column: 6 }));
+        assert_eq!(positions.find("gripper", Some("nope")), Some(Position { row: 7, column: 1 }));
+
+        // spreadsheet exports: BOM, padding, columns in any order, blank rows
+        let sheet = "\u{feff}range_max, range_min ,homing_offset,drive_mode,id,joint\n3000,1000,-5,1,2,lift\n,,,,,\n";
+        let (p, _) = from_csv(sheet.as_bytes()).unwrap();
+        assert_eq!(p.0["lift"], Joint { id: 2, drive_mode: 1, homing_offset: -5, range_min: 1000, range_max: 3000, ..Default::default() });
+    }
+
+    #[test]
+    fn csv_errors_point_at_the_cell() {
+        let err = from_csv(b"joint,id,drive_mode,homing_offset,range_min,range_max\npan,1,0,0,10,20\nlift,two,0,0,10,20\n").unwrap_err();
+        assert_eq!((err.row, err.column, err.field.as_deref()), (3, 2, Some("id")));
+        assert_eq!(err.to_string(), "row 3, column 2 (id): `two` is not an integer");
+        let err = from_csv(b"joint,id,drive_mode,homing_offset,range_min\n").unwrap_err();
+        assert_eq!((err.row, err.field.as_deref()), (1, Some("range_max")));
+        let err = from_csv(b"joint,id,drive_mode,homing_offset,range_min,range_max\npan,1,0,0,10,20\npan,2,0,0,10,20\n").unwrap_err();
+        assert_eq!((err.row, err.column), (3, 1));
+        let err = from_csv(b"joint,id,drive_mode,homing_offset,range_min,range_max\npan,1,0\n").unwrap_err();
+        assert_eq!(err.row, 2);
+    }
+
+    #[test]
+    fn yaml_round_trips_and_locates_fields() {
+        let p = registry::lookup("so101_leader").unwrap().template();
+        let text = to_yaml(&p);
+        assert!(std::str::from_utf8(&text).unwrap().starts_with("shoulder_pan:\n  id: 1\n"));
+        let (back, positions) = from_yaml(&text).unwrap();
+        assert_eq!(back, p);
+        assert_eq!(positions.find("shoulder_pan", Some("range_max")), Some(Position { row: 6, column: 3 }));
+        assert_eq!(positions.find("gripper", None).map(|p| p.row), Some(31));
+
+        let err = from_yaml(b"pan:\n  id: 1\n  drive_mode: [\n").unwrap_err();
+        assert!(err.row >= 3, "{:?}", err);
+        assert!(Media::from_accept("text/html, text/csv;q=0.9").is_some_and(|m| m == Media::Csv));
+        assert_eq!(Media::from_mime("application/x-yaml"), Some(Media::Yaml));
+    }
+}
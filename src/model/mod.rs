@@ -1,47 +1,267 @@
-use serde::{Deserialize, Serialize};
+use indexmap::IndexMap;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub mod format;
+pub mod legacy;
+pub mod media;
+pub mod registry;
+pub mod rules;
+
+/// Fields this tool does not know, kept as read so rewriting a file loses nothing.
+pub type Extra = IndexMap<String, serde_json::Value>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Joint {
     pub id: i32,
     pub drive_mode: i32,
     pub homing_offset: i32,
     pub range_min: i32,
     pub range_max: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 impl Joint {
+    /// First error the STS3215 rules find in this joint; see `rules` for all of them.
     pub fn validate(&self) -> Result<(), String> {
-        if self.id <= 0 {
-            return Err("id must be > 0".into());
-        }
-        if self.range_min >= self.range_max {
-            return Err("range_min must be < range_max".into());
+        let mut report = rules::Report::default();
+        rules::check_joint("", self, &rules::Limits::default(), &mut report);
+        match report.errors().next() {
+            Some(issue) => Err(issue.message.clone()),
+            None => Ok(()),
         }
-        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct Profile(pub std::collections::HashMap<String, Joint>);
+/// Joints by name, in file order, and the top-level entries that are not
+/// joints (written after the joints).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile(pub IndexMap<String, Joint>, pub Extra);
 
 impl Profile {
+    pub fn new(joints: IndexMap<String, Joint>) -> Self {
+        Self(joints, Extra::new())
+    }
+
+    /// Copies the unknown fields of `old` onto this profile, joint by joint,
+    /// for profiles rebuilt from scratch (e.g. read back from the servos).
+    pub fn keep_extra_from(&mut self, old: &Profile) {
+        for (name, joint) in self.0.iter_mut() {
+            if let Some(o) = old.0.get(name) {
+                joint.extra = o.extra.clone();
+            }
+        }
+        self.1 = old.1.clone();
+    }
+
+    /// Puts joints in kinematic order: the order `ty` lists them in, then any
+    /// other joints by servo id. Untyped profiles are ordered by servo id.
+    pub fn canonicalize(&mut self, ty: Option<&registry::RobotType>) {
+        let rank = |name: &str| ty.and_then(|t| t.joints.iter().position(|j| j.name == name)).unwrap_or(usize::MAX);
+        self.0.sort_by(|a, ja, b, jb| rank(a).cmp(&rank(b)).then(ja.id.cmp(&jb.id)).then(a.cmp(b)));
+    }
+
+    /// Every rule error in this profile, joined; warnings are ignored.
     pub fn validate(&self) -> Result<(), String> {
-        for (name, joint) in &self.0 {
-            joint
-                .validate()
-                .map_err(|e| format!("joint `{}` invalid: {}", name, e))?;
+        let report = rules::check(self, None);
+        if report.has_errors() {
+            return Err(report.errors().map(ToString::to_string).collect::<Vec<_>>().join("; "));
         }
         Ok(())
     }
 }
 
+impl Serialize for Profile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len() + self.1.len()))?;
+        for (name, joint) in &self.0 {
+            map.serialize_entry(name, joint)?;
+        }
+        for (key, value) in &self.1 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Profile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Entry {
+            Joint(Joint),
+            Other(serde_json::Value),
+        }
+        let mut profile = Profile::default();
+        for (key, entry) in IndexMap::<String, Entry>::deserialize(deserializer)? {
+            match entry {
+                Entry::Joint(joint) => {
+                    profile.0.insert(key, joint);
+                }
+                Entry::Other(value) => {
+                    profile.1.insert(key, value);
+                }
+            }
+        }
+        Ok(profile)
+    }
+}
+
+/// One difference between two profiles.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Change {
+    Added { joint: String, value: Joint },
+    Removed { joint: String, value: Joint },
+    Modified { joint: String, field: &'static str, before: i32, after: i32 },
+    /// An unknown field of `joint`, or a top-level one when `joint` is `None`.
+    Extra { joint: Option<String>, key: String, before: Option<serde_json::Value>, after: Option<serde_json::Value> },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { joint, .. } => write!(f, "+{}", joint),
+            Change::Removed { joint, .. } => write!(f, "-{}", joint),
+            Change::Modified { joint, field, before, after } => write!(f, "{}.{} {} -> {}", joint, field, before, after),
+            Change::Extra { joint, key, before, after } => {
+                let show = |v: &Option<serde_json::Value>| v.as_ref().map_or("(none)".to_string(), ToString::to_string);
+                match joint {
+                    Some(joint) => write!(f, "{}.{} {} -> {}", joint, key, show(before), show(after)),
+                    None => write!(f, "{} {} -> {}", key, show(before), show(after)),
+                }
+            }
+        }
+    }
+}
+
+impl Joint {
+    /// Names of the numeric fields, in `fields()` order.
+    pub const FIELDS: [&'static str; 5] = ["id", "drive_mode", "homing_offset", "range_min", "range_max"];
+
+    fn fields(&self) -> [(&'static str, i32); 5] {
+        let values = [self.id, self.drive_mode, self.homing_offset, self.range_min, self.range_max];
+        std::array::from_fn(|i| (Self::FIELDS[i], values[i]))
+    }
+}
+
+/// Field-level changes turning `before` into `after`, sorted by joint name,
+/// followed by changed top-level fields.
+pub fn diff(before: &Profile, after: &Profile) -> Vec<Change> {
+    let mut joints = compare(before, after);
+    joints.sort_by(|a, b| a.joint.cmp(&b.joint));
+    let mut changes = Vec::new();
+    for j in joints {
+        match j.status {
+            JointStatus::Removed => changes.push(Change::Removed { value: before.0[&j.joint].clone(), joint: j.joint }),
+            JointStatus::Added => changes.push(Change::Added { value: after.0[&j.joint].clone(), joint: j.joint }),
+            JointStatus::Same | JointStatus::Changed => {
+                changes.extend(j.fields.iter().filter_map(|f| match (f.left, f.right) {
+                    (Some(before), Some(after)) if before != after => Some(Change::Modified { joint: j.joint.clone(), field: f.field, before, after }),
+                    _ => None,
+                }));
+                changes.extend(j.extra.into_iter().map(|e| Change::Extra { joint: Some(j.joint.clone()), key: e.key, before: e.left, after: e.right }));
+            }
+        }
+    }
+    changes.extend(compare_extra(&before.1, &after.1).into_iter().map(|e| Change::Extra { joint: None, key: e.key, before: e.left, after: e.right }));
+    changes
+}
+
+/// How a joint differs between two profiles.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JointStatus {
+    Same,
+    Changed,
+    /// Only in the right-hand profile.
+    Added,
+    /// Only in the left-hand profile.
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub left: Option<i32>,
+    pub right: Option<i32>,
+    /// `right - left` when both sides have the field.
+    pub delta: Option<i32>,
+}
+
+impl FieldDiff {
+    pub fn changed(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+/// An unknown field that differs; `None` where a side lacks it.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ExtraDiff {
+    pub key: String,
+    pub left: Option<serde_json::Value>,
+    pub right: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct JointDiff {
+    pub joint: String,
+    pub status: JointStatus,
+    pub fields: Vec<FieldDiff>,
+    /// Unknown fields that differ, for joints on both sides.
+    pub extra: Vec<ExtraDiff>,
+}
+
+/// Keys of `left` and `right` whose values differ, in first-seen order.
+pub fn compare_extra(left: &Extra, right: &Extra) -> Vec<ExtraDiff> {
+    let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+    let mut seen = std::collections::HashSet::new();
+    keys.retain(|k| seen.insert(*k));
+    keys.into_iter()
+        .filter(|k| left.get(*k) != right.get(*k))
+        .map(|k| ExtraDiff { key: k.clone(), left: left.get(k).cloned(), right: right.get(k).cloned() })
+        .collect()
+}
+
+/// Side-by-side comparison of every joint and field, ordered by servo id.
+pub fn compare(left: &Profile, right: &Profile) -> Vec<JointDiff> {
+    let mut names: Vec<(i32, &String)> = left.0.iter().chain(right.0.iter()).map(|(n, j)| (j.id, n)).collect();
+    names.sort();
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|(_, n)| seen.insert(*n));
+    names
+        .into_iter()
+        .map(|(_, name)| {
+            let (l, r) = (left.0.get(name), right.0.get(name));
+            let values = |j: Option<&Joint>| j.map(|j| j.fields().map(|(_, v)| Some(v))).unwrap_or_default();
+            let fields: Vec<FieldDiff> = Joint::FIELDS
+                .into_iter()
+                .zip(values(l).into_iter().zip(values(r)))
+                .map(|(field, (left, right))| FieldDiff { field, left, right, delta: left.zip(right).map(|(a, b)| b - a) })
+                .collect();
+            let extra = match (l, r) {
+                (Some(l), Some(r)) => compare_extra(&l.extra, &r.extra),
+                _ => Vec::new(),
+            };
+            let status = match (l, r) {
+                (Some(_), None) => JointStatus::Removed,
+                (None, Some(_)) => JointStatus::Added,
+                _ if fields.iter().any(FieldDiff::changed) || !extra.is_empty() => JointStatus::Changed,
+                _ => JointStatus::Same,
+            };
+            JointDiff { joint: name.clone(), status, fields, extra }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn joint_validation() {
-        let ok = Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 10, range_max: 20 };
+        let ok = Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 10, range_max: 20, ..Default::default() };
         assert!(ok.validate().is_ok());
 
         let bad_id = Joint { id: 0, ..ok.clone() };
@@ -53,13 +273,69 @@ mod tests {
 
     #[test]
     fn profile_validation() {
-        let mut map = std::collections::HashMap::new();
+        let mut map = indexmap::IndexMap::new();
         map.insert(
             "shoulder_pan".to_string(),
-            Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 100, range_max: 200 },
+            Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 100, range_max: 200, ..Default::default() },
         );
-        let profile = Profile(map);
+        let profile = Profile::new(map);
         assert!(profile.validate().is_ok());
     }
-}
 
+    #[test]
+    fn diff_lists_field_changes() {
+        let j = Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 100, range_max: 200, ..Default::default() };
+        let mut a = indexmap::IndexMap::new();
+        a.insert("wrist".to_string(), j.clone());
+        a.insert("elbow".to_string(), Joint { id: 2, ..j.clone() });
+        let mut b = a.clone();
+        b.get_mut("wrist").unwrap().range_max = 250;
+        b.shift_remove("elbow");
+        b.insert("gripper".to_string(), Joint { id: 3, ..j });
+        let changes = diff(&Profile::new(a), &Profile::new(b));
+        let text: Vec<String> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(text, ["-elbow", "+gripper", "wrist.range_max 200 -> 250"]);
+    }
+
+    #[test]
+    fn diff_includes_unknown_fields() {
+        let a: Profile = serde_json::from_str(r#"{"wrist": {"id": 4, "drive_mode": 0, "homing_offset": 0, "range_min": 10, "range_max": 20, "torque_limit": 500}, "version": 1}"#).unwrap();
+        let b: Profile = serde_json::from_str(r#"{"wrist": {"id": 4, "drive_mode": 0, "homing_offset": 0, "range_min": 10, "range_max": 20, "torque_limit": 600}, "version": 2, "note": "x"}"#).unwrap();
+        let text: Vec<String> = diff(&a, &b).iter().map(ToString::to_string).collect();
+        assert_eq!(text, ["wrist.torque_limit 500 -> 600", "version 1 -> 2", "note (none) -> \"x\""]);
+        let rows = compare(&a, &b);
+        assert_eq!(rows[0].status, JointStatus::Changed);
+        assert_eq!(rows[0].extra[0].key, "torque_limit");
+    }
+
+    #[test]
+    fn compare_side_by_side() {
+        let j = Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 100, range_max: 200, ..Default::default() };
+        let mut a = indexmap::IndexMap::new();
+        a.insert("pan".to_string(), j.clone());
+        a.insert("lift".to_string(), Joint { id: 2, ..j.clone() });
+        let mut b = a.clone();
+        b.get_mut("pan").unwrap().homing_offset = -30;
+        b.shift_remove("lift");
+        b.insert("gripper".to_string(), Joint { id: 6, ..j });
+        let rows = compare(&Profile::new(a), &Profile::new(b));
+        let status: Vec<(&str, JointStatus)> = rows.iter().map(|r| (r.joint.as_str(), r.status)).collect();
+        assert_eq!(status, [("pan", JointStatus::Changed), ("lift", JointStatus::Removed), ("gripper", JointStatus::Added)]);
+        assert_eq!(rows[0].fields[2], FieldDiff { field: "homing_offset", left: Some(0), right: Some(-30), delta: Some(-30) });
+        assert_eq!((rows[1].fields[0].left, rows[1].fields[0].right, rows[1].fields[0].delta), (Some(2), None, None));
+    }
+
+    #[test]
+    fn unknown_fields_survive_a_round_trip() {
+        let text = r#"{"wrist": {"id": 4, "drive_mode": 0, "homing_offset": 12, "range_min": 10, "range_max": 20, "note": {"z": 1, "a": [2]}}, "meta": {"tool": "x", "at": 3}}"#;
+        let mut p: Profile = serde_json::from_str(text).unwrap();
+        assert_eq!(p.0["wrist"].extra["note"], serde_json::json!({"z": 1, "a": [2]}));
+        assert_eq!(p.1.keys().collect::<Vec<_>>(), ["meta"]);
+        assert_eq!(serde_json::to_string(&p).unwrap().replace(' ', ""), text.replace(' ', ""));
+
+        p.0.get_mut("wrist").unwrap().range_max = 30;
+        let back: Profile = serde_json::from_str(&serde_json::to_string(&p).unwrap()).unwrap();
+        assert_eq!(back.0["wrist"].extra, p.0["wrist"].extra);
+        assert_eq!(back.1, p.1);
+    }
+}
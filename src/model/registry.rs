@@ -0,0 +1,209 @@
+//! Known robot and teleoperator types.
+//!
+//! Each type lists the joints LeRobot expects in its calibration file, with
+//! servo id and motor model. Profiles stored under a type directory
+//! (`robots/so101_follower/<id>.json`) are checked against it on write.
+
+use serde::Serialize;
+
+use super::{Joint, Profile};
+use crate::bus::Protocol;
+use crate::store::Kind;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct JointSpec {
+    pub name: &'static str,
+    pub id: u8,
+    pub motor: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct RobotType {
+    /// Directory name LeRobot uses, e.g. `so101_follower`.
+    pub name: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+    pub protocol: Protocol,
+    /// Encoder ticks per turn.
+    pub resolution: u32,
+    pub joints: &'static [JointSpec],
+}
+
+const fn joint(name: &'static str, id: u8, motor: &'static str) -> JointSpec {
+    JointSpec { name, id, motor }
+}
+
+const SO_ARM: [JointSpec; 6] = [
+    joint("shoulder_pan", 1, "sts3215"),
+    joint("shoulder_lift", 2, "sts3215"),
+    joint("elbow_flex", 3, "sts3215"),
+    joint("wrist_flex", 4, "sts3215"),
+    joint("wrist_roll", 5, "sts3215"),
+    joint("gripper", 6, "sts3215"),
+];
+
+const KOCH_FOLLOWER: [JointSpec; 6] = [
+    joint("shoulder_pan", 1, "xl430-w250"),
+    joint("shoulder_lift", 2, "xl430-w250"),
+    joint("elbow_flex", 3, "xl330-m288"),
+    joint("wrist_flex", 4, "xl330-m288"),
+    joint("wrist_roll", 5, "xl330-m288"),
+    joint("gripper", 6, "xl330-m288"),
+];
+
+const KOCH_LEADER: [JointSpec; 6] = [
+    joint("shoulder_pan", 1, "xl330-m077"),
+    joint("shoulder_lift", 2, "xl330-m077"),
+    joint("elbow_flex", 3, "xl330-m077"),
+    joint("wrist_flex", 4, "xl330-m077"),
+    joint("wrist_roll", 5, "xl330-m077"),
+    joint("gripper", 6, "xl330-m077"),
+];
+
+const LEKIWI: [JointSpec; 9] = [
+    joint("arm_shoulder_pan", 1, "sts3215"),
+    joint("arm_shoulder_lift", 2, "sts3215"),
+    joint("arm_elbow_flex", 3, "sts3215"),
+    joint("arm_wrist_flex", 4, "sts3215"),
+    joint("arm_wrist_roll", 5, "sts3215"),
+    joint("arm_gripper", 6, "sts3215"),
+    joint("base_left_wheel", 7, "sts3215"),
+    joint("base_back_wheel", 8, "sts3215"),
+    joint("base_right_wheel", 9, "sts3215"),
+];
+
+pub static TYPES: &[RobotType] = &[
+    RobotType { name: "so100_follower", kind: Kind::Robots, description: "SO-100 follower arm", protocol: Protocol::Feetech, resolution: 4096, joints: &SO_ARM },
+    RobotType { name: "so101_follower", kind: Kind::Robots, description: "SO-101 follower arm", protocol: Protocol::Feetech, resolution: 4096, joints: &SO_ARM },
+    RobotType { name: "koch_follower", kind: Kind::Robots, description: "Koch v1.1 follower arm", protocol: Protocol::Dynamixel, resolution: 4096, joints: &KOCH_FOLLOWER },
+    RobotType { name: "lekiwi", kind: Kind::Robots, description: "LeKiwi mobile base with SO-100 arm", protocol: Protocol::Feetech, resolution: 4096, joints: &LEKIWI },
+    RobotType { name: "so100_leader", kind: Kind::Teleoperators, description: "SO-100 leader arm", protocol: Protocol::Feetech, resolution: 4096, joints: &SO_ARM },
+    RobotType { name: "so101_leader", kind: Kind::Teleoperators, description: "SO-101 leader arm", protocol: Protocol::Feetech, resolution: 4096, joints: &SO_ARM },
+    RobotType { name: "koch_leader", kind: Kind::Teleoperators, description: "Koch v1.1 leader arm", protocol: Protocol::Dynamixel, resolution: 4096, joints: &KOCH_LEADER },
+];
+
+pub fn lookup(name: &str) -> Option<&'static RobotType> {
+    TYPES.iter().find(|t| t.name == name)
+}
+
+/// Type of a profile named `<robot_type>/<id>`, if the type is known.
+pub fn type_of(kind: Kind, profile_name: &str) -> Option<&'static RobotType> {
+    let (ty, _) = profile_name.split_once('/')?;
+    lookup(ty).filter(|t| t.kind == kind)
+}
+
+/// Type assumed for profiles outside a type directory.
+pub fn default_for(kind: Kind) -> &'static RobotType {
+    match kind {
+        Kind::Robots => lookup("so101_follower"),
+        Kind::Teleoperators => lookup("so101_leader"),
+    }
+    .expect("default types are registered")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + (ca != *cb) as usize).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+impl RobotType {
+    pub fn joint(&self, name: &str) -> Option<&'static JointSpec> {
+        self.joints.iter().find(|j| j.name == name)
+    }
+
+    /// Joint names and servo ids, as `readback::read_back` takes them.
+    pub fn joint_ids(&self) -> Vec<(&'static str, u8)> {
+        self.joints.iter().map(|j| (j.name, j.id)).collect()
+    }
+
+    /// Closest joint name, for typos of up to three characters.
+    fn suggest(&self, name: &str) -> Option<&'static str> {
+        self.joints.iter().map(|j| (edit_distance(name, j.name), j.name)).filter(|(d, _)| *d <= 3).min().map(|(_, n)| n)
+    }
+
+    /// Problems with `profile` as a calibration of this type: unknown or
+    /// missing joints and ids that differ from the type's.
+    pub fn check(&self, profile: &Profile) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut names: Vec<&String> = profile.0.keys().collect();
+        names.sort();
+        for name in names {
+            match (self.joint(name), self.suggest(name)) {
+                (Some(spec), _) if profile.0[name].id != spec.id as i32 => {
+                    problems.push(format!("joint `{}` has id {}, {} expects {}", name, profile.0[name].id, self.name, spec.id));
+                }
+                (Some(_), _) => {}
+                (None, Some(s)) => problems.push(format!("unknown joint `{}` for {} (did you mean `{}`?)", name, self.name, s)),
+                (None, None) => problems.push(format!("unknown joint `{}` for {}", name, self.name)),
+            }
+        }
+        for spec in self.joints {
+            if !profile.0.contains_key(spec.name) {
+                problems.push(format!("missing joint `{}` (id {})", spec.name, spec.id));
+            }
+        }
+        problems
+    }
+
+    /// Uncalibrated profile with every joint at its id and the full range.
+    pub fn template(&self) -> Profile {
+        Profile::new(
+            self.joints
+                .iter()
+                .map(|j| (j.name.to_string(), Joint { id: j.id as i32, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: self.resolution as i32 - 1, ..Default::default() }))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::KNOWN_MODELS;
+
+    #[test]
+    fn every_motor_is_a_known_model() {
+        for ty in TYPES {
+            for j in ty.joints {
+                assert!(KNOWN_MODELS.iter().any(|t| t.model == j.motor), "{}: unknown motor `{}`", ty.name, j.motor);
+            }
+        }
+    }
+
+    #[test]
+    fn profiles_are_checked_against_their_type() {
+        let t = lookup("so101_follower").unwrap();
+        let mut p = t.template();
+        assert!(t.check(&p).is_empty());
+
+        let pan = p.0.shift_remove("shoulder_pan").unwrap();
+        p.0.insert("sholder_pan".into(), pan);
+        p.0.get_mut("gripper").unwrap().id = 7;
+        assert_eq!(
+            t.check(&p),
+            [
+                "joint `gripper` has id 7, so101_follower expects 6",
+                "unknown joint `sholder_pan` for so101_follower (did you mean `shoulder_pan`?)",
+                "missing joint `shoulder_pan` (id 1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn type_comes_from_the_directory() {
+        assert_eq!(type_of(Kind::Robots, "lekiwi/kiwi").map(|t| t.joints.len()), Some(9));
+        assert!(type_of(Kind::Robots, "so101_leader/arm").is_none());
+        assert!(type_of(Kind::Robots, "arm").is_none());
+        assert_eq!(default_for(Kind::Teleoperators).name, "so101_leader");
+    }
+}
@@ -0,0 +1,268 @@
+//! Hardware-aware validation rules for profiles.
+//!
+//! `check` runs every rule and returns all findings at once. Errors make a
+//! profile unusable on the servos; warnings flag calibrations that are
+//! probably wrong (a joint that barely moves) but can still be written.
+//! Fields the tool does not know are kept on write and reported as warnings.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::registry::RobotType;
+use super::{Joint, Profile};
+use crate::bus::Protocol;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Rule that produced the issue, e.g. `position_range`.
+    pub rule: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub joint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.joint {
+            Some(joint) => write!(f, "joint `{}`: {}", joint, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Register limits of the servos a profile is written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Encoder ticks per turn; positions are `0..resolution`.
+    pub resolution: i32,
+    /// Largest homing offset magnitude the register holds.
+    pub homing_max: i32,
+    /// Highest usable servo id; 253 and up are reserved (254 is broadcast).
+    pub max_id: i32,
+}
+
+impl Limits {
+    /// STS3215: 12-bit positions, homing offset in 11 bits plus a sign bit.
+    pub const FEETECH: Limits = Limits { resolution: 4096, homing_max: 2047, max_id: 252 };
+    /// Dynamixel X-series: 4-byte homing offset limited to ±1024 turns.
+    pub const DYNAMIXEL: Limits = Limits { resolution: 4096, homing_max: 1_044_479, max_id: 252 };
+
+    pub fn for_type(ty: Option<&RobotType>) -> Limits {
+        match ty {
+            Some(t) => Limits {
+                resolution: t.resolution as i32,
+                ..match t.protocol {
+                    Protocol::Feetech => Self::FEETECH,
+                    Protocol::Dynamixel => Self::DYNAMIXEL,
+                }
+            },
+            None => Self::FEETECH,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::FEETECH
+    }
+}
+
+/// Ranges narrower than this fraction of a turn are flagged.
+const NARROW_RANGE_FRACTION: i32 = 10;
+
+/// Findings of one validation run.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Warning)
+    }
+
+    fn push(&mut self, severity: Severity, rule: &'static str, joint: Option<&str>, field: Option<&'static str>, message: String) {
+        self.issues.push(Issue { severity, rule, joint: joint.map(str::to_string), field, message });
+    }
+}
+
+/// Rules that look at one joint on its own.
+pub fn check_joint(name: &str, joint: &Joint, limits: &Limits, report: &mut Report) {
+    let at = Some(name);
+    if joint.id <= 0 || joint.id > limits.max_id {
+        report.push(Severity::Error, "id_range", at, Some("id"), format!("id {} must be within 1..={}", joint.id, limits.max_id));
+    }
+    let top = limits.resolution - 1;
+    for (field, value) in [("range_min", joint.range_min), ("range_max", joint.range_max)] {
+        if !(0..=top).contains(&value) {
+            report.push(Severity::Error, "position_range", at, Some(field), format!("{} {} must be within 0..={}", field, value, top));
+        }
+    }
+    if joint.range_min >= joint.range_max {
+        report.push(Severity::Error, "range_order", at, Some("range_min"), "range_min must be < range_max".into());
+    } else if (joint.range_max - joint.range_min) * NARROW_RANGE_FRACTION < limits.resolution {
+        report.push(
+            Severity::Warning,
+            "narrow_range",
+            at,
+            Some("range_max"),
+            format!("range {}..{} covers less than 1/{} of a turn", joint.range_min, joint.range_max, NARROW_RANGE_FRACTION),
+        );
+    }
+    if joint.homing_offset.abs() > limits.homing_max {
+        report.push(Severity::Error, "homing_offset", at, Some("homing_offset"), format!("homing_offset {} exceeds ±{}", joint.homing_offset, limits.homing_max));
+    }
+    if !matches!(joint.drive_mode, 0 | 1) {
+        report.push(Severity::Error, "drive_mode", at, Some("drive_mode"), format!("drive_mode {} must be 0 or 1", joint.drive_mode));
+    }
+    for key in joint.extra.keys() {
+        report.push(Severity::Warning, "unknown_field", at, None, format!("unknown field `{}` is kept as is", key));
+    }
+}
+
+/// Top-level objects that are not joints.
+pub const METADATA_FIELDS: [&str; 2] = ["meta", "metadata"];
+
+/// Runs every rule over `profile`, including the joint schema of `ty`.
+pub fn check(profile: &Profile, ty: Option<&RobotType>) -> Report {
+    let limits = Limits::for_type(ty);
+    let mut report = Report::default();
+    let mut names: Vec<&String> = profile.0.keys().collect();
+    names.sort();
+    let mut by_id: HashMap<i32, Vec<&str>> = HashMap::new();
+    for name in names {
+        let joint = &profile.0[name];
+        check_joint(name, joint, &limits, &mut report);
+        by_id.entry(joint.id).or_default().push(name);
+    }
+    let mut shared: Vec<(i32, Vec<&str>)> = by_id.into_iter().filter(|(_, n)| n.len() > 1).collect();
+    shared.sort();
+    for (id, joints) in shared {
+        for joint in &joints {
+            report.push(Severity::Error, "unique_id", Some(joint), Some("id"), format!("id {} is also used by {}", id, joints.iter().filter(|j| *j != joint).cloned().collect::<Vec<_>>().join(", ")));
+        }
+    }
+    if profile.0.is_empty() && profile.1.get("motor_names").is_some_and(serde_json::Value::is_array) {
+        report.push(Severity::Error, "legacy_layout", None, None, "legacy LeRobot layout (parallel arrays); import it to convert".into());
+        return report;
+    }
+    for (key, value) in &profile.1 {
+        // an object was meant as a joint unless it is a known metadata block;
+        // anything else is someone else's data
+        match value.as_object().filter(|_| !METADATA_FIELDS.contains(&key.as_str())).map(|_| serde_json::from_value::<Joint>(value.clone())) {
+            Some(Err(e)) => report.push(Severity::Error, "malformed_joint", Some(key), None, format!("not a valid joint: {}", e)),
+            _ => report.push(Severity::Warning, "unknown_field", None, None, format!("unknown top-level field `{}` is kept as is", key)),
+        }
+    }
+    if let Some(ty) = ty {
+        for problem in ty.check(profile) {
+            report.push(Severity::Error, "robot_type", None, None, problem);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::registry;
+
+    fn joint() -> Joint {
+        Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1000, range_max: 3000, ..Default::default() }
+    }
+
+    fn rules(report: &Report) -> Vec<(&'static str, Severity)> {
+        report.issues.iter().map(|i| (i.rule, i.severity)).collect()
+    }
+
+    #[test]
+    fn all_violations_are_reported() {
+        let bad = Joint { id: 253, drive_mode: 2, homing_offset: -2048, range_min: -5, range_max: 4096, ..Default::default() };
+        let mut report = Report::default();
+        check_joint("elbow_flex", &bad, &Limits::FEETECH, &mut report);
+        assert_eq!(
+            rules(&report),
+            [
+                ("id_range", Severity::Error),
+                ("position_range", Severity::Error),
+                ("position_range", Severity::Error),
+                ("homing_offset", Severity::Error),
+                ("drive_mode", Severity::Error),
+            ]
+        );
+        assert_eq!(report.issues[3].to_string(), "joint `elbow_flex`: homing_offset -2048 exceeds ±2047");
+
+        let mut report = Report::default();
+        check_joint("elbow_flex", &Joint { range_max: 1300, ..joint() }, &Limits::FEETECH, &mut report);
+        assert_eq!(rules(&report), [("narrow_range", Severity::Warning)]);
+        assert!(!report.has_errors());
+        // Dynamixel homing offsets go far beyond the Feetech register
+        let mut report = Report::default();
+        check_joint("elbow_flex", &Joint { homing_offset: -5000, ..joint() }, &Limits::DYNAMIXEL, &mut report);
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn ids_are_unique_and_types_apply() {
+        let ty = registry::lookup("so101_follower").unwrap();
+        let mut p = ty.template();
+        assert!(check(&p, Some(ty)).issues.is_empty());
+        p.0.get_mut("wrist_roll").unwrap().id = 6;
+        let report = check(&p, None);
+        assert_eq!(rules(&report), [("unique_id", Severity::Error), ("unique_id", Severity::Error)]);
+        assert_eq!(report.issues[0].message, "id 6 is also used by wrist_roll");
+        let report = check(&p, Some(ty));
+        assert_eq!(report.errors().count(), 3);
+        assert_eq!(report.issues[2].rule, "robot_type");
+    }
+
+    #[test]
+    fn unknown_fields_are_warnings() {
+        let p: Profile = serde_json::from_str(
+            r#"{
+                "gripper": {"id": 6, "drive_mode": 0, "homing_offset": 0, "range_min": 1000, "range_max": 3000, "torque_limit": 500},
+                "elbow": {"id": "2", "drive_mode": 0},
+                "wrist": {"drive_mode": 0, "range_min": 10},
+                "version": 2,
+                "meta": {"tool": "x"}
+            }"#,
+        )
+        .unwrap();
+        let report = check(&p, None);
+        assert_eq!(
+            rules(&report),
+            [
+                ("unknown_field", Severity::Warning),
+                ("malformed_joint", Severity::Error),
+                ("malformed_joint", Severity::Error),
+                ("unknown_field", Severity::Warning),
+                ("unknown_field", Severity::Warning),
+            ]
+        );
+        assert_eq!(report.issues[0].to_string(), "joint `gripper`: unknown field `torque_limit` is kept as is");
+        assert!(report.issues[2].message.contains("missing field `id`"), "{}", report.issues[2].message);
+        assert_eq!(report.issues[3].message, "unknown top-level field `version` is kept as is");
+
+        let legacy: Profile = serde_json::from_str(r#"{"homing_offset": [0], "drive_mode": [0], "motor_names": ["gripper"]}"#).unwrap();
+        assert_eq!(rules(&check(&legacy, None)), [("legacy_layout", Severity::Error)]);
+    }
+}
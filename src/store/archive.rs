@@ -0,0 +1,505 @@
+//! Whole-tree archives.
+//!
+//! An export holds every selected profile file as `<kind>/<name>.json`,
+//! bytes as stored, next to a `manifest.json` listing them with their
+//! versions. Imports accept such archives (`.tar.gz`, `.tar` or `.zip`) or a
+//! calibration directory zipped by hand: everything up to the first `robots/`
+//! or `teleoperators/` component is ignored. Importing is planned first so
+//! callers can preview what would be created, overwritten or skipped.
+
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Write};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use super::history::{format_utc, now};
+use super::{version_of, Kind, Origin, ProfileName, Store, StoreError};
+use crate::model::{registry, rules, Profile};
+
+pub const MANIFEST: &str = "manifest.json";
+/// Largest profile file accepted from an archive.
+pub const MAX_ENTRY_BYTES: u64 = 1 << 20;
+/// Largest total of unpacked profile bytes accepted from an archive.
+pub const MAX_TOTAL_BYTES: u64 = 64 << 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// Gzip-compressed tar.
+    #[default]
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Tar => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Tar => "application/gzip",
+            Self::Zip => "application/zip",
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" | "tar.gz" | "tgz" => Ok(Self::Tar),
+            "zip" => Ok(Self::Zip),
+            _ => Err(format!("unknown archive format `{}` (expected tar or zip)", s)),
+        }
+    }
+}
+
+/// Which profiles an export includes; empty fields match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ArchiveFilter {
+    #[serde(default)]
+    pub kind: Option<Kind>,
+    /// Registry type name, matched against the profile's type directory.
+    #[serde(default)]
+    pub robot_type: Option<String>,
+}
+
+impl ArchiveFilter {
+    fn matches(&self, kind: Kind, name: &str) -> bool {
+        self.kind.is_none_or(|k| k == kind)
+            && self.robot_type.as_deref().is_none_or(|t| registry::type_of(kind, name).is_some_and(|ty| ty.name == t))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Layout version of the archive.
+    pub format: u32,
+    pub created_at: String,
+    pub generator: String,
+    pub profiles: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub kind: Kind,
+    pub name: String,
+    /// Path inside the archive.
+    pub path: String,
+    /// Store version (see `version_of`) of the file bytes.
+    pub version: String,
+    pub joints: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub robot_type: Option<String>,
+}
+
+/// A profile file read from an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub kind: Kind,
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The profile files of an uploaded archive.
+#[derive(Debug, Clone, Default)]
+pub struct Archive {
+    pub manifest: Option<Manifest>,
+    pub entries: Vec<ArchiveEntry>,
+    /// Paths that are not profile files (backups, history, other files).
+    pub ignored: Vec<String>,
+}
+
+/// What to do with profiles that already exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    /// Keep the existing profile.
+    #[default]
+    Skip,
+    Overwrite,
+    /// Refuse the whole import.
+    Fail,
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!("unknown conflict policy `{}` (expected skip, overwrite or fail)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Create,
+    Overwrite,
+    /// Same content as the existing profile.
+    Unchanged,
+    Skip,
+    /// Exists, and the policy is `fail`.
+    Conflict,
+    /// Unreadable or breaks validation rules; never written.
+    Invalid,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Overwrite => "overwrite",
+            Self::Unchanged => "unchanged",
+            Self::Skip => "skip",
+            Self::Conflict => "conflict",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanItem {
+    pub kind: Kind,
+    pub name: String,
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// What an import does (or did) with every profile of an archive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportPlan {
+    pub items: Vec<PlanItem>,
+    pub ignored: Vec<String>,
+    /// Whether the plan was carried out.
+    pub applied: bool,
+}
+
+impl ImportPlan {
+    pub fn count(&self, action: Action) -> usize {
+        self.items.iter().filter(|i| i.action == action).count()
+    }
+}
+
+impl Archive {
+    /// Reads a `.tar.gz`, `.tar` or `.zip`, told apart by their magic bytes.
+    pub fn read(bytes: &[u8]) -> Result<Self, StoreError> {
+        let bad = |e: &dyn std::fmt::Display| StoreError::Archive(e.to_string());
+        let mut files = Vec::new();
+        let mut total = 0u64;
+        let mut take = |path: String, size: u64, reader: &mut dyn Read| -> Result<(), StoreError> {
+            if size > MAX_ENTRY_BYTES || !path.ends_with(".json") {
+                files.push((path, None));
+                return Ok(());
+            }
+            // sizes in headers can lie; count what is actually unpacked
+            let mut buf = Vec::new();
+            reader.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut buf)?;
+            total += buf.len() as u64;
+            if total > MAX_TOTAL_BYTES {
+                return Err(StoreError::Archive(format!("archive unpacks to more than {} bytes", MAX_TOTAL_BYTES)));
+            }
+            files.push((path, (buf.len() as u64 <= MAX_ENTRY_BYTES).then_some(buf)));
+            Ok(())
+        };
+        if bytes.starts_with(b"PK\x03\x04") {
+            let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| bad(&e))?;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i).map_err(|e| bad(&e))?;
+                if file.is_file() {
+                    let (path, size) = (file.name().to_string(), file.size());
+                    take(path, size, &mut file)?;
+                }
+            }
+        } else {
+            let reader: Box<dyn Read> = if bytes.starts_with(&[0x1f, 0x8b]) { Box::new(flate2::read::GzDecoder::new(bytes)) } else { Box::new(bytes) };
+            let mut tar = tar::Archive::new(reader);
+            for entry in tar.entries().map_err(|e| bad(&e))? {
+                let mut entry = entry.map_err(|e| bad(&e))?;
+                if entry.header().entry_type().is_file() {
+                    let path = entry.path().map_err(|e| bad(&e))?.to_string_lossy().into_owned();
+                    let size = entry.size();
+                    take(path, size, &mut entry)?;
+                }
+            }
+            if files.is_empty() {
+                return Err(StoreError::Archive("not a tar or zip archive, or it is empty".into()));
+            }
+        }
+
+        let mut archive = Archive::default();
+        for (path, bytes) in files {
+            match (bytes, entry_name(&path)) {
+                (Some(bytes), Some((kind, name))) => archive.entries.push(ArchiveEntry { kind, name, bytes }),
+                (Some(bytes), None) if path.trim_start_matches("./").rsplit('/').next() == Some(MANIFEST) && archive.manifest.is_none() => {
+                    archive.manifest = Some(serde_json::from_slice(&bytes).map_err(|e| StoreError::Archive(format!("{}: {}", MANIFEST, e)))?);
+                }
+                _ => archive.ignored.push(path),
+            }
+        }
+        archive.check_manifest()?;
+        Ok(archive)
+    }
+
+    /// Every file the manifest lists must be present with its listed version.
+    fn check_manifest(&self) -> Result<(), StoreError> {
+        for listed in self.manifest.iter().flat_map(|m| &m.profiles) {
+            let found = self.entries.iter().find(|e| e.kind == listed.kind && e.name == listed.name);
+            match found {
+                Some(e) if version_of(&e.bytes) == listed.version => {}
+                Some(_) => return Err(StoreError::Archive(format!("{} does not match its manifest version", listed.path))),
+                None => return Err(StoreError::Archive(format!("manifest lists {} but the archive lacks it", listed.path))),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kind and profile name of a path like `[prefix/]robots/<type>/<id>.json`.
+/// Paths through hidden directories (`.history`, `.trash`, ...) are not
+/// profiles, even when they contain a `robots` component.
+fn entry_name(path: &str) -> Option<(Kind, String)> {
+    let parts: Vec<&str> = path.trim_start_matches("./").split('/').collect();
+    if parts.iter().any(|p| p.starts_with('.')) {
+        return None;
+    }
+    let at = parts.iter().position(|p| *p == "robots" || *p == "teleoperators")?;
+    let kind = if parts[at] == "robots" { Kind::Robots } else { Kind::Teleoperators };
+    let name = parts[at + 1..].join("/");
+    let name = name.strip_suffix(".json")?;
+    ProfileName::parse(name).ok().map(|n| (kind, n.into()))
+}
+
+impl Store {
+    /// Packs the profiles `filter` selects, with a manifest.
+    #[instrument(skip(self))]
+    pub fn export_archive(&self, filter: &ArchiveFilter, format: ArchiveFormat) -> Result<Vec<u8>, StoreError> {
+        let mut files = Vec::new();
+        let mut profiles = Vec::new();
+        for kind in [Kind::Robots, Kind::Teleoperators] {
+            for meta in self.list_profiles(kind)? {
+                if !filter.matches(kind, &meta.name) {
+                    continue;
+                }
+                let bytes = std::fs::read(&meta.path)?;
+                let joints = serde_json::from_slice::<Profile>(&bytes).map(|p| p.0.len()).unwrap_or(0);
+                let path = format!("{}/{}.json", kind.as_str(), meta.name);
+                profiles.push(ManifestEntry {
+                    kind,
+                    name: meta.name.clone(),
+                    path: path.clone(),
+                    version: version_of(&bytes),
+                    joints,
+                    robot_type: registry::type_of(kind, &meta.name).map(|t| t.name.to_string()),
+                });
+                files.push((path, bytes));
+            }
+        }
+        let manifest = Manifest { format: 1, created_at: format_utc(now()), generator: concat!("lerobot-servo-adjust ", env!("CARGO_PKG_VERSION")).into(), profiles };
+        files.insert(0, (MANIFEST.to_string(), serde_json::to_vec_pretty(&manifest)?));
+        let packed = match format {
+            ArchiveFormat::Tar => pack_tar(&files)?,
+            ArchiveFormat::Zip => pack_zip(&files)?,
+        };
+        info!(profiles = files.len() - 1, bytes = packed.len(), "export archive");
+        Ok(packed)
+    }
+
+    /// What importing `archive` under `conflict` would do, without writing.
+    pub fn preview_archive(&self, archive: &Archive, conflict: Conflict) -> Result<ImportPlan, StoreError> {
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for entry in &archive.entries {
+            let item = |action, reason: Option<String>| PlanItem { kind: entry.kind, name: entry.name.clone(), action, reason };
+            if !seen.insert((entry.kind, entry.name.clone())) {
+                items.push(item(Action::Skip, Some("listed twice in the archive".into())));
+                continue;
+            }
+            let profile: Profile = match serde_json::from_slice(&entry.bytes) {
+                Ok(p) => p,
+                Err(e) => {
+                    items.push(item(Action::Invalid, Some(format!("invalid JSON: {}", e))));
+                    continue;
+                }
+            };
+            if profile.0.is_empty() {
+                items.push(item(Action::Invalid, Some("no joints".into())));
+                continue;
+            }
+            let report = rules::check(&profile, registry::type_of(entry.kind, &entry.name));
+            if report.has_errors() {
+                items.push(item(Action::Invalid, Some(report.errors().map(ToString::to_string).collect::<Vec<_>>().join("; "))));
+                continue;
+            }
+            let existing = match self.locate(entry.kind, &entry.name) {
+                Ok((_, path)) if path.is_file() => Some(std::fs::read(&path)?),
+                Ok(_) => None,
+                Err(e) => {
+                    items.push(item(Action::Invalid, Some(e.to_string())));
+                    continue;
+                }
+            };
+            items.push(match existing {
+                None => item(Action::Create, None),
+                // compare serialized forms; `Profile`'s `PartialEq` ignores joint order
+                Some(bytes) if bytes == entry.bytes || serde_json::from_slice::<Profile>(&bytes).is_ok_and(|p| serde_json::to_vec(&p).ok() == serde_json::to_vec(&profile).ok()) => item(Action::Unchanged, None),
+                Some(_) => match conflict {
+                    Conflict::Skip => item(Action::Skip, Some("already exists".into())),
+                    Conflict::Overwrite => item(Action::Overwrite, None),
+                    Conflict::Fail => item(Action::Conflict, Some("already exists".into())),
+                },
+            });
+        }
+        Ok(ImportPlan { items, ignored: archive.ignored.clone(), applied: false })
+    }
+
+    /// Plans and carries out an import. Overwritten profiles keep a `.bak`
+    /// and get a history revision like any other write. Planning and writing
+    /// happen under one lock, so the plan cannot go stale in between.
+    #[instrument(skip(self, archive, origin))]
+    pub fn import_archive(&self, archive: &Archive, conflict: Conflict, origin: Origin) -> Result<ImportPlan, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut plan = self.preview_archive(archive, conflict)?;
+        let conflicts: Vec<String> = plan.items.iter().filter(|i| i.action == Action::Conflict).map(|i| format!("{}:{}", i.kind.as_str(), i.name)).collect();
+        if !conflicts.is_empty() {
+            return Err(StoreError::Exists(conflicts.join(", ")));
+        }
+        let origin = if origin.summary.is_none() { origin.summary(Some("imported from archive".into())) } else { origin };
+        for (item, entry) in plan.items.iter().zip(&archive.entries) {
+            if matches!(item.action, Action::Create | Action::Overwrite) {
+                let profile: Profile = serde_json::from_slice(&entry.bytes)?;
+                self.write_locked(entry.kind, &entry.name, &profile, true, origin.clone(), None)?;
+            }
+        }
+        plan.applied = true;
+        info!(created = plan.count(Action::Create), overwritten = plan.count(Action::Overwrite), skipped = plan.count(Action::Skip), "import archive");
+        Ok(plan)
+    }
+}
+
+fn pack_tar(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, StoreError> {
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    let mtime = now();
+    for (path, bytes) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        tar.append_data(&mut header, path, bytes.as_slice())?;
+    }
+    Ok(tar.into_inner()?.finish()?)
+}
+
+fn pack_zip(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, StoreError> {
+    let bad = |e: zip::result::ZipError| StoreError::Archive(e.to_string());
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (path, bytes) in files {
+        zip.start_file(path.as_str(), options).map_err(bad)?;
+        zip.write_all(bytes)?;
+    }
+    Ok(zip.finish().map_err(bad)?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_with_profiles(dir: &std::path::Path) -> Store {
+        let store = Store::new(dir.to_path_buf());
+        let follower = registry::lookup("so101_follower").unwrap().template();
+        store.write_profile(Kind::Robots, "so101_follower/arm", &follower, false).unwrap();
+        store.write_profile(Kind::Robots, "koch_follower/arm", &registry::lookup("koch_follower").unwrap().template(), false).unwrap();
+        store.write_profile(Kind::Teleoperators, "so101_leader/lead", &registry::lookup("so101_leader").unwrap().template(), false).unwrap();
+        store
+    }
+
+    #[test]
+    fn exports_and_reimports_in_both_formats() {
+        let src = tempfile::tempdir().unwrap();
+        let store = store_with_profiles(src.path());
+        for format in [ArchiveFormat::Tar, ArchiveFormat::Zip] {
+            let bytes = store.export_archive(&ArchiveFilter::default(), format).unwrap();
+            let archive = Archive::read(&bytes).unwrap();
+            let manifest = archive.manifest.as_ref().unwrap();
+            assert_eq!(manifest.profiles.len(), 3);
+            assert_eq!(manifest.profiles[0].robot_type.as_deref(), Some("koch_follower"));
+
+            let dst = tempfile::tempdir().unwrap();
+            let target = Store::new(dst.path().to_path_buf());
+            let plan = target.import_archive(&archive, Conflict::Skip, Origin::new("test")).unwrap();
+            assert_eq!((plan.count(Action::Create), plan.applied), (3, true));
+            let file = "robots/so101_follower/arm.json";
+            assert_eq!(std::fs::read(dst.path().join(file)).unwrap(), std::fs::read(src.path().join(file)).unwrap());
+            assert_eq!(target.history(Kind::Robots, "so101_follower/arm").unwrap()[0].summary, "imported from archive");
+        }
+
+        let filter = ArchiveFilter { kind: Some(Kind::Robots), robot_type: Some("so101_follower".into()) };
+        let archive = Archive::read(&store.export_archive(&filter, ArchiveFormat::Zip).unwrap()).unwrap();
+        assert_eq!(archive.entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["so101_follower/arm"]);
+    }
+
+    #[test]
+    fn plans_conflicts_by_policy() {
+        let src = tempfile::tempdir().unwrap();
+        let archive = Archive::read(&store_with_profiles(src.path()).export_archive(&ArchiveFilter::default(), ArchiveFormat::Tar).unwrap()).unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        let target = store_with_profiles(dst.path());
+        let mut changed = registry::lookup("so101_follower").unwrap().template();
+        changed.0.get_mut("gripper").unwrap().range_min = 100;
+        target.write_profile(Kind::Robots, "so101_follower/arm", &changed, false).unwrap();
+
+        let actions = |plan: &ImportPlan| plan.items.iter().map(|i| i.action).collect::<Vec<_>>();
+        let plan = target.preview_archive(&archive, Conflict::Skip).unwrap();
+        assert_eq!(actions(&plan), [Action::Unchanged, Action::Skip, Action::Unchanged]);
+        assert!(!plan.applied);
+        assert!(matches!(target.import_archive(&archive, Conflict::Fail, Origin::new("test")), Err(StoreError::Exists(_))));
+        assert_eq!(target.read_profile(Kind::Robots, "so101_follower/arm").unwrap(), changed);
+        let plan = target.import_archive(&archive, Conflict::Overwrite, Origin::new("test")).unwrap();
+        assert_eq!(actions(&plan), [Action::Unchanged, Action::Overwrite, Action::Unchanged]);
+        assert_ne!(target.read_profile(Kind::Robots, "so101_follower/arm").unwrap(), changed);
+    }
+
+    #[test]
+    fn reads_hand_made_archives_and_rejects_bad_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = serde_json::to_vec(&registry::lookup("so101_follower").unwrap().template()).unwrap();
+        let files = [
+            ("huggingface/lerobot/calibration/robots/so101_follower/arm.json".to_string(), p.clone()),
+            ("huggingface/lerobot/calibration/robots/so101_follower/arm.json.bak".to_string(), p.clone()),
+            ("huggingface/lerobot/calibration/robots/.history/x/1.json".to_string(), p.clone()),
+            // a zipped calibration root: revisions and trash look like profiles by path alone
+            ("calibration/.history/robots/arm/000001.json".to_string(), p.clone()),
+            ("calibration/.trash/1700000000-1/robots/arm.json".to_string(), p.clone()),
+            ("huggingface/lerobot/calibration/robots/bad.json".to_string(), b"{\"j\": {\"id\": 0, \"drive_mode\": 0, \"homing_offset\": 0, \"range_min\": 0, \"range_max\": 10}}".to_vec()),
+            ("robots/empty.json".to_string(), b"{\"version\": 2}".to_vec()),
+        ];
+        let archive = Archive::read(&pack_zip(&files).unwrap()).unwrap();
+        assert_eq!(archive.entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["so101_follower/arm", "bad", "empty"]);
+        assert_eq!(archive.ignored.len(), 4);
+        let plan = Store::new(dir.path().to_path_buf()).preview_archive(&archive, Conflict::Skip).unwrap();
+        assert_eq!(plan.items.iter().map(|i| i.action).collect::<Vec<_>>(), [Action::Create, Action::Invalid, Action::Invalid]);
+        assert_eq!(plan.items[2].reason.as_deref(), Some("no joints"));
+
+        assert!(matches!(Archive::read(b"not an archive"), Err(StoreError::Archive(_))));
+        // a manifest that does not match the files means a damaged archive
+        let manifest = Manifest {
+            format: 1,
+            created_at: String::new(),
+            generator: String::new(),
+            profiles: vec![ManifestEntry { kind: Kind::Robots, name: "arm".into(), path: "robots/arm.json".into(), version: "0".into(), joints: 6, robot_type: None }],
+        };
+        let files = [(MANIFEST.to_string(), serde_json::to_vec(&manifest).unwrap()), ("robots/arm.json".to_string(), p)];
+        assert!(matches!(Archive::read(&pack_tar(&files).unwrap()), Err(StoreError::Archive(m)) if m.contains("manifest version")));
+    }
+}
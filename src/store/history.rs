@@ -0,0 +1,335 @@
+//! Numbered revisions of every profile write.
+//!
+//! Each write of `<kind>/<name>.json` is also recorded as
+//! `<root>/.history/<kind>/<name>/<revision>.json`, together with when it
+//! happened, who or what made it and a summary of the change. Revisions are
+//! pruned according to `Retention`; the latest one is always kept. Deleting a
+//! profile moves its history into the trash with it (see `trash`); restoring
+//! brings it back, while a profile re-created under the same name starts
+//! again from revision 1.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use super::{Kind, Store, StoreError};
+use crate::model::{self, Profile};
+
+/// How many revisions to keep per profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retention {
+    /// Keep at most this many revisions; `None` keeps all.
+    pub max_revisions: Option<usize>,
+    /// Drop revisions older than this; `None` keeps them regardless of age.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self { max_revisions: Some(50), max_age: None }
+    }
+}
+
+/// Who or what is writing a profile.
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+    /// Subsystem making the write: `api`, `web`, `homing`, `recording`, ...
+    pub source: String,
+    pub author: Option<String>,
+    /// Overrides the generated change summary.
+    pub summary: Option<String>,
+}
+
+impl Origin {
+    pub fn new(source: &str) -> Self {
+        Self { source: source.to_string(), ..Self::default() }
+    }
+
+    pub fn author(mut self, author: Option<String>) -> Self {
+        self.author = author.filter(|a| !a.is_empty());
+        self
+    }
+
+    pub fn summary(mut self, summary: Option<String>) -> Self {
+        self.summary = summary.filter(|s| !s.is_empty());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RevisionMeta {
+    pub revision: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub source: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub summary: String,
+}
+
+impl RevisionMeta {
+    /// `timestamp` as `YYYY-MM-DD HH:MM:SS UTC`.
+    pub fn time(&self) -> String {
+        format_utc(self.timestamp)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Revision {
+    #[serde(flatten)]
+    pub meta: RevisionMeta,
+    pub profile: Profile,
+}
+
+/// Summaries list at most this many changes.
+const SUMMARY_CHANGES: usize = 4;
+
+fn summarize(before: Option<&Profile>, after: &Profile) -> String {
+    let Some(before) = before else {
+        return format!("created with {} joints", after.0.len());
+    };
+    let changes = model::diff(before, after);
+    if changes.is_empty() {
+        return if before.0.keys().ne(after.0.keys()) { "joints reordered".into() } else { "no changes".into() };
+    }
+    let mut text: Vec<String> = changes.iter().take(SUMMARY_CHANGES).map(ToString::to_string).collect();
+    if changes.len() > SUMMARY_CHANGES {
+        text.push(format!("(+{} more)", changes.len() - SUMMARY_CHANGES));
+    }
+    text.join("; ")
+}
+
+pub(super) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub(super) fn format_utc(secs: u64) -> String {
+    // days-to-civil, from Howard Hinnant's date algorithms
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+impl Store {
+    /// Replaces the default retention policy.
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub(super) fn history_dir(&self, kind: Kind, name: &str) -> PathBuf {
+        self.root.join(".history").join(kind.as_str()).join(name)
+    }
+
+    fn revision_path(&self, kind: Kind, name: &str, revision: u64) -> PathBuf {
+        self.history_dir(kind, name).join(format!("{:06}.json", revision))
+    }
+
+    pub(super) fn revision_numbers(&self, kind: Kind, name: &str) -> Result<Vec<u64>, StoreError> {
+        let dir = self.history_dir(kind, name);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut numbers: Vec<u64> = fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .filter_map(|e| e.path().file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()))
+            .collect();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// Revisions of a profile, oldest first.
+    #[instrument(skip(self))]
+    pub fn history(&self, kind: Kind, name: &str) -> Result<Vec<RevisionMeta>, StoreError> {
+        let name = &self.canonical_name(kind, name)?;
+        let numbers = self.revision_numbers(kind, name)?;
+        if numbers.is_empty() && !self.exists(kind, name) {
+            return Err(StoreError::NotFound(format!("{}:{}", kind.as_str(), name)));
+        }
+        numbers.into_iter().map(|n| self.load_revision(kind, name, n).map(|r| r.meta)).collect()
+    }
+
+    #[instrument(skip(self))]
+    pub fn revision(&self, kind: Kind, name: &str, revision: u64) -> Result<Revision, StoreError> {
+        self.load_revision(kind, &self.canonical_name(kind, name)?, revision)
+    }
+
+    /// The profile at `revision`, or the current file when `None`.
+    pub fn read_at(&self, kind: Kind, name: &str, revision: Option<u64>) -> Result<Profile, StoreError> {
+        match revision {
+            Some(rev) => self.revision(kind, name, rev).map(|r| r.profile),
+            None => self.read_profile(kind, name),
+        }
+    }
+
+    /// Reads a revision of the profile with canonical name `name`.
+    fn load_revision(&self, kind: Kind, name: &str, revision: u64) -> Result<Revision, StoreError> {
+        let path = self.revision_path(kind, name, revision);
+        if !path.exists() {
+            return Err(StoreError::NotFound(format!("{}:{}@{}", kind.as_str(), name, revision)));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Writes revision `revision` back as the current profile, itself
+    /// recorded as a new revision. Fails with `Unchanged` when the profile
+    /// already has that content.
+    #[instrument(skip(self, origin))]
+    pub fn rollback(&self, kind: Kind, name: &str, revision: u64, origin: Origin) -> Result<RevisionMeta, StoreError> {
+        let name = &self.canonical_name(kind, name)?;
+        let old = self.load_revision(kind, name, revision)?;
+        let origin = Origin { summary: origin.summary.or_else(|| Some(format!("rollback to revision {}", revision))), ..origin };
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let before = self.revision_numbers(kind, name)?.last().copied();
+        self.write_locked(kind, name, &old.profile, false, origin, None)?;
+        let latest = self.revision_numbers(kind, name)?.last().copied();
+        match latest {
+            Some(n) if latest != before => Ok(self.load_revision(kind, name, n)?.meta),
+            // the profile already holds that content; nothing was written
+            _ => Err(StoreError::Unchanged { revision }),
+        }
+    }
+
+    /// Records a profile that predates history as revision 1, so the first
+    /// tracked write can still be rolled back.
+    pub(super) fn import_existing(&self, kind: Kind, name: &str) -> Result<(), StoreError> {
+        if !self.revision_numbers(kind, name)?.is_empty() {
+            return Ok(());
+        }
+        let Ok(current) = self.read_profile(kind, name) else {
+            return Ok(());
+        };
+        let origin = Origin::new("import").summary(Some("existing profile".into()));
+        self.append_revision(kind, name, &current, &origin).map(|_| ())
+    }
+
+    pub(super) fn append_revision(&self, kind: Kind, name: &str, profile: &Profile, origin: &Origin) -> Result<RevisionMeta, StoreError> {
+        let numbers = self.revision_numbers(kind, name)?;
+        let previous = match numbers.last() {
+            Some(&n) => Some(self.load_revision(kind, name, n)?.profile),
+            None => None,
+        };
+        let meta = RevisionMeta {
+            revision: numbers.last().map_or(1, |n| n + 1),
+            timestamp: now(),
+            source: origin.source.clone(),
+            author: origin.author.clone(),
+            summary: origin.summary.clone().unwrap_or_else(|| summarize(previous.as_ref(), profile)),
+        };
+        fs::create_dir_all(self.history_dir(kind, name))?;
+        let rev = Revision { meta: meta.clone(), profile: profile.clone() };
+        fs::write(self.revision_path(kind, name, meta.revision), serde_json::to_vec_pretty(&rev)?)?;
+        info!(kind = kind.as_str(), name, revision = meta.revision, source = %meta.source, "revision recorded");
+        self.prune(kind, name, meta.timestamp)?;
+        Ok(meta)
+    }
+
+    fn prune(&self, kind: Kind, name: &str, now: u64) -> Result<(), StoreError> {
+        let numbers = self.revision_numbers(kind, name)?;
+        let Some((_, older)) = numbers.split_last() else {
+            return Ok(());
+        };
+        let over = self.retention.max_revisions.map_or(0, |max| numbers.len().saturating_sub(max.max(1)));
+        for (i, &n) in older.iter().enumerate() {
+            let expired = match self.retention.max_age {
+                Some(age) => self.load_revision(kind, name, n)?.meta.timestamp + age.as_secs() < now,
+                None => false,
+            };
+            if i < over || expired {
+                fs::remove_file(self.revision_path(kind, name, n))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Joint;
+
+    fn profile(range_max: i32) -> Profile {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("gripper".to_string(), Joint { id: 6, drive_mode: 0, homing_offset: 0, range_min: 2000, range_max, ..Default::default() });
+        Profile::new(map)
+    }
+
+    #[test]
+    fn writes_are_numbered_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        store.write_profile(Kind::Robots, "arm", &profile(3000), true).unwrap();
+        store.write_profile_from(Kind::Robots, "arm", &profile(3500), true, Origin::new("api").author(Some("ana".into()))).unwrap();
+
+        let history = store.history(Kind::Robots, "arm").unwrap();
+        assert_eq!(history.iter().map(|m| m.revision).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(history[0].summary, "created with 1 joints");
+        assert_eq!(history[1].summary, "gripper.range_max 3000 -> 3500");
+        assert_eq!((history[1].source.as_str(), history[1].author.as_deref()), ("api", Some("ana")));
+
+        let meta = store.rollback(Kind::Robots, "arm", 1, Origin::new("api")).unwrap();
+        assert_eq!((meta.revision, meta.summary.as_str()), (3, "rollback to revision 1"));
+        assert_eq!(store.read_profile(Kind::Robots, "arm").unwrap(), profile(3000));
+        assert!(matches!(store.rollback(Kind::Robots, "arm", 1, Origin::new("api")), Err(StoreError::Unchanged { revision: 1 })));
+        assert_eq!(store.history(Kind::Robots, "arm").unwrap().len(), 3);
+        assert!(matches!(store.revision(Kind::Robots, "arm", 9), Err(StoreError::NotFound(_))));
+    }
+
+    #[test]
+    fn unchanged_saves_are_not_recorded_but_reorders_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf()).with_retention(Retention { max_revisions: Some(2), max_age: None });
+        let mut p = profile(3000);
+        p.0.insert("wrist_roll".into(), Joint { id: 5, range_max: 4095, ..Default::default() });
+        store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+        store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+        assert_eq!(store.history(Kind::Robots, "arm").unwrap().len(), 1);
+
+        p.0.reverse();
+        store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+        let history = store.history(Kind::Robots, "arm").unwrap();
+        assert_eq!(history.iter().map(|m| m.revision).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(history[1].summary, "joints reordered");
+        let stored = store.read_profile(Kind::Robots, "arm").unwrap();
+        assert_eq!(stored.0.keys().collect::<Vec<_>>(), ["wrist_roll", "gripper"]);
+    }
+
+    #[test]
+    fn untracked_profile_is_imported_and_retention_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let robots = dir.path().join("robots");
+        fs::create_dir_all(&robots).unwrap();
+        fs::write(robots.join("arm.json"), serde_json::to_vec(&profile(3000)).unwrap()).unwrap();
+        let store = Store::new(dir.path().to_path_buf()).with_retention(Retention { max_revisions: Some(3), max_age: None });
+        assert!(store.history(Kind::Robots, "arm").unwrap().is_empty());
+
+        store.write_profile(Kind::Robots, "arm", &profile(3100), false).unwrap();
+        let history = store.history(Kind::Robots, "arm").unwrap();
+        assert_eq!(history[0].source, "import");
+        assert_eq!(store.revision(Kind::Robots, "arm", 1).unwrap().profile, profile(3000));
+
+        for max in [3200, 3300, 3400] {
+            store.write_profile(Kind::Robots, "arm", &profile(max), false).unwrap();
+        }
+        let kept: Vec<u64> = store.history(Kind::Robots, "arm").unwrap().iter().map(|m| m.revision).collect();
+        assert_eq!(kept, [3, 4, 5]);
+    }
+
+    #[test]
+    fn utc_formatting() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(1_709_251_199), "2024-02-29 23:59:59 UTC");
+    }
+}
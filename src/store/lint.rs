@@ -0,0 +1,113 @@
+//! Checking every stored profile at once.
+
+use std::ffi::OsStr;
+use std::fs;
+
+use serde::Serialize;
+
+use super::{Kind, ProfileName, Store, StoreError};
+use crate::model::rules::{self, Issue, Severity};
+use crate::model::{registry, Profile};
+
+/// Findings for one profile file.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintEntry {
+    pub kind: Kind,
+    pub name: String,
+    /// Path relative to the calibration root.
+    pub path: String,
+    pub issues: Vec<Issue>,
+}
+
+impl LintEntry {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|i| i.severity == Severity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.iter().filter(|i| i.severity == Severity::Warning).count()
+    }
+}
+
+impl Store {
+    /// Parses and validates every `.json` file of `kinds`, reporting files
+    /// that cannot be read or parsed instead of skipping them. The tree is
+    /// walked directly, so files the store would not list (nested too deep
+    /// or badly named) show up as errors too.
+    pub fn lint(&self, kinds: &[Kind]) -> Result<Vec<LintEntry>, StoreError> {
+        let mut entries = Vec::new();
+        for &kind in kinds {
+            let dir = self.kind_dir(kind);
+            if !dir.exists() {
+                continue;
+            }
+            let files = walkdir::WalkDir::new(&dir)
+                .into_iter()
+                // hidden directories (editor or VCS state) are not part of the store
+                .filter_entry(|e| e.depth() == 0 || !e.file_type().is_dir() || !e.file_name().to_string_lossy().starts_with('.'))
+                .filter_map(Result::ok)
+                .filter(|e| e.file_type().is_file() && e.path().extension() == Some(OsStr::new("json")));
+            let mut found = Vec::new();
+            for file in files {
+                let name = file.path().with_extension("").strip_prefix(&dir).map(|p| p.to_string_lossy().replace('\\', "/")).unwrap_or_default();
+                let mut issues = Vec::new();
+                let typed = match ProfileName::parse(&name) {
+                    Ok(_) => registry::type_of(kind, &name),
+                    Err(e) => {
+                        issues.push(problem("name", format!("not listed as a profile: {}", e)));
+                        None
+                    }
+                };
+                issues.extend(match fs::read_to_string(file.path()) {
+                    Ok(text) => match serde_json::from_str::<Profile>(&text) {
+                        Ok(profile) => rules::check(&profile, typed).issues,
+                        Err(e) => vec![problem("parse", format!("invalid json: {}", e))],
+                    },
+                    Err(e) => vec![problem("read", format!("cannot read file: {}", e))],
+                });
+                let path = file.path().strip_prefix(&self.root).unwrap_or(file.path()).to_string_lossy().replace('\\', "/");
+                found.push(LintEntry { kind, name, path, issues });
+            }
+            found.sort_by(|a, b| a.name.cmp(&b.name));
+            entries.extend(found);
+        }
+        Ok(entries)
+    }
+}
+
+fn problem(rule: &'static str, message: String) -> Issue {
+    Issue { severity: Severity::Error, rule, joint: None, field: None, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_reports_broken_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let good = registry::lookup("so101_follower").unwrap().template();
+        store.write_profile(Kind::Robots, "so101_follower/good", &good, false).unwrap();
+        let robots = dir.path().join("robots");
+        fs::write(robots.join("truncated.json"), "{\"gripper\": {\"id\": 6,").unwrap();
+        let mut bad = good.clone();
+        bad.0.get_mut("gripper").unwrap().drive_mode = 4;
+        bad.0.get_mut("wrist_roll").unwrap().range_max = 100;
+        fs::write(robots.join("so101_follower/bad.json"), serde_json::to_vec(&bad).unwrap()).unwrap();
+        // files the store would not list are still reported
+        fs::create_dir_all(robots.join("so101_follower/old")).unwrap();
+        fs::write(robots.join("so101_follower/old/arm.json"), serde_json::to_vec(&good).unwrap()).unwrap();
+        fs::write(robots.join("my arm.json"), serde_json::to_vec(&good).unwrap()).unwrap();
+        fs::create_dir_all(robots.join(".git")).unwrap();
+        fs::write(robots.join(".git/ignored.json"), "{").unwrap();
+
+        let entries = store.lint(&[Kind::Robots, Kind::Teleoperators]).unwrap();
+        let summary: Vec<(&str, usize, usize)> = entries.iter().map(|e| (e.name.as_str(), e.errors(), e.warnings())).collect();
+        assert_eq!(summary, [("my arm", 1, 0), ("so101_follower/bad", 1, 1), ("so101_follower/good", 0, 0), ("so101_follower/old/arm", 1, 0), ("truncated", 1, 0)]);
+        assert_eq!(entries[0].issues[0].rule, "name");
+        assert_eq!(entries[3].issues[0].rule, "name");
+        assert_eq!(entries[4].issues[0].rule, "parse");
+        assert_eq!(entries[1].path, "robots/so101_follower/bad.json");
+    }
+}
@@ -2,14 +2,30 @@ use std::ffi::OsStr;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
+use std::sync::Mutex;
 
+use serde::{Deserialize, Serialize};
 use serde_json::Error as SerdeError;
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 use thiserror::Error;
 
-use crate::model::Profile;
+use crate::model::format::{self, Style};
+use crate::model::{registry, rules, Profile};
 
-#[derive(Debug, Clone, Copy)]
+pub mod archive;
+pub mod history;
+pub mod lint;
+pub mod name;
+pub mod transfer;
+pub mod trash;
+
+pub use history::{Origin, Retention, Revision, RevisionMeta};
+pub use lint::LintEntry;
+pub use name::{parse_profile_ref, parse_revision_ref, NameError, ProfileName};
+pub use trash::TrashEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Kind {
     Robots,
     Teleoperators,
@@ -32,29 +48,148 @@ pub enum StoreError {
     Json(#[from] SerdeError),
     #[error("validation error: {0}")]
     Validation(String),
+    #[error("invalid profile name `{name}`: {reason}")]
+    InvalidName { name: String, reason: NameError },
+    /// The profile breaks validation rules; the report lists every finding.
+    #[error("invalid profile: {}", .0.errors().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid(rules::Report),
     #[error("not found: {0}")]
     NotFound(String),
+    #[error("already exists: {0}")]
+    Exists(String),
+    #[error("`{name}` matches several profiles: {}", candidates.join(", "))]
+    Ambiguous { name: String, candidates: Vec<String> },
+    /// `If-Match` precondition failed; `actual` is `None` when the profile does not exist.
+    #[error("version mismatch: expected {expected}, found {}", actual.as_deref().unwrap_or("no profile"))]
+    VersionMismatch { expected: String, actual: Option<String> },
+    /// A rollback target whose content the profile already has.
+    #[error("profile is already at revision {revision}")]
+    Unchanged { revision: u64 },
+    #[error("archive error: {0}")]
+    Archive(String),
+}
+
+/// Content version of a stored profile: FNV-1a 64 of the file bytes, in hex.
+pub fn version_of(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    format!("{:016x}", hash)
 }
 
+/// Whether an `If-Match` value (`*`, or a comma-separated list of possibly
+/// quoted or weak tags) accepts `version`.
+pub fn version_matches(if_match: &str, version: &str) -> bool {
+    if_match.split(',').map(str::trim).any(|tag| {
+        tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == version
+    })
+}
+
+/// A stored profile. `name` is its identity within the kind: `<robot_type>/<id>`
+/// for files in a type subdirectory (LeRobot's layout), or a bare `<id>` for
+/// files directly under the kind directory.
 #[derive(Debug, Clone)]
 pub struct ProfileMeta {
     pub name: String,
     pub path: PathBuf,
 }
 
+impl ProfileMeta {
+    /// The id part of the name, without the robot type.
+    pub fn id(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+/// Profiles of different robot types sharing an id, which a bare name cannot tell apart.
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub id: String,
+    pub names: Vec<String>,
+}
+
+/// Parses a profile name from a caller, see `ProfileName`.
+fn parse_name(name: &str) -> Result<ProfileName, StoreError> {
+    ProfileName::parse(name).map_err(|reason| StoreError::InvalidName { name: name.to_string(), reason })
+}
+
+/// How profile files are laid out on write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Keep each file's indentation and joint order; new files use LeRobot's layout.
+    #[default]
+    Preserve,
+    /// Always LeRobot's layout with joints in kinematic order (see `format::canonical`).
+    Canonical,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(Self::Preserve),
+            "canonical" => Ok(Self::Canonical),
+            _ => Err(format!("unknown profile format `{}` (expected preserve or canonical)", s)),
+        }
+    }
+}
+
 pub struct Store {
     root: PathBuf,
+    format: Format,
+    retention: Retention,
+    /// Trashed profiles older than this are purged; `None` keeps them.
+    trash_max_age: Option<std::time::Duration>,
+    /// Serializes version checks with the writes they guard.
+    write_lock: Mutex<()>,
 }
 
 impl Store {
     pub fn new(root: PathBuf) -> Self {
-        Self { root }
+        Self { root, format: Format::default(), retention: Retention::default(), trash_max_age: Some(trash::DEFAULT_MAX_AGE), write_lock: Mutex::new(()) }
+    }
+
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Profiles whose files are not in canonical layout; rewrites them
+    /// (with a `.bak` and a `fmt` revision) unless `check_only`. Files that do not parse are skipped (see `lint`).
+    pub fn canonicalize_files(&self, kinds: &[Kind], check_only: bool) -> Result<Vec<(Kind, String)>, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut changed = Vec::new();
+        for &kind in kinds {
+            for meta in self.list_profiles(kind)? {
+                let bytes = fs::read(&meta.path)?;
+                let Ok(profile) = serde_json::from_slice::<Profile>(&bytes) else {
+                    continue;
+                };
+                let canonical = format::canonical(&profile, registry::type_of(kind, &meta.name));
+                if canonical != bytes {
+                    if !check_only {
+                        // like any other write: keep the old file and record a revision
+                        self.import_existing(kind, &meta.name)?;
+                        fs::copy(&meta.path, meta.path.with_extension("json.bak"))?;
+                        let tmp = meta.path.with_extension("json.tmp");
+                        fs::write(&tmp, &canonical)?;
+                        fs::rename(&tmp, &meta.path)?;
+                        self.append_revision(kind, &meta.name, &profile, &Origin::new("fmt").summary(Some("canonical layout".into())))?;
+                    }
+                    changed.push((kind, meta.name));
+                }
+            }
+        }
+        Ok(changed)
     }
 
     fn kind_dir(&self, kind: Kind) -> PathBuf {
         self.root.join(kind.as_str())
     }
 
+    fn profile_path(&self, kind: Kind, name: &str) -> PathBuf {
+        self.kind_dir(kind).join(format!("{}.json", name))
+    }
+
+    /// Profiles of a kind, at the top level and one robot-type directory deep.
     #[instrument(skip(self))]
     pub fn list_profiles(&self, kind: Kind) -> Result<Vec<ProfileMeta>, StoreError> {
         let mut metas = Vec::new();
@@ -62,11 +197,21 @@ impl Store {
         if !dir.exists() {
             return Ok(metas);
         }
-        for entry in walkdir::WalkDir::new(&dir).into_iter().filter_map(Result::ok) {
+        let entries = walkdir::WalkDir::new(&dir)
+            .max_depth(2)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(Result::ok);
+        for entry in entries {
             let p = entry.path();
-            if p.is_file() && p.extension() == Some(OsStr::new("json")) {
-                if let Some(name) = p.file_stem().and_then(OsStr::to_str) {
-                    metas.push(ProfileMeta { name: name.to_string(), path: p.to_path_buf() });
+            if p.is_file() && p.extension() == Some(OsStr::new("json"))
+                && let Ok(rel) = p.with_extension("").strip_prefix(&dir)
+                && let Some(name) = rel.to_str()
+            {
+                let name = name.replace('\\', "/");
+                match ProfileName::parse(&name) {
+                    Ok(_) => metas.push(ProfileMeta { name, path: p.to_path_buf() }),
+                    Err(e) => warn!(?p, error = %e, "skipping file with an unusable profile name"),
                 }
             }
         }
@@ -75,21 +220,85 @@ impl Store {
         Ok(metas)
     }
 
+    /// Ids used by more than one profile of a kind.
+    pub fn duplicates(&self, kind: Kind) -> Result<Vec<Duplicate>, StoreError> {
+        let mut by_id: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+        for m in self.list_profiles(kind)? {
+            by_id.entry(m.id().to_string()).or_default().push(m.name);
+        }
+        let dups: Vec<Duplicate> = by_id.into_iter().filter(|(_, names)| names.len() > 1).map(|(id, names)| Duplicate { id, names }).collect();
+        for d in &dups {
+            warn!(kind = kind.as_str(), id = %d.id, names = ?d.names, "duplicate profile id");
+        }
+        Ok(dups)
+    }
+
+    /// Canonical name and path of a profile, whether or not it exists yet.
+    ///
+    /// `<robot_type>/<id>` maps straight to its file. A bare `<id>` means the
+    /// top-level file if there is one, else the single type directory holding
+    /// that id; several matches are `Ambiguous` rather than picking one.
+    fn locate(&self, kind: Kind, name: &str) -> Result<(String, PathBuf), StoreError> {
+        let name = parse_name(name)?;
+        let path = self.profile_path(kind, name.as_str());
+        if name.robot_type().is_some() || path.exists() {
+            return Ok((name.into(), path));
+        }
+        let name = name.as_str();
+        let mut matches: Vec<ProfileMeta> = self.list_profiles(kind)?.into_iter().filter(|m| m.id() == name).collect();
+        match matches.len() {
+            0 => Ok((name.to_string(), path)),
+            1 => {
+                let m = matches.remove(0);
+                Ok((m.name, m.path))
+            }
+            _ => Err(StoreError::Ambiguous { name: name.to_string(), candidates: matches.into_iter().map(|m| m.name).collect() }),
+        }
+    }
+
+    /// Canonical name of a profile (see `locate`).
+    pub fn canonical_name(&self, kind: Kind, name: &str) -> Result<String, StoreError> {
+        self.locate(kind, name).map(|(n, _)| n)
+    }
+
+    fn find_profile(&self, kind: Kind, name: &str) -> Result<PathBuf, StoreError> {
+        let (_, path) = self.locate(kind, name)?;
+        if path.is_file() { Ok(path) } else { Err(StoreError::NotFound(format!("{}:{}", kind.as_str(), name))) }
+    }
+
+    pub fn exists(&self, kind: Kind, name: &str) -> bool {
+        self.find_profile(kind, name).is_ok()
+    }
+
     #[instrument(skip(self))]
     pub fn read_profile(&self, kind: Kind, name: &str) -> Result<Profile, StoreError> {
-        let dir = self.kind_dir(kind);
-        // try to find file by name under dir
-        let mut found: Option<PathBuf> = None;
-        for entry in walkdir::WalkDir::new(&dir).into_iter().filter_map(Result::ok) {
-            let p = entry.path();
-            if p.is_file() && p.extension() == Some(OsStr::new("json")) {
-                if p.file_stem().and_then(OsStr::to_str) == Some(name) {
-                    found = Some(p.to_path_buf());
-                    break;
-                }
-            }
+        self.read_profile_versioned(kind, name).map(|(p, _)| p)
+    }
+
+    /// Current version of a profile, `None` when it does not exist.
+    pub fn version(&self, kind: Kind, name: &str) -> Result<Option<String>, StoreError> {
+        match self.find_profile(kind, name) {
+            Ok(path) => Ok(Some(version_of(&fs::read(path)?))),
+            Err(StoreError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn check_version(&self, kind: Kind, name: &str, if_match: Option<&str>) -> Result<(), StoreError> {
+        let Some(expected) = if_match else {
+            return Ok(());
+        };
+        let actual = self.version(kind, name)?;
+        match &actual {
+            Some(v) if version_matches(expected, v) => Ok(()),
+            _ => Err(StoreError::VersionMismatch { expected: expected.to_string(), actual }),
         }
-        let path = found.ok_or_else(|| StoreError::NotFound(format!("{}:{}", kind.as_str(), name)))?;
+    }
+
+    /// Reads a profile together with its version.
+    #[instrument(skip(self))]
+    pub fn read_profile_versioned(&self, kind: Kind, name: &str) -> Result<(Profile, String), StoreError> {
+        let path = self.find_profile(kind, name)?;
         let data = fs::read_to_string(&path).map_err(|e| {
             error!(?e, ?path, "read file error");
             e
@@ -102,21 +311,64 @@ impl Store {
             error!(error = %e, "validation error");
             StoreError::Validation(e)
         })?;
-        Ok(profile)
+        Ok((profile, version_of(data.as_bytes())))
     }
 
     #[instrument(skip(self, profile))]
     pub fn write_profile(&self, kind: Kind, name: &str, profile: &Profile, backup: bool) -> Result<PathBuf, StoreError> {
-        profile.validate().map_err(StoreError::Validation)?;
-        let dir = self.kind_dir(kind);
-        fs::create_dir_all(&dir)?;
-        let path = dir.join(format!("{}.json", name));
-        let tmp = dir.join(format!("{}.json.tmp", name));
-        let payload = serde_json::to_vec_pretty(profile)?;
+        self.write_profile_from(kind, name, profile, backup, Origin::new("store")).map(|(path, _)| path)
+    }
+
+    /// Writes a profile and records it as a new revision attributed to `origin`.
+    /// Returns the file's path and its new version.
+    #[instrument(skip(self, profile, origin))]
+    pub fn write_profile_from(&self, kind: Kind, name: &str, profile: &Profile, backup: bool, origin: Origin) -> Result<(PathBuf, String), StoreError> {
+        self.write_profile_if(kind, name, profile, backup, origin, None)
+    }
+
+    /// Like `write_profile_from`, but only if the stored version satisfies
+    /// `if_match` (see `version_matches`).
+    #[instrument(skip(self, profile, origin))]
+    pub fn write_profile_if(&self, kind: Kind, name: &str, profile: &Profile, backup: bool, origin: Origin, if_match: Option<&str>) -> Result<(PathBuf, String), StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.write_locked(kind, name, profile, backup, origin, if_match)
+    }
+
+    /// `write_profile_if` for callers already holding `write_lock`.
+    fn write_locked(&self, kind: Kind, name: &str, profile: &Profile, backup: bool, origin: Origin, if_match: Option<&str>) -> Result<(PathBuf, String), StoreError> {
+        let (name, path) = self.locate(kind, name)?;
+        let name = name.as_str();
+        let report = rules::check(profile, registry::type_of(kind, name));
+        if report.has_errors() {
+            return Err(StoreError::Invalid(report));
+        }
+        self.check_version(kind, name, if_match)?;
+        self.import_existing(kind, name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let existing = fs::read(&path).ok();
+        let payload = match (self.format, &existing) {
+            (Format::Canonical, _) => format::canonical(profile, registry::type_of(kind, name)),
+            (Format::Preserve, Some(bytes)) => format::to_vec(profile, &Style::detect(bytes)),
+            (Format::Preserve, None) => format::to_vec(profile, &Style::lerobot()),
+        };
+        // `Profile`'s `PartialEq` ignores joint order, so compare serialized
+        // forms: a save that only reorders joints is still a change
+        let same_content = || {
+            let stored = existing.as_deref().and_then(|b| serde_json::from_slice::<Profile>(b).ok());
+            stored.is_some_and(|p| serde_json::to_vec(&p).ok() == serde_json::to_vec(profile).ok())
+        };
+        if existing.as_deref() == Some(&payload[..]) || (self.format == Format::Preserve && same_content()) {
+            // nothing to write or record; leave the file (and its mtime) alone
+            info!(kind = kind.as_str(), name, "profile unchanged");
+            let version = version_of(existing.as_deref().unwrap_or_default());
+            return Ok((path, version));
+        }
+        let tmp = path.with_extension("json.tmp");
 
         if backup && path.exists() {
-            let bak = dir.join(format!("{}.json.bak", name));
-            fs::copy(&path, bak)?;
+            fs::copy(&path, path.with_extension("json.bak"))?;
         }
 
         {
@@ -130,22 +382,31 @@ impl Store {
             e
         })?;
         info!(kind = kind.as_str(), name, ?path, "write profile ok");
-        Ok(path)
+        self.append_revision(kind, name, profile, &origin)?;
+        Ok((path, version_of(&payload)))
     }
 
+    /// Moves a profile to the trash (see `trash`).
     #[instrument(skip(self))]
-    pub fn delete_profile(&self, kind: Kind, name: &str) -> Result<(), StoreError> {
-        let dir = self.kind_dir(kind);
-        let path = dir.join(format!("{}.json", name));
-        if !path.exists() {
+    pub fn delete_profile(&self, kind: Kind, name: &str) -> Result<TrashEntry, StoreError> {
+        self.delete_profile_if(kind, name, None)
+    }
+
+    #[instrument(skip(self))]
+    pub fn delete_profile_if(&self, kind: Kind, name: &str, if_match: Option<&str>) -> Result<TrashEntry, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.check_version(kind, name, if_match)?;
+        let (name, path) = self.locate(kind, name)?;
+        if !path.is_file() {
             return Err(StoreError::NotFound(format!("{}:{}", kind.as_str(), name)));
         }
-        fs::remove_file(&path).map_err(|e| {
-            error!(?e, ?path, "delete error");
-            e
-        })?;
-        info!(kind = kind.as_str(), name, "delete profile ok");
-        Ok(())
+        let entry = self.move_to_trash(kind, &name, &path)?;
+        info!(kind = kind.as_str(), name, trash = %entry.id, "delete profile ok");
+        // the delete itself succeeded; a failed cleanup must not report otherwise
+        if let Err(e) = self.purge_expired_locked() {
+            warn!(error = %e, "cannot purge old trash entries");
+        }
+        Ok(entry)
     }
 }
 
@@ -153,16 +414,16 @@ impl Store {
 mod tests {
     use super::*;
     use crate::model::{Joint, Profile};
-    use std::collections::HashMap;
+    use indexmap::IndexMap;
 
     #[test]
     fn list_and_rw_profile() {
         let dir = tempfile::tempdir().unwrap();
         let store = Store::new(dir.path().to_path_buf());
 
-        let mut map = HashMap::new();
-        map.insert("j1".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1, range_max: 10 });
-        let p = Profile(map);
+        let mut map = IndexMap::new();
+        map.insert("j1".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1, range_max: 10, ..Default::default() });
+        let p = Profile::new(map);
 
         // write
         let out = store.write_profile(Kind::Robots, "test_profile", &p, true).unwrap();
@@ -176,4 +437,125 @@ mod tests {
         let read = store.read_profile(Kind::Robots, "test_profile").unwrap();
         assert_eq!(read, p);
     }
+
+    #[test]
+    fn writes_check_the_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let mut map = IndexMap::new();
+        map.insert("j1".to_string(), Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1, range_max: 10, ..Default::default() });
+        let p = Profile::new(map);
+
+        let err = store.write_profile_if(Kind::Robots, "arm", &p, false, Origin::new("test"), Some("*")).unwrap_err();
+        assert!(matches!(err, StoreError::VersionMismatch { actual: None, .. }));
+        store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+        let (_, v1) = store.read_profile_versioned(Kind::Robots, "arm").unwrap();
+        assert_eq!(store.version(Kind::Robots, "arm").unwrap().as_deref(), Some(v1.as_str()));
+
+        let mut q = p.clone();
+        q.0.get_mut("j1").unwrap().range_max = 20;
+        let (_, v2) = store.write_profile_if(Kind::Robots, "arm", &q, false, Origin::new("test"), Some(&format!("\"{}\"", v1))).unwrap();
+        assert_eq!(store.version(Kind::Robots, "arm").unwrap(), Some(v2.clone()));
+        // an unchanged save reports the version already on disk
+        assert_eq!(store.write_profile_from(Kind::Robots, "arm", &q, false, Origin::new("test")).unwrap().1, v2);
+        let err = store.write_profile_if(Kind::Robots, "arm", &p, false, Origin::new("test"), Some(&v1)).unwrap_err();
+        assert!(matches!(err, StoreError::VersionMismatch { actual: Some(_), .. }));
+        assert!(store.delete_profile_if(Kind::Robots, "arm", Some(&v1)).is_err());
+        assert_eq!(store.read_profile(Kind::Robots, "arm").unwrap(), q);
+    }
+
+    #[test]
+    fn if_match_parsing() {
+        assert!(version_matches("*", "abc"));
+        assert!(version_matches("\"x\", W/\"abc\"", "abc"));
+        assert!(!version_matches("\"abcd\"", "abc"));
+    }
+
+    #[test]
+    fn subdirectory_profiles_share_one_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let p = registry::lookup("so101_follower").unwrap().template();
+        let follower = dir.path().join("robots/so101_follower");
+        fs::create_dir_all(&follower).unwrap();
+        fs::write(follower.join("my_arm.json"), serde_json::to_vec(&p).unwrap()).unwrap();
+
+        let names: Vec<String> = store.list_profiles(Kind::Robots).unwrap().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, ["so101_follower/my_arm"]);
+
+        // a bare id resolves to the single file holding it; writes land there too
+        let mut q = p.clone();
+        q.0.get_mut("gripper").unwrap().range_max = 3000;
+        let path = store.write_profile(Kind::Robots, "my_arm", &q, false).unwrap();
+        assert_eq!(path, follower.join("my_arm.json"));
+        assert_eq!(store.read_profile(Kind::Robots, "so101_follower/my_arm").unwrap(), q);
+        assert_eq!(store.history(Kind::Robots, "my_arm").unwrap().len(), 2);
+        assert!(!dir.path().join("robots/my_arm.json").exists());
+
+        // the same id under a second type is reported, and a bare id no longer resolves
+        store.write_profile(Kind::Robots, "so100_follower/my_arm", &p, false).unwrap();
+        let mut typo = p.clone();
+        typo.0.shift_remove("gripper");
+        assert!(matches!(store.write_profile(Kind::Robots, "so100_follower/my_arm", &typo, false), Err(StoreError::Invalid(_))));
+        let dups = store.duplicates(Kind::Robots).unwrap();
+        assert_eq!((dups[0].id.as_str(), dups[0].names.len()), ("my_arm", 2));
+        assert!(matches!(store.read_profile(Kind::Robots, "my_arm"), Err(StoreError::Ambiguous { .. })));
+
+        store.delete_profile(Kind::Robots, "so100_follower/my_arm").unwrap();
+        store.delete_profile(Kind::Robots, "my_arm").unwrap();
+        assert!(store.list_profiles(Kind::Robots).unwrap().is_empty());
+        assert!(matches!(store.read_profile(Kind::Robots, "../robots/my_arm"), Err(StoreError::InvalidName { .. })));
+        assert!(matches!(store.write_profile(Kind::Robots, "../../escape", &p, false), Err(StoreError::InvalidName { .. })));
+        assert!(!dir.path().parent().unwrap().join("escape.json").exists());
+    }
+
+    #[test]
+    fn writes_keep_joint_order_and_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let path = dir.path().join("robots/so101_follower/arm.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut p = registry::lookup("so101_follower").unwrap().template();
+        p.0.reverse();
+        let original = format::to_vec(&p, &Style { indent: Some("  ".into()), trailing_newline: true });
+        fs::write(&path, &original).unwrap();
+
+        store.write_profile(Kind::Robots, "so101_follower/arm", &p, true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!path.with_extension("json.bak").exists());
+
+        p.0.get_mut("gripper").unwrap().range_min = 100;
+        store.write_profile(Kind::Robots, "so101_follower/arm", &p, false).unwrap();
+        let written = String::from_utf8(fs::read(&path).unwrap()).unwrap();
+        let original = String::from_utf8(original).unwrap();
+        let changed: Vec<(&str, &str)> = original.lines().zip(written.lines()).filter(|(a, b)| a != b).map(|(a, b)| (a.trim(), b.trim())).collect();
+        assert_eq!(changed, [("\"range_min\": 0,", "\"range_min\": 100,")]);
+        assert!(written.starts_with("{\n  \"gripper\""));
+
+        let canonical = Store::new(dir.path().to_path_buf()).with_format(Format::Canonical);
+        canonical.write_profile(Kind::Robots, "so101_follower/arm", &p, false).unwrap();
+        let written = fs::read(&path).unwrap();
+        assert_eq!(written, format::canonical(&p, registry::lookup("so101_follower")));
+        assert!(written.starts_with(b"{\n    \"shoulder_pan\""));
+        assert!(canonical.canonicalize_files(&[Kind::Robots], true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fmt_keeps_a_backup_and_records_a_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let p = registry::lookup("so101_follower").unwrap().template();
+        let path = dir.path().join("robots/so101_follower/arm.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let original = format::to_vec(&p, &Style { indent: Some("  ".into()), trailing_newline: true });
+        fs::write(&path, &original).unwrap();
+
+        let changed = store.canonicalize_files(&[Kind::Robots], false).unwrap();
+        assert_eq!(changed, [(Kind::Robots, "so101_follower/arm".to_string())]);
+        assert_eq!(fs::read(&path).unwrap(), format::canonical(&p, registry::lookup("so101_follower")));
+        assert_eq!(fs::read(path.with_extension("json.bak")).unwrap(), original);
+        let history = store.history(Kind::Robots, "so101_follower/arm").unwrap();
+        assert_eq!(history.iter().map(|m| m.source.as_str()).collect::<Vec<_>>(), ["import", "fmt"]);
+        assert!(store.canonicalize_files(&[Kind::Robots], false).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,188 @@
+//! Profile names.
+//!
+//! Names come from URL paths, request bodies and CLI arguments and end up
+//! joined into file paths, so they are parsed strictly: one or two
+//! components (`<id>` or `<robot_type>/<id>`), each made of ASCII letters,
+//! digits, `_`, `-` and `.`, not starting with a dot.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Kind;
+
+/// Longest accepted component.
+pub const MAX_COMPONENT_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NameError {
+    #[error("name is empty")]
+    Empty,
+    #[error("name has {0} components; expected <id> or <robot_type>/<id>")]
+    TooManyComponents(usize),
+    #[error("empty component (leading, trailing or doubled `/`)")]
+    EmptyComponent,
+    #[error("component `{0}` starts with a dot")]
+    Hidden(String),
+    #[error("component is longer than {MAX_COMPONENT_LEN} characters")]
+    TooLong,
+    #[error("character {0:?} is not allowed (use letters, digits, `_`, `-` and `.`)")]
+    BadChar(char),
+}
+
+/// A validated profile name, safe to join under a kind directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ProfileName(String);
+
+impl ProfileName {
+    pub fn parse(s: &str) -> Result<Self, NameError> {
+        if s.is_empty() {
+            return Err(NameError::Empty);
+        }
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() > 2 {
+            return Err(NameError::TooManyComponents(parts.len()));
+        }
+        for part in parts {
+            if part.is_empty() {
+                return Err(NameError::EmptyComponent);
+            }
+            if part.starts_with('.') {
+                return Err(NameError::Hidden(part.to_string()));
+            }
+            if part.len() > MAX_COMPONENT_LEN {
+                return Err(NameError::TooLong);
+            }
+            if let Some(c) = part.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))) {
+                return Err(NameError::BadChar(c));
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The robot-type directory, for `<robot_type>/<id>` names.
+    pub fn robot_type(&self) -> Option<&str> {
+        self.0.split_once('/').map(|(t, _)| t)
+    }
+
+    /// The id part, without the robot type.
+    pub fn id(&self) -> &str {
+        self.0.rsplit('/').next().unwrap_or(&self.0)
+    }
+
+    /// `<robot_type>/<id>`, or the name unchanged if it already has a type.
+    pub fn with_type(&self, robot_type: &str) -> Result<Self, NameError> {
+        match self.robot_type() {
+            Some(_) => Ok(self.clone()),
+            None => Self::parse(&format!("{}/{}", robot_type, self.0)),
+        }
+    }
+}
+
+impl FromStr for ProfileName {
+    type Err = NameError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for ProfileName {
+    type Error = NameError;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<ProfileName> for String {
+    fn from(n: ProfileName) -> Self {
+        n.0
+    }
+}
+
+impl AsRef<str> for ProfileName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ProfileName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Parses `<robots|teleoperators>/<name>`.
+pub fn parse_profile_ref(s: &str) -> Option<(Kind, String)> {
+    let (kind, name) = s.split_once('/')?;
+    let kind = match kind {
+        "robots" => Kind::Robots,
+        "teleoperators" => Kind::Teleoperators,
+        _ => return None,
+    };
+    ProfileName::parse(name).ok().map(|n| (kind, n.into()))
+}
+
+/// Parses `<kind>/<name>[@<revision>]`, as the diff endpoints take it.
+pub fn parse_revision_ref(s: &str) -> Option<(Kind, String, Option<u64>)> {
+    match s.rsplit_once('@') {
+        Some((r, rev)) => {
+            let (kind, name) = parse_profile_ref(r)?;
+            Some((kind, name, Some(rev.parse().ok()?)))
+        }
+        None => parse_profile_ref(s).map(|(kind, name)| (kind, name, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ids_and_typed_names() {
+        let n = ProfileName::parse("so101_follower/my-arm.v2").unwrap();
+        assert_eq!((n.robot_type(), n.id()), (Some("so101_follower"), "my-arm.v2"));
+        let n = ProfileName::parse("arm").unwrap();
+        assert_eq!((n.robot_type(), n.id()), (None, "arm"));
+        assert_eq!(n.with_type("koch_leader").unwrap().as_str(), "koch_leader/arm");
+    }
+
+    #[test]
+    fn rejects_traversal_and_odd_names() {
+        for (name, err) in [
+            ("", NameError::Empty),
+            ("..", NameError::Hidden("..".into())),
+            ("../etc/passwd", NameError::TooManyComponents(3)),
+            ("../robots", NameError::Hidden("..".into())),
+            ("so101_follower/..", NameError::Hidden("..".into())),
+            (".history", NameError::Hidden(".history".into())),
+            ("/etc", NameError::EmptyComponent),
+            ("arm/", NameError::EmptyComponent),
+            ("a//b", NameError::TooManyComponents(3)),
+            ("a\\..\\b", NameError::BadChar('\\')),
+            ("C:arm", NameError::BadChar(':')),
+            ("arm\0", NameError::BadChar('\0')),
+            ("my arm", NameError::BadChar(' ')),
+            ("bräu", NameError::BadChar('ä')),
+        ] {
+            assert_eq!(ProfileName::parse(name), Err(err), "{:?}", name);
+        }
+        assert_eq!(ProfileName::parse(&"a".repeat(MAX_COMPONENT_LEN + 1)), Err(NameError::TooLong));
+        assert!(serde_json::from_str::<ProfileName>("\"../x\"").is_err());
+    }
+
+    #[test]
+    fn revision_refs() {
+        assert_eq!(parse_revision_ref("robots/so101_follower/arm@3"), Some((Kind::Robots, "so101_follower/arm".into(), Some(3))));
+        assert_eq!(parse_revision_ref("teleoperators/arm"), Some((Kind::Teleoperators, "arm".into(), None)));
+        assert_eq!(parse_revision_ref("robots/arm@latest"), None);
+        assert_eq!(parse_revision_ref("arm@2"), None);
+        assert_eq!(parse_revision_ref("robots/../../etc/passwd"), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Copying and renaming profiles.
+//!
+//! A copy starts a fresh history; a rename moves the file, its `.bak` backup
+//! and its history together, so the profile keeps its revisions.
+
+use std::fs;
+
+use tracing::{error, info, instrument};
+
+use super::{Kind, Origin, Store, StoreError};
+use crate::model::{registry, rules};
+
+impl Store {
+    /// Copies a profile to a new name, possibly of the other kind (e.g. a
+    /// follower seeded from a leader). Returns the canonical target name.
+    #[instrument(skip(self, origin))]
+    pub fn copy_profile(&self, kind: Kind, name: &str, to_kind: Kind, to: &str, origin: Origin) -> Result<String, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (from, _) = self.locate(kind, name)?;
+        let profile = self.read_profile(kind, &from)?;
+        let (target, path) = self.locate(to_kind, to)?;
+        if path.exists() {
+            return Err(StoreError::Exists(format!("{}:{}", to_kind.as_str(), target)));
+        }
+        let summary = origin.summary.clone().or_else(|| Some(format!("copied from {}/{}", kind.as_str(), from)));
+        self.write_locked(to_kind, &target, &profile, false, origin.summary(summary), None)?;
+        info!(from = %from, to = %target, "copy profile ok");
+        Ok(target)
+    }
+
+    /// Moves a profile with its backup and history to a new name. Returns the
+    /// canonical target name.
+    #[instrument(skip(self, origin))]
+    pub fn rename_profile(&self, kind: Kind, name: &str, to_kind: Kind, to: &str, if_match: Option<&str>, origin: Origin) -> Result<String, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.check_version(kind, name, if_match)?;
+        let (from, from_path) = self.locate(kind, name)?;
+        let profile = self.read_profile(kind, &from)?;
+        let (target, to_path) = self.locate(to_kind, to)?;
+        if to_path.exists() {
+            return Err(StoreError::Exists(format!("{}:{}", to_kind.as_str(), target)));
+        }
+        let (from_history, to_history) = (self.history_dir(kind, &from), self.history_dir(to_kind, &target));
+        if to_history.exists() {
+            return Err(StoreError::Exists(format!("history of {}:{}", to_kind.as_str(), target)));
+        }
+        let report = rules::check(&profile, registry::type_of(to_kind, &target));
+        if report.has_errors() {
+            return Err(StoreError::Invalid(report));
+        }
+        self.import_existing(kind, &from)?;
+
+        for dir in [to_path.parent(), to_history.parent()].into_iter().flatten() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::rename(&from_path, &to_path)?;
+        if let Err(e) = fs::rename(&from_history, &to_history) {
+            error!(?e, ?from_history, ?to_history, "history move error");
+            fs::rename(&to_path, &from_path)?;
+            return Err(e.into());
+        }
+        let backup = from_path.with_extension("json.bak");
+        if backup.exists() {
+            fs::rename(&backup, to_path.with_extension("json.bak"))?;
+        }
+        let summary = origin.summary.clone().or_else(|| Some(format!("renamed from {}/{}", kind.as_str(), from)));
+        self.append_revision(to_kind, &target, &profile, &origin.summary(summary))?;
+        info!(from = %from, to = %target, "rename profile ok");
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::registry;
+
+    #[test]
+    fn copy_and_rename_keep_history_where_it_belongs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let mut leader = registry::lookup("so101_leader").unwrap().template();
+        store.write_profile(Kind::Teleoperators, "so101_leader/lead", &leader, true).unwrap();
+        leader.0.get_mut("gripper").unwrap().range_min = 1000;
+        store.write_profile(Kind::Teleoperators, "so101_leader/lead", &leader, true).unwrap();
+
+        let copy = store.copy_profile(Kind::Teleoperators, "lead", Kind::Robots, "so101_follower/arm", Origin::new("test")).unwrap();
+        assert_eq!(copy, "so101_follower/arm");
+        assert_eq!(store.read_profile(Kind::Robots, "arm").unwrap(), leader);
+        let history = store.history(Kind::Robots, "arm").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].summary, "copied from teleoperators/so101_leader/lead");
+        assert!(matches!(store.copy_profile(Kind::Teleoperators, "lead", Kind::Robots, "arm", Origin::new("test")), Err(StoreError::Exists(_))));
+
+        let renamed = store.rename_profile(Kind::Teleoperators, "lead", Kind::Teleoperators, "so101_leader/left", None, Origin::new("test")).unwrap();
+        assert_eq!(renamed, "so101_leader/left");
+        assert!(!store.exists(Kind::Teleoperators, "lead"));
+        let history = store.history(Kind::Teleoperators, "left").unwrap();
+        assert_eq!(history.iter().map(|m| m.revision).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(history[2].summary, "renamed from teleoperators/so101_leader/lead");
+        assert!(dir.path().join("teleoperators/so101_leader/left.json.bak").exists());
+        assert!(!dir.path().join("teleoperators/so101_leader/lead.json.bak").exists());
+
+        // the target type's schema applies
+        let err = store.rename_profile(Kind::Teleoperators, "left", Kind::Robots, "lekiwi/arm", None, Origin::new("test")).unwrap_err();
+        assert!(matches!(err, StoreError::Invalid(_)));
+        assert!(store.exists(Kind::Teleoperators, "left"));
+    }
+}
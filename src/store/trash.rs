@@ -0,0 +1,261 @@
+//! Deleted profiles.
+//!
+//! Deleting moves the profile file, its `.bak` backup and its history into
+//! `<root>/.trash/<id>/` next to a `meta.json` describing where it came from.
+//! Entries can be restored under their old (or a new) name, or purged;
+//! entries older than the store's trash age are purged automatically.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use super::history::{format_utc, now};
+use super::{Kind, Origin, Store, StoreError};
+use crate::model::{registry, rules, Profile};
+
+/// Default age after which trashed profiles are purged.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30 * 86_400);
+
+const META: &str = "meta.json";
+const PROFILE: &str = "profile.json";
+const BACKUP: &str = "profile.json.bak";
+const HISTORY: &str = "history";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrashEntry {
+    /// Directory name under `.trash`, unique per deletion.
+    pub id: String,
+    pub kind: Kind,
+    /// Canonical name the profile had.
+    pub name: String,
+    /// Seconds since the Unix epoch.
+    pub deleted_at: u64,
+    pub has_backup: bool,
+    pub revisions: usize,
+}
+
+impl TrashEntry {
+    pub fn time(&self) -> String {
+        format_utc(self.deleted_at)
+    }
+}
+
+impl Store {
+    /// Replaces the default trash age; `None` keeps trashed profiles forever.
+    pub fn with_trash_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.trash_max_age = max_age;
+        self
+    }
+
+    fn trash_dir(&self) -> PathBuf {
+        self.root.join(".trash")
+    }
+
+    /// Directory of a trash entry; ids are single plain path components.
+    fn entry_dir(&self, id: &str) -> Result<PathBuf, StoreError> {
+        let plain = !id.is_empty() && !id.starts_with('.') && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        let dir = self.trash_dir().join(id);
+        if !plain || !dir.join(META).is_file() {
+            return Err(StoreError::NotFound(format!("trash:{}", id)));
+        }
+        Ok(dir)
+    }
+
+    /// Moves a profile and everything kept for it into a new trash entry.
+    /// The caller holds `write_lock`.
+    pub(super) fn move_to_trash(&self, kind: Kind, name: &str, path: &Path) -> Result<TrashEntry, StoreError> {
+        self.import_existing(kind, name)?;
+        let revisions = self.revision_numbers(kind, name)?.len();
+        let deleted_at = now();
+        let base = format!("{}-{}-{}", deleted_at, kind.as_str(), name.replace('/', "--"));
+        let (id, dir) = (1..)
+            .map(|n| if n == 1 { base.clone() } else { format!("{}-{}", base, n) })
+            .map(|id| {
+                let dir = self.trash_dir().join(&id);
+                (id, dir)
+            })
+            .find(|(_, dir)| !dir.exists())
+            .expect("unbounded");
+        fs::create_dir_all(&dir)?;
+        fs::rename(path, dir.join(PROFILE))?;
+        let backup = path.with_extension("json.bak");
+        let has_backup = backup.exists();
+        if has_backup {
+            fs::rename(&backup, dir.join(BACKUP))?;
+        }
+        let history = self.history_dir(kind, name);
+        if history.exists() {
+            fs::rename(&history, dir.join(HISTORY))?;
+        }
+        let entry = TrashEntry { id, kind, name: name.to_string(), deleted_at, has_backup, revisions };
+        fs::write(dir.join(META), serde_json::to_vec_pretty(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Trashed profiles, newest first.
+    pub fn trash(&self) -> Result<Vec<TrashEntry>, StoreError> {
+        let dir = self.trash_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for e in fs::read_dir(dir)?.filter_map(Result::ok) {
+            match fs::read(e.path().join(META)).map_err(StoreError::from).and_then(|b| Ok(serde_json::from_slice::<TrashEntry>(&b)?)) {
+                // the directory name is authoritative, not the id in the file
+                Ok(entry) => entries.push(TrashEntry { id: e.file_name().to_string_lossy().into_owned(), ..entry }),
+                Err(err) => warn!(path = ?e.path(), error = %err, "skipping unreadable trash entry"),
+            }
+        }
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| b.id.cmp(&a.id)));
+        Ok(entries)
+    }
+
+    /// The profile as it was when deleted.
+    pub fn trashed_profile(&self, id: &str) -> Result<Profile, StoreError> {
+        Ok(serde_json::from_slice(&fs::read(self.entry_dir(id)?.join(PROFILE))?)?)
+    }
+
+    /// Puts a trashed profile back, under its old name unless `to` is given.
+    /// Returns where it was restored to.
+    #[instrument(skip(self))]
+    pub fn restore(&self, id: &str, to: Option<(Kind, &str)>) -> Result<(Kind, String), StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = self.entry_dir(id)?;
+        let entry: TrashEntry = serde_json::from_slice(&fs::read(dir.join(META))?)?;
+        let (kind, name) = to.unwrap_or((entry.kind, entry.name.as_str()));
+        let (name, path) = self.locate(kind, name)?;
+        if path.exists() {
+            return Err(StoreError::Exists(format!("{}:{}", kind.as_str(), name)));
+        }
+        let history = self.history_dir(kind, &name);
+        if history.exists() {
+            return Err(StoreError::Exists(format!("history of {}:{}", kind.as_str(), name)));
+        }
+        let profile: Profile = serde_json::from_slice(&fs::read(dir.join(PROFILE))?)?;
+        let report = rules::check(&profile, registry::type_of(kind, &name));
+        if report.has_errors() {
+            return Err(StoreError::Invalid(report));
+        }
+
+        for d in [path.parent(), history.parent()].into_iter().flatten() {
+            fs::create_dir_all(d)?;
+        }
+        fs::rename(dir.join(PROFILE), &path)?;
+        if dir.join(BACKUP).exists() {
+            fs::rename(dir.join(BACKUP), path.with_extension("json.bak"))?;
+        }
+        if dir.join(HISTORY).exists() {
+            fs::rename(dir.join(HISTORY), &history)?;
+        }
+        fs::remove_dir_all(&dir)?;
+        let origin = Origin::new("trash").summary(Some(format!("restored from trash ({})", entry.time())));
+        self.append_revision(kind, &name, &profile, &origin)?;
+        info!(id, kind = kind.as_str(), name, "restore profile ok");
+        Ok((kind, name))
+    }
+
+    /// Removes a trash entry for good.
+    #[instrument(skip(self))]
+    pub fn purge(&self, id: &str) -> Result<(), StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        fs::remove_dir_all(self.entry_dir(id)?)?;
+        info!(id, "purge trash entry ok");
+        Ok(())
+    }
+
+    /// Purges entries deleted more than `age` ago (all of them for zero).
+    pub fn purge_older_than(&self, age: Duration) -> Result<Vec<TrashEntry>, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.purge_older_locked(age)
+    }
+
+    /// Applies the store's trash age.
+    pub fn purge_expired(&self) -> Result<Vec<TrashEntry>, StoreError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.purge_expired_locked()
+    }
+
+    /// `purge_expired` for callers already holding `write_lock`.
+    pub(super) fn purge_expired_locked(&self) -> Result<Vec<TrashEntry>, StoreError> {
+        match self.trash_max_age {
+            Some(age) => self.purge_older_locked(age),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn purge_older_locked(&self, age: Duration) -> Result<Vec<TrashEntry>, StoreError> {
+        let cutoff = now().saturating_sub(age.as_secs());
+        let mut purged = Vec::new();
+        for entry in self.trash()? {
+            if age.is_zero() || entry.deleted_at < cutoff {
+                fs::remove_dir_all(self.trash_dir().join(&entry.id))?;
+                purged.push(entry);
+            }
+        }
+        if !purged.is_empty() {
+            info!(count = purged.len(), "purged trash");
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_profiles_can_be_restored_or_purged() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let mut p = registry::lookup("so101_follower").unwrap().template();
+        store.write_profile(Kind::Robots, "so101_follower/arm", &p, true).unwrap();
+        p.0.get_mut("gripper").unwrap().range_min = 1000;
+        store.write_profile(Kind::Robots, "so101_follower/arm", &p, true).unwrap();
+
+        let entry = store.delete_profile(Kind::Robots, "arm").unwrap();
+        assert_eq!((entry.name.as_str(), entry.has_backup, entry.revisions), ("so101_follower/arm", true, 2));
+        assert!(!store.exists(Kind::Robots, "arm"));
+        assert!(store.history(Kind::Robots, "so101_follower/arm").is_err());
+        assert_eq!(store.trash().unwrap(), std::slice::from_ref(&entry));
+        assert_eq!(store.trashed_profile(&entry.id).unwrap(), p);
+
+        // a new profile took the name: restoring needs another one
+        store.write_profile(Kind::Robots, "so101_follower/arm", &p, false).unwrap();
+        assert!(matches!(store.restore(&entry.id, None), Err(StoreError::Exists(_))));
+        let (kind, name) = store.restore(&entry.id, Some((Kind::Robots, "so101_follower/old"))).unwrap();
+        assert_eq!((kind, name.as_str()), (Kind::Robots, "so101_follower/old"));
+        assert_eq!(store.read_profile(Kind::Robots, "old").unwrap(), p);
+        let history = store.history(Kind::Robots, "old").unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(history[2].summary.starts_with("restored from trash"));
+        assert!(dir.path().join("robots/so101_follower/old.json.bak").exists());
+        assert!(store.trash().unwrap().is_empty());
+
+        let entry = store.delete_profile(Kind::Robots, "old").unwrap();
+        assert!(matches!(store.restore("../robots", None), Err(StoreError::NotFound(_))));
+        assert!(matches!(store.purge("nope"), Err(StoreError::NotFound(_))));
+        store.purge(&entry.id).unwrap();
+        assert!(store.trash().unwrap().is_empty());
+    }
+
+    #[test]
+    fn old_entries_are_purged() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf()).with_trash_max_age(Some(Duration::from_secs(3600)));
+        let p = registry::lookup("so101_leader").unwrap().template();
+        store.write_profile(Kind::Teleoperators, "a", &p, false).unwrap();
+        store.write_profile(Kind::Teleoperators, "b", &p, false).unwrap();
+        let old = store.delete_profile(Kind::Teleoperators, "a").unwrap();
+        // age the entry past the limit
+        let meta = dir.path().join(".trash").join(&old.id).join(META);
+        let aged = TrashEntry { deleted_at: old.deleted_at - 7200, ..old.clone() };
+        fs::write(meta, serde_json::to_vec(&aged).unwrap()).unwrap();
+
+        let recent = store.delete_profile(Kind::Teleoperators, "b").unwrap();
+        assert_eq!(store.trash().unwrap(), [recent]);
+        assert_eq!(store.purge_older_than(Duration::ZERO).unwrap().len(), 1);
+    }
+}
@@ -9,39 +9,141 @@ use axum::{
 use serde::Deserialize;
 
 use crate::api::AppState;
-use crate::store::Kind;
+use crate::calib::homing::{self, HomingEntry};
+use crate::calib::ids::{self, Assignment, Device};
+use crate::model::{self, registry, Change, Joint, JointStatus};
+use crate::store::{parse_revision_ref, Kind, Origin, ProfileName, Revision, RevisionMeta, StoreError, TrashEntry};
 
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/", get(index))
+        .route("/profiles", post(create_from_template))
         .route("/profiles/:kind/:profile", get(view_profile))
         .route("/profiles/:kind/:profile", post(update_profile))
+        .route("/profiles/:kind/:profile/history", get(view_history))
+        .route("/profiles/:kind/:profile/history", post(update_history))
         .route("/arm/:kind/:profile", get(view_arm))
         .route("/arm/:kind/:profile", post(update_arm))
+        .route("/arm/:kind/:profile/homing", get(view_homing))
+        .route("/arm/:kind/:profile/homing", post(update_homing))
+        .route("/arm/:kind/:profile/ids", get(view_ids))
+        .route("/arm/:kind/:profile/ids", post(update_ids))
+        .route("/diff", get(view_diff))
+        .route("/trash", get(view_trash))
+        .route("/trash", post(update_trash))
         .route("/assets/lerobot-arm.jpg", get(arm_image))
         .with_state(state)
 }
 
+/// Percent-encodes a profile name (`<robot_type>/<id>`) as a single path segment.
+fn segment(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate<'a> {
     title: &'a str,
-    robots: Vec<String>,
-    leaders: Vec<String>,
+    robots: Vec<ProfileRow>,
+    leaders: Vec<ProfileRow>,
+    /// Ids used under several robot types, as `kind: id (name, name)`.
+    duplicates: Vec<String>,
+    /// Choices for "new from template".
+    types: &'static [registry::RobotType],
+    error: Option<String>,
+}
+
+/// A profile on the index page with its lint findings.
+struct ProfileRow {
+    name: String,
+    /// False for files the store cannot open by name; shown without links.
+    listed: bool,
+    path: String,
+    errors: usize,
+    warnings: usize,
+    /// Issue messages, errors first.
+    issues: Vec<String>,
+}
+
+fn profile_rows(state: &AppState, kind: Kind) -> Vec<ProfileRow> {
+    match state.store.lint(&[kind]) {
+        Ok(entries) => entries
+            .into_iter()
+            .map(|e| {
+                let (errors, warnings) = (e.errors(), e.warnings());
+                let mut issues = e.issues;
+                issues.sort_by_key(|i| i.severity);
+                let listed = ProfileName::parse(&e.name).is_ok();
+                ProfileRow { name: e.name, listed, path: e.path, errors, warnings, issues: issues.iter().map(ToString::to_string).collect() }
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn index_page(state: &AppState, error: Option<String>) -> IndexTemplate<'static> {
+    let robots = profile_rows(state, Kind::Robots);
+    let leaders = profile_rows(state, Kind::Teleoperators);
+    let duplicates = [Kind::Robots, Kind::Teleoperators]
+        .into_iter()
+        .flat_map(|k| {
+            state.store.duplicates(k).unwrap_or_default().into_iter().map(move |d| format!("{}: {} ({})", k.as_str(), d.id, d.names.join(", ")))
+        })
+        .collect();
+    IndexTemplate { title: "LeRobot Servo Adjust", robots, leaders, duplicates, types: registry::TYPES, error }
 }
 
 async fn index(State(state): State<AppState>) -> impl IntoResponse {
-    let robots = state
-        .store
-        .list_profiles(Kind::Robots)
-        .map(|v| v.into_iter().map(|m| m.name).collect())
-        .unwrap_or_default();
-    let leaders = state
-        .store
-        .list_profiles(Kind::Teleoperators)
-        .map(|v| v.into_iter().map(|m| m.name).collect())
-        .unwrap_or_default();
-    IndexTemplate { title: "LeRobot Servo Adjust", robots, leaders }
+    index_page(&state, None)
+}
+
+#[derive(Deserialize)]
+struct TemplateForm {
+    template: String,
+    id: String,
+}
+
+/// Response of the API's create, copy and rename endpoints.
+#[derive(Deserialize)]
+struct Placed {
+    kind: String,
+    name: String,
+}
+
+async fn create_from_template(State(state): State<AppState>, Form(form): Form<TemplateForm>) -> Response {
+    let failed = |e: String| index_page(&state, Some(e)).into_response();
+    let Some(ty) = registry::lookup(&form.template) else {
+        return failed(format!("unknown robot type `{}`", form.template));
+    };
+    let id = form.id.trim();
+    let name = match ProfileName::parse(id).and_then(|n| n.with_type(ty.name)) {
+        Ok(n) => n.to_string(),
+        Err(e) => return failed(format!("invalid id `{}`: {}", id, e)),
+    };
+    if let Some(base) = state.base_url.as_deref() {
+        let url = format!("{}/api/profiles/{}", base, ty.kind.as_str());
+        let body = serde_json::json!({"name": id, "template": ty.name});
+        match reqwest::Client::new().post(url).json(&body).send().await {
+            Ok(resp) if resp.status().is_success() => match resp.json::<Placed>().await {
+                Ok(p) => Redirect::to(&format!("/arm/{}/{}", p.kind, segment(&p.name))).into_response(),
+                Err(e) => failed(format!("invalid response: {}", e)),
+            },
+            Ok(resp) => failed(format!("create failed: {}", resp.status())),
+            Err(e) => failed(format!("request error: {}", e)),
+        }
+    } else if state.store.exists(ty.kind, &name) {
+        failed(format!("{} already exists", name))
+    } else {
+        match state.store.write_profile_from(ty.kind, &name, &ty.template(), false, Origin::new("web").summary(Some(format!("created from {} template", ty.name)))) {
+            Ok(_) => Redirect::to(&format!("/arm/{}/{}", ty.kind.as_str(), segment(&name))).into_response(),
+            Err(e) => failed(write_error(&e)),
+        }
+    }
 }
 
 #[derive(Template)]
@@ -51,19 +153,36 @@ struct ProfileTemplate<'a> {
     kind: String,
     name: String,
     json: String,
+    /// Store version the form was loaded at, sent back as `If-Match`.
+    version: String,
     error: Option<String>,
 }
 
+const CONFLICT: &str = "Someone else saved this profile after you opened it, so your change was not saved. Reload to get the latest version.";
+
+/// Message for a failed store write, spelling out version conflicts.
+fn write_error(e: &StoreError) -> String {
+    match e {
+        StoreError::VersionMismatch { .. } => CONFLICT.into(),
+        e => format!("{}", e),
+    }
+}
+
+/// Message for a non-success API response to a forwarded write.
+fn api_write_error(what: &str, status: reqwest::StatusCode) -> String {
+    if status == reqwest::StatusCode::PRECONDITION_FAILED { CONFLICT.into() } else { format!("{} failed: {}", what, status) }
+}
+
 async fn view_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> impl IntoResponse {
     let kind_str = kind.clone();
     let k = match kind.as_str() {
         "robots" => Kind::Robots,
         "teleoperators" => Kind::Teleoperators,
-        _ => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some("invalid kind".into()) },
+        _ => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), version: String::new(), error: Some("invalid kind".into()) },
     };
-    match state.store.read_profile(k, &profile) {
-        Ok(p) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: serde_json::to_string_pretty(&p).unwrap_or_default(), error: None },
-        Err(e) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some(format!("{}", e)) },
+    match state.store.read_profile_versioned(k, &profile) {
+        Ok((p, version)) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::from_utf8(model::format::to_vec(&p, &model::format::Style::lerobot())).unwrap_or_default(), version, error: None },
+        Err(e) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), version: String::new(), error: Some(format!("{}", e)) },
     }
 }
 
@@ -71,54 +190,99 @@ async fn view_profile(State(state): State<AppState>, Path((kind, profile)): Path
 struct UpdateForm {
     action: Option<String>,
     json: Option<String>,
+    /// Target of `copy` and `rename`.
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    to_kind: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
 }
 
 async fn update_profile(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Form(form): Form<UpdateForm>) -> impl IntoResponse {
     let kind_str = kind.clone();
+    let version = form.version.clone().filter(|v| !v.is_empty());
     let k = match kind.as_str() {
         "robots" => Kind::Robots,
         "teleoperators" => Kind::Teleoperators,
-        _ => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some("invalid kind".into()) }.into_response(),
+        _ => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), version: String::new(), error: Some("invalid kind".into()) }.into_response(),
+    };
+    let failed = |json: String, error: String| ProfileTemplate { title: "Profile", kind: kind_str.clone(), name: profile.clone(), json, version: version.clone().unwrap_or_default(), error: Some(error) }.into_response();
+    let api_url = state.base_url.as_deref().map(|u| format!("{}/api/profiles/{}/{}", u, kind_str, segment(&profile)));
+    let with_version = |req: reqwest::RequestBuilder| match version.as_deref() {
+        Some(v) => req.header("if-match", format!("\"{}\"", v)),
+        None => req,
     };
-    let api_url = state.base_url.as_deref().map(|u| format!("{}/api/profiles/{}/{}", u, kind_str, profile));
     if matches!(form.action.as_deref(), Some("delete")) {
         if let Some(url) = api_url.as_deref() {
-            match reqwest::Client::new().delete(url).send().await {
+            match with_version(reqwest::Client::new().delete(url)).send().await {
                 Ok(resp) if resp.status().is_success() => return Redirect::to("/").into_response(),
-                Ok(resp) => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some(format!("delete failed: {}", resp.status())) }.into_response(),
-                Err(e) => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some(format!("request error: {}", e)) }.into_response(),
+                Ok(resp) => return failed(String::new(), api_write_error("delete", resp.status())),
+                Err(e) => return failed(String::new(), format!("request error: {}", e)),
             }
         } else {
-            match state.store.delete_profile(k, &profile) {
+            match state.store.delete_profile_if(k, &profile, version.as_deref()) {
                 Ok(_) => return Redirect::to("/").into_response(),
-                Err(e) => return ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some(format!("{}", e)) }.into_response(),
+                Err(e) => return failed(String::new(), write_error(&e)),
+            }
+        }
+    }
+    if let Some(action @ ("copy" | "rename")) = form.action.as_deref() {
+        let to = form.to.as_deref().unwrap_or_default().trim();
+        let to_kind = form.to_kind.as_deref().unwrap_or(&kind_str);
+        let to_k = match to_kind {
+            "robots" => Kind::Robots,
+            "teleoperators" => Kind::Teleoperators,
+            _ => return failed(String::new(), "invalid target kind".into()),
+        };
+        if to.is_empty() {
+            return failed(String::new(), format!("missing name to {} to", action));
+        }
+        if let Some(url) = api_url.as_deref() {
+            let body = serde_json::json!({"kind": to_kind, "to": to});
+            match with_version(reqwest::Client::new().post(format!("{}/{}", url, action))).json(&body).send().await {
+                Ok(resp) if resp.status().is_success() => match resp.json::<Placed>().await {
+                    Ok(p) => return Redirect::to(&format!("/profiles/{}/{}", p.kind, segment(&p.name))).into_response(),
+                    Err(e) => return failed(String::new(), format!("invalid response: {}", e)),
+                },
+                Ok(resp) if resp.status() == reqwest::StatusCode::CONFLICT => return failed(String::new(), format!("{}/{} already exists", to_kind, to)),
+                Ok(resp) => return failed(String::new(), api_write_error(action, resp.status())),
+                Err(e) => return failed(String::new(), format!("request error: {}", e)),
             }
         }
+        let result = if action == "copy" {
+            state.store.copy_profile(k, &profile, to_k, to, Origin::new("web"))
+        } else {
+            state.store.rename_profile(k, &profile, to_k, to, version.as_deref(), Origin::new("web"))
+        };
+        return match result {
+            Ok(name) => Redirect::to(&format!("/profiles/{}/{}", to_kind, segment(&name))).into_response(),
+            Err(e) => failed(String::new(), write_error(&e)),
+        };
     }
     if let Some(json) = form.json {
         if let Some(url) = api_url.as_deref() {
-            let resp = reqwest::Client::new()
-                .put(url)
+            let resp = with_version(reqwest::Client::new().put(url))
                 .header("content-type", "application/json")
                 .body(json.clone())
                 .send()
                 .await;
             match resp {
-                Ok(resp) if resp.status().is_success() => Redirect::to(&format!("/profiles/{}/{}", kind_str, profile)).into_response(),
-                Ok(resp) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json, error: Some(format!("update failed: {}", resp.status())) }.into_response(),
-                Err(e) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json, error: Some(format!("request error: {}", e)) }.into_response(),
+                Ok(resp) if resp.status().is_success() => Redirect::to(&format!("/profiles/{}/{}", kind_str, segment(&profile))).into_response(),
+                Ok(resp) => failed(json, api_write_error("update", resp.status())),
+                Err(e) => failed(json, format!("request error: {}", e)),
             }
         } else {
             match serde_json::from_str::<crate::model::Profile>(&json) {
-                Ok(p) => match state.store.write_profile(k, &profile, &p, true) {
-                    Ok(_) => Redirect::to(&format!("/profiles/{}/{}", kind_str, profile)).into_response(),
-                    Err(e) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json, error: Some(format!("{}", e)) }.into_response(),
+                Ok(p) => match state.store.write_profile_if(k, &profile, &p, true, Origin::new("web"), version.as_deref()) {
+                    Ok(_) => Redirect::to(&format!("/profiles/{}/{}", kind_str, segment(&profile))).into_response(),
+                    Err(e) => failed(json, write_error(&e)),
                 },
-                Err(e) => ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json, error: Some(format!("invalid json: {}", e)) }.into_response(),
+                Err(e) => failed(json, format!("invalid json: {}", e)),
             }
         }
     } else {
-        ProfileTemplate { title: "Profile", kind: kind_str, name: profile, json: String::new(), error: Some("missing json".into()) }.into_response()
+        failed(String::new(), "missing json".into())
     }
 }
 
@@ -131,7 +295,6 @@ struct ArmTemplate {
     kind: String,
     name: String,
     label_prefix: String,
-    selected: Option<u8>,
     has_selection: bool,
     joint_name: Option<String>,
     has_joint: bool,
@@ -147,7 +310,17 @@ struct ArmTemplate {
     hotspots: Vec<Hotspot>,
     robots_btns: Vec<(String, bool)>,
     leaders_btns: Vec<(String, bool)>,
+    /// Registry type the hotspots and knobs are built from, with its description.
+    robot_type: String,
+    /// Encoder ticks per turn of the type's motors.
+    resolution: i32,
+    /// Store version the edit form was loaded at.
+    version: String,
     read_only: bool,
+    /// Fields of the selected joint and of the profile this tool does not
+    /// know; shown read-only, kept on save.
+    joint_extra: Vec<(String, String)>,
+    profile_extra: Vec<(String, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -158,7 +331,10 @@ struct ArmQuery { sel: Option<u8> }
 
 async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<ArmQuery>) -> Response {
     let label_prefix = if kind == "teleoperators" { "L" } else { "F" }.to_string();
-    let sel = q.sel.filter(|v| (1..=6).contains(v));
+    let k = match kind.as_str() { "robots" => Kind::Robots, "teleoperators" => Kind::Teleoperators, _ => Kind::Robots };
+    let canonical = state.store.canonical_name(k, &profile).unwrap_or_else(|_| profile.clone());
+    let ty = registry::type_of(k, &canonical).unwrap_or(registry::default_for(k));
+    let sel = q.sel.filter(|v| ty.joints.iter().any(|j| j.id == *v));
 
     let robots: Vec<String> = state
         .store
@@ -170,15 +346,14 @@ async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(St
         .list_profiles(Kind::Teleoperators)
         .map(|v| v.into_iter().map(|m| m.name).collect())
         .unwrap_or_default();
-    let robots_btns: Vec<(String, bool)> = robots.iter().cloned().map(|n| (n.clone(), kind == "robots" && profile == n)).collect();
-    let leaders_btns: Vec<(String, bool)> = leaders.iter().cloned().map(|n| (n.clone(), kind == "teleoperators" && profile == n)).collect();
+    let robots_btns: Vec<(String, bool)> = robots.iter().map(|n| (n.clone(), kind == "robots" && &profile == n)).collect();
+    let leaders_btns: Vec<(String, bool)> = leaders.iter().map(|n| (n.clone(), kind == "teleoperators" && &profile == n)).collect();
 
     let mut tpl = ArmTemplate {
         title: format!("Arm - {} / {}", &kind, &profile),
         kind: kind.clone(),
         name: profile.clone(),
         label_prefix,
-        selected: sel,
         has_selection: sel.is_some(),
         joint_name: None,
         has_joint: false,
@@ -194,7 +369,12 @@ async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(St
         hotspots: Vec::new(),
         robots_btns,
         leaders_btns,
+        robot_type: format!("{} ({})", ty.name, ty.description),
+        resolution: ty.resolution as i32,
+        version: String::new(),
         read_only: state.read_only,
+        joint_extra: Vec::new(),
+        profile_extra: Vec::new(),
     };
 
     let coords: [(u8, u8); 6] = match kind.as_str() {
@@ -202,21 +382,24 @@ async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(St
         _ => [(83,36), (58,31), (20,45), (19,28), (15,16), (30,9)],
     };
 
-    let k = match kind.as_str() { "robots" => Kind::Robots, "teleoperators" => Kind::Teleoperators, _ => Kind::Robots };
     let mut id_to_name: std::collections::HashMap<i32, String> = Default::default();
     if let Ok(p) = state.store.read_profile(k, &profile) {
         for (name, j) in p.0.iter() {
             id_to_name.insert(j.id, name.clone());
         }
+        tpl.profile_extra = p.1.iter().map(|(k, v)| (k.clone(), v.to_string())).collect();
     }
-    for (i, (t, l)) in coords.iter().enumerate() {
-        let n = (i as u8)+1;
-        let label = id_to_name.get(&(n as i32)).cloned().unwrap_or_else(|| format!("{}{}", tpl.label_prefix, n));
-        tpl.hotspots.push(Hotspot { n, top: *t, left: *l, selected: sel == Some(n), label });
+    for (i, spec) in ty.joints.iter().enumerate() {
+        // joints past the arm's six (LeKiwi wheels) line up along the bottom edge
+        let (t, l) = coords.get(i).copied().unwrap_or((94, 10 + 12 * (i as u8 - 6)));
+        let n = spec.id;
+        let label = id_to_name.get(&(n as i32)).cloned().unwrap_or_else(|| spec.name.to_string());
+        tpl.hotspots.push(Hotspot { n, top: t, left: l, selected: sel == Some(n), label });
     }
     if let Some(s) = sel {
-        match state.store.read_profile(k, &profile) {
-            Ok(p) => {
+        match state.store.read_profile_versioned(k, &profile) {
+            Ok((p, version)) => {
+                tpl.version = version;
                 for (name, j) in p.0.iter() {
                     if j.id == s as i32 {
                         tpl.joint_name = Some(name.clone());
@@ -227,6 +410,7 @@ async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(St
                         tpl.homing_offset_v = j.homing_offset;
                         tpl.range_min_v = j.range_min;
                         tpl.range_max_v = j.range_max;
+                        tpl.joint_extra = j.extra.iter().map(|(k, v)| (k.clone(), v.to_string())).collect();
                         break;
                     }
                 }
@@ -242,12 +426,13 @@ async fn view_arm(State(state): State<AppState>, Path((kind, profile)): Path<(St
 
 #[derive(Deserialize)]
 struct ArmUpdateForm {
-    action: Option<String>,
     id: u8,
     drive_mode: i32,
     homing_offset: i32,
     range_min: i32,
     range_max: i32,
+    #[serde(default)]
+    version: Option<String>,
 }
 
 async fn update_arm(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Form(form): Form<ArmUpdateForm>) -> Response {
@@ -255,14 +440,14 @@ async fn update_arm(State(state): State<AppState>, Path((kind, profile)): Path<(
     let k = match kind.as_str() { "robots" => Kind::Robots, "teleoperators" => Kind::Teleoperators, _ => Kind::Robots };
     let prof = match state.store.read_profile(k, &profile) {
         Ok(p) => p,
-        Err(e) => return <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), selected: None, has_selection: false, joint_name: None, has_joint: false, joint_label: String::new(), selected_n: 0, id_v: 0, drive_mode_v: 0, homing_offset_v: 0, range_min_v: 0, range_max_v: 0, error: Some(format!("{}", e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], read_only: state.read_only }),
+        Err(e) => return <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), has_selection: false, joint_name: None, has_joint: false, joint_label: String::new(), selected_n: 0, id_v: 0, drive_mode_v: 0, homing_offset_v: 0, range_min_v: 0, range_max_v: 0, error: Some(format!("{}", e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], robot_type: String::new(), resolution: 4096, version: form.version.clone().unwrap_or_default(), read_only: state.read_only, joint_extra: vec![], profile_extra: vec![] }),
     };
     let mut joint_name: Option<String> = None;
     for (name, j) in prof.0.iter() {
         if j.id == form.id as i32 { joint_name = Some(name.clone()); break; }
     }
     let Some(jname) = joint_name else {
-        return <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), selected: None, has_selection: false, joint_name: None, has_joint: false, joint_label: String::new(), selected_n: 0, id_v: 0, drive_mode_v: 0, homing_offset_v: 0, range_min_v: 0, range_max_v: 0, error: Some("invalid id".into()), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], read_only: state.read_only });
+        return <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), has_selection: false, joint_name: None, has_joint: false, joint_label: String::new(), selected_n: 0, id_v: 0, drive_mode_v: 0, homing_offset_v: 0, range_min_v: 0, range_max_v: 0, error: Some("invalid id".into()), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], robot_type: String::new(), resolution: 4096, version: form.version.clone().unwrap_or_default(), read_only: state.read_only, joint_extra: vec![], profile_extra: vec![] });
     };
 
     let jname_key = jname.clone();
@@ -277,11 +462,15 @@ async fn update_arm(State(state): State<AppState>, Path((kind, profile)): Path<(
     });
 
     if let Some(base) = state.base_url.as_deref() {
-        let url = format!("{}/api/profiles/{}/{}", base, kind_str, profile);
-        match reqwest::Client::new().patch(url).json(&body).send().await {
-            Ok(resp) if resp.status().is_success() => Redirect::to(&format!("/arm/{}/{}?sel={}", kind_str, profile, form.id)).into_response(),
-            Ok(resp) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), selected: Some(form.id), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname.clone(), selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(format!("update failed: {}", resp.status())), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], read_only: state.read_only }),
-            Err(e) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), selected: Some(form.id), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname.clone(), selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(format!("request error: {}", e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], read_only: state.read_only }),
+        let url = format!("{}/api/profiles/{}/{}", base, kind_str, segment(&profile));
+        let mut req = reqwest::Client::new().patch(url).json(&body);
+        if let Some(v) = form.version.as_deref().filter(|v| !v.is_empty()) {
+            req = req.header("if-match", format!("\"{}\"", v));
+        }
+        match req.send().await {
+            Ok(resp) if resp.status().is_success() => Redirect::to(&format!("/arm/{}/{}?sel={}", kind_str, segment(&profile), form.id)).into_response(),
+            Ok(resp) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname.clone(), selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(api_write_error("update", resp.status())), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], robot_type: String::new(), resolution: 4096, version: form.version.clone().unwrap_or_default(), read_only: state.read_only, joint_extra: vec![], profile_extra: vec![] }),
+            Err(e) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname.clone(), selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(format!("request error: {}", e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], robot_type: String::new(), resolution: 4096, version: form.version.clone().unwrap_or_default(), read_only: state.read_only, joint_extra: vec![], profile_extra: vec![] }),
         }
     } else {
         let mut p = prof;
@@ -292,11 +481,461 @@ async fn update_arm(State(state): State<AppState>, Path((kind, profile)): Path<(
             j.range_min = form.range_min;
             j.range_max = form.range_max;
         }
-        match state.store.write_profile(k, &profile, &p, true) {
-            Ok(_) => Redirect::to(&format!("/arm/{}/{}?sel={}", kind_str, profile, form.id)).into_response(),
-            Err(e) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), selected: Some(form.id), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname, selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(format!("{}", e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], read_only: state.read_only }),
+        match state.store.write_profile_if(k, &profile, &p, true, Origin::new("web"), form.version.as_deref().filter(|v| !v.is_empty())) {
+            Ok(_) => Redirect::to(&format!("/arm/{}/{}?sel={}", kind_str, segment(&profile), form.id)).into_response(),
+            Err(e) => <ArmTemplate as askama_axum::IntoResponse>::into_response(ArmTemplate { title: "Arm".into(), kind: kind_str, name: profile, label_prefix: String::new(), has_selection: true, joint_name: Some(jname.clone()), has_joint: true, joint_label: jname, selected_n: form.id, id_v: form.id as i32, drive_mode_v: form.drive_mode, homing_offset_v: form.homing_offset, range_min_v: form.range_min, range_max_v: form.range_max, error: Some(write_error(&e)), message: None, hotspots: vec![], robots_btns: vec![], leaders_btns: vec![], robot_type: String::new(), resolution: 4096, version: form.version.clone().unwrap_or_default(), read_only: state.read_only, joint_extra: vec![], profile_extra: vec![] }),
+        }
+    }
+}
+
+// ---------------- Homing wizard ----------------
+
+#[derive(Template)]
+#[template(path = "homing.html")]
+struct HomingTemplate {
+    title: String,
+    kind: String,
+    name: String,
+    joints: Vec<HomingEntry>,
+    /// Profile version the preview was computed against.
+    version: String,
+    /// `joints` as JSON, posted back on apply so exactly these are saved.
+    preview: String,
+    applied: bool,
+    error: Option<String>,
+    has_bus: bool,
+    read_only: bool,
+}
+
+impl HomingTemplate {
+    fn new(state: &AppState, kind: &str, name: &str) -> Self {
+        Self {
+            title: format!("Homing - {} / {}", kind, name),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            joints: Vec::new(),
+            version: String::new(),
+            preview: String::new(),
+            applied: false,
+            error: None,
+            has_bus: state.bus.is_some(),
+            read_only: state.read_only,
+        }
+    }
+}
+
+async fn view_homing(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Response {
+    <HomingTemplate as askama_axum::IntoResponse>::into_response(HomingTemplate::new(&state, &kind, &profile))
+}
+
+#[derive(Deserialize)]
+struct HomingForm {
+    action: String,
+    /// Version shown with the preview being applied.
+    #[serde(default)]
+    version: Option<String>,
+    /// The previewed entries, as JSON.
+    #[serde(default)]
+    preview: Option<String>,
+}
+
+async fn update_homing(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Form(form): Form<HomingForm>) -> Response {
+    let mut tpl = HomingTemplate::new(&state, &kind, &profile);
+    let k = match kind.as_str() {
+        "robots" => Kind::Robots,
+        "teleoperators" => Kind::Teleoperators,
+        _ => {
+            tpl.error = Some("invalid kind".into());
+            return <HomingTemplate as askama_axum::IntoResponse>::into_response(tpl);
+        }
+    };
+    let Some(bus) = state.bus.clone() else {
+        return <HomingTemplate as askama_axum::IntoResponse>::into_response(tpl);
+    };
+    let (current, version) = match state.store.read_profile_versioned(k, &profile) {
+        Ok(p) => p,
+        Err(e) => {
+            tpl.error = Some(format!("{}", e));
+            return <HomingTemplate as askama_axum::IntoResponse>::into_response(tpl);
+        }
+    };
+    tpl.version = version;
+    match crate::bus::run(&bus, move |b| homing::preview(b, &current)).await {
+        Ok(joints) => tpl.joints = joints,
+        Err(e) => tpl.error = Some(format!("{}", e)),
+    }
+    if form.action == "apply" && tpl.error.is_none() && !state.read_only {
+        let previewed: Option<Vec<HomingEntry>> = form.preview.as_deref().and_then(|p| serde_json::from_str(p).ok());
+        let expected = form.version.filter(|v| !v.is_empty()).unwrap_or_else(|| tpl.version.clone());
+        match previewed {
+            None => tpl.error = Some("read the pose before applying".into()),
+            Some(previewed) => {
+                let drift = homing::drift(&previewed, &tpl.joints, homing::POSE_TOLERANCE);
+                if !drift.is_empty() {
+                    let names: Vec<&str> = drift.iter().map(|d| d.name.as_str()).collect();
+                    // the table now shows the new pose; applying again saves that
+                    tpl.error = Some(format!("the arm moved since the preview ({}); review the new offsets and apply again", names.join(", ")));
+                } else {
+                    match homing::apply(&state.store, k, &profile, &previewed, &expected) {
+                        Ok((_, version)) => {
+                            tpl.applied = true;
+                            tpl.version = version;
+                            tpl.joints = previewed;
+                        }
+                        Err(e) => tpl.error = Some(write_error(&e)),
+                    }
+                }
+            }
+        }
+    }
+    tpl.preview = serde_json::to_string(&tpl.joints).unwrap_or_default();
+    <HomingTemplate as askama_axum::IntoResponse>::into_response(tpl)
+}
+
+// ---------------- Servo ids ----------------
+
+#[derive(Template)]
+#[template(path = "ids.html")]
+struct IdsTemplate {
+    title: String,
+    kind: String,
+    name: String,
+    /// Joint names and the ids the profile expects, by id.
+    joints: Vec<(String, i32)>,
+    devices: Vec<Device>,
+    scanned: bool,
+    assigned: Option<Assignment>,
+    error: Option<String>,
+    has_bus: bool,
+    read_only: bool,
+}
+
+impl IdsTemplate {
+    fn new(state: &AppState, kind: &str, name: &str) -> Self {
+        let mut joints = Vec::new();
+        let mut error = None;
+        let k = match kind {
+            "robots" => Some(Kind::Robots),
+            "teleoperators" => Some(Kind::Teleoperators),
+            _ => None,
+        };
+        match k.map(|k| state.store.read_profile(k, name)) {
+            Some(Ok(p)) => {
+                joints = p.0.into_iter().map(|(n, j)| (n, j.id)).collect();
+                joints.sort_by_key(|(_, id)| *id);
+            }
+            Some(Err(e)) => error = Some(format!("{}", e)),
+            None => error = Some("invalid kind".into()),
+        }
+        Self {
+            title: format!("Servo ids - {} / {}", kind, name),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            joints,
+            devices: Vec::new(),
+            scanned: false,
+            assigned: None,
+            error,
+            has_bus: state.bus.is_some(),
+            read_only: state.read_only,
+        }
+    }
+}
+
+async fn view_ids(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>) -> Response {
+    <IdsTemplate as askama_axum::IntoResponse>::into_response(IdsTemplate::new(&state, &kind, &profile))
+}
+
+#[derive(Deserialize)]
+struct IdsForm {
+    action: String,
+    #[serde(default)]
+    joint: String,
+}
+
+async fn update_ids(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Form(form): Form<IdsForm>) -> Response {
+    let mut tpl = IdsTemplate::new(&state, &kind, &profile);
+    let Some(bus) = state.bus.clone() else {
+        return <IdsTemplate as askama_axum::IntoResponse>::into_response(tpl);
+    };
+    if tpl.error.is_some() {
+        return <IdsTemplate as askama_axum::IntoResponse>::into_response(tpl);
+    }
+    if form.action == "assign" && !state.read_only {
+        let k = if kind == "robots" { Kind::Robots } else { Kind::Teleoperators };
+        let current = match state.store.read_profile(k, &profile) {
+            Ok(p) => p,
+            Err(e) => {
+                tpl.error = Some(format!("{}", e));
+                return <IdsTemplate as askama_axum::IntoResponse>::into_response(tpl);
+            }
+        };
+        let joint = form.joint.clone();
+        match crate::bus::run(&bus, move |b| Ok(ids::assign(b, &current, &joint, &[]))).await {
+            Ok(Ok(a)) => tpl.assigned = Some(a),
+            Ok(Err(e)) => tpl.error = Some(format!("{}", e)),
+            Err(e) => tpl.error = Some(format!("{}", e)),
+        }
+    }
+    match crate::bus::run(&bus, |b| ids::scan(b, &[])).await {
+        Ok(devices) => {
+            tpl.devices = devices;
+            tpl.scanned = true;
+        }
+        Err(e) => tpl.error = tpl.error.take().or(Some(format!("{}", e))),
+    }
+    <IdsTemplate as askama_axum::IntoResponse>::into_response(tpl)
+}
+
+// ---------------- History ----------------
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate {
+    title: String,
+    kind: String,
+    name: String,
+    /// Newest first.
+    revisions: Vec<RevisionMeta>,
+    selected: Option<Revision>,
+    json: String,
+    /// Revision the selected one is compared against.
+    against: Option<u64>,
+    changes: Vec<Change>,
+    message: Option<String>,
+    error: Option<String>,
+    read_only: bool,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    rev: Option<u64>,
+    against: Option<u64>,
+}
+
+impl HistoryTemplate {
+    fn new(state: &AppState, kind: &str, name: &str, q: &HistoryQuery) -> Self {
+        let mut tpl = Self {
+            title: format!("History - {} / {}", kind, name),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            revisions: Vec::new(),
+            selected: None,
+            json: String::new(),
+            against: None,
+            changes: Vec::new(),
+            message: None,
+            error: None,
+            read_only: state.read_only,
+        };
+        let k = match kind {
+            "robots" => Kind::Robots,
+            "teleoperators" => Kind::Teleoperators,
+            _ => {
+                tpl.error = Some("invalid kind".into());
+                return tpl;
+            }
+        };
+        match state.store.history(k, name) {
+            Ok(mut revs) => {
+                revs.reverse();
+                tpl.revisions = revs;
+            }
+            Err(e) => {
+                tpl.error = Some(format!("{}", e));
+                return tpl;
+            }
+        }
+        let Some(rev) = q.rev.or_else(|| tpl.revisions.first().map(|m| m.revision)) else {
+            return tpl;
+        };
+        let selected = match state.store.revision(k, name, rev) {
+            Ok(r) => r,
+            Err(e) => {
+                tpl.error = Some(format!("{}", e));
+                return tpl;
+            }
+        };
+        // default to the revision just before the selected one
+        tpl.against = q.against.or_else(|| tpl.revisions.iter().map(|m| m.revision).find(|&n| n < rev));
+        if let Some(against) = tpl.against {
+            match state.store.revision(k, name, against) {
+                Ok(old) => tpl.changes = model::diff(&old.profile, &selected.profile),
+                Err(e) => tpl.error = Some(format!("{}", e)),
+            }
+        }
+        tpl.json = serde_json::to_string_pretty(&selected.profile).unwrap_or_default();
+        tpl.selected = Some(selected);
+        tpl
+    }
+}
+
+async fn view_history(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Query(q): Query<HistoryQuery>) -> Response {
+    <HistoryTemplate as askama_axum::IntoResponse>::into_response(HistoryTemplate::new(&state, &kind, &profile, &q))
+}
+
+#[derive(Deserialize)]
+struct RollbackForm {
+    rev: u64,
+}
+
+async fn update_history(State(state): State<AppState>, Path((kind, profile)): Path<(String, String)>, Form(form): Form<RollbackForm>) -> Response {
+    let k = match kind.as_str() {
+        "robots" => Kind::Robots,
+        "teleoperators" => Kind::Teleoperators,
+        _ => return view_history(State(state), Path((kind, profile)), Query(HistoryQuery { rev: None, against: None })).await,
+    };
+    let result = if state.read_only { Err("read-only mode".to_string()) } else { state.store.rollback(k, &profile, form.rev, Origin::new("web")).map_err(|e| e.to_string()) };
+    let mut tpl = HistoryTemplate::new(&state, &kind, &profile, &HistoryQuery { rev: None, against: None });
+    match result {
+        Ok(meta) => tpl.message = Some(format!("Rolled back to revision {} (saved as revision {}).", form.rev, meta.revision)),
+        Err(e) => tpl.error = Some(e),
+    }
+    <HistoryTemplate as askama_axum::IntoResponse>::into_response(tpl)
+}
+
+// ---------------- Trash ----------------
+
+#[derive(Template)]
+#[template(path = "trash.html")]
+struct TrashTemplate {
+    title: &'static str,
+    /// Newest first.
+    entries: Vec<TrashEntry>,
+    message: Option<String>,
+    error: Option<String>,
+    read_only: bool,
+}
+
+impl TrashTemplate {
+    fn new(state: &AppState, message: Option<String>, error: Option<String>) -> Self {
+        let (entries, error) = match state.store.trash() {
+            Ok(entries) => (entries, error),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        Self { title: "Trash", entries, message, error, read_only: state.read_only }
+    }
+}
+
+async fn view_trash(State(state): State<AppState>) -> Response {
+    <TrashTemplate as askama_axum::IntoResponse>::into_response(TrashTemplate::new(&state, None, None))
+}
+
+#[derive(Deserialize)]
+struct TrashForm {
+    id: String,
+    action: String,
+}
+
+async fn update_trash(State(state): State<AppState>, Form(form): Form<TrashForm>) -> Response {
+    if state.read_only {
+        return <TrashTemplate as askama_axum::IntoResponse>::into_response(TrashTemplate::new(&state, None, Some("read-only mode".into())));
+    }
+    let tpl = match form.action.as_str() {
+        "restore" => match state.store.restore(&form.id, None) {
+            Ok((kind, name)) => return Redirect::to(&format!("/profiles/{}/{}", kind.as_str(), segment(&name))).into_response(),
+            Err(StoreError::Exists(what)) => TrashTemplate::new(&state, None, Some(format!("{} already exists; rename or delete it first, or restore through the API under another name", what))),
+            Err(e) => TrashTemplate::new(&state, None, Some(e.to_string())),
+        },
+        "purge" => match state.store.purge(&form.id) {
+            Ok(()) => TrashTemplate::new(&state, Some("Permanently deleted.".into()), None),
+            Err(e) => TrashTemplate::new(&state, None, Some(e.to_string())),
+        },
+        _ => TrashTemplate::new(&state, None, Some("unknown action".into())),
+    };
+    <TrashTemplate as askama_axum::IntoResponse>::into_response(tpl)
+}
+
+// ---------------- Diff ----------------
+
+#[derive(Template)]
+#[template(path = "diff.html")]
+struct DiffTemplate {
+    title: String,
+    left: String,
+    right: String,
+    /// Every `<kind>/<name>`, for the pickers.
+    refs: Vec<String>,
+    fields: Vec<&'static str>,
+    rows: Vec<DiffRow>,
+    /// Unknown fields that differ: `joint.key` (or a top-level key), left, right.
+    extra: Vec<(String, String, String)>,
+    error: Option<String>,
+}
+
+struct DiffRow {
+    joint: String,
+    /// `same`, `changed`, `added` or `removed`; also the row's CSS class.
+    status: &'static str,
+    cells: Vec<DiffCell>,
+}
+
+struct DiffCell {
+    left: String,
+    right: String,
+    /// Signed `right - left`, empty when unchanged or one side is missing.
+    delta: String,
+    changed: bool,
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    #[serde(default)]
+    left: String,
+    #[serde(default)]
+    right: String,
+}
+
+async fn view_diff(State(state): State<AppState>, Query(q): Query<DiffQuery>) -> Response {
+    let refs = [Kind::Robots, Kind::Teleoperators]
+        .into_iter()
+        .flat_map(|k| state.store.list_profiles(k).unwrap_or_default().into_iter().map(move |m| format!("{}/{}", k.as_str(), m.name)))
+        .collect();
+    let mut tpl = DiffTemplate { title: "Compare profiles".into(), left: q.left, right: q.right, refs, fields: Vec::new(), rows: Vec::new(), extra: Vec::new(), error: None };
+    if !tpl.left.is_empty() && !tpl.right.is_empty() {
+        let read = |r: &str| -> Result<model::Profile, String> {
+            let (kind, name, rev) = parse_revision_ref(r).ok_or_else(|| format!("invalid reference `{}` (expected <kind>/<name>[@<revision>])", r))?;
+            state.store.read_at(kind, &name, rev).map_err(|e| e.to_string())
+        };
+        match read(&tpl.left).and_then(|a| Ok((a, read(&tpl.right)?))) {
+            Ok((a, b)) => {
+                let joints = model::compare(&a, &b);
+                let show = |v: Option<serde_json::Value>| v.map(|v| v.to_string()).unwrap_or_default();
+                tpl.extra = joints
+                    .iter()
+                    .flat_map(|j| j.extra.iter().map(move |e| (format!("{}.{}", j.joint, e.key), e)))
+                    .map(|(k, e)| (k, e.left.clone(), e.right.clone()))
+                    .chain(model::compare_extra(&a.1, &b.1).into_iter().map(|e| (e.key, e.left, e.right)))
+                    .map(|(k, l, r)| (k, show(l), show(r)))
+                    .collect();
+                tpl.fields = Joint::FIELDS.to_vec();
+                tpl.rows = joints
+                    .into_iter()
+                    .map(|j| DiffRow {
+                        status: match j.status {
+                            JointStatus::Same => "same",
+                            JointStatus::Changed => "changed",
+                            JointStatus::Added => "added",
+                            JointStatus::Removed => "removed",
+                        },
+                        cells: j
+                            .fields
+                            .iter()
+                            .map(|f| DiffCell {
+                                left: f.left.map(|v| v.to_string()).unwrap_or_default(),
+                                right: f.right.map(|v| v.to_string()).unwrap_or_default(),
+                                delta: f.delta.filter(|d| *d != 0).map(|d| format!("{:+}", d)).unwrap_or_default(),
+                                changed: f.changed(),
+                            })
+                            .collect(),
+                        joint: j.joint,
+                    })
+                    .collect();
+            }
+            Err(e) => tpl.error = Some(e),
         }
     }
+    <DiffTemplate as askama_axum::IntoResponse>::into_response(tpl)
 }
 
 async fn arm_image() -> Response {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::sync::Arc;
 
 use axum::body::Body;
@@ -9,16 +9,51 @@ use tower::util::ServiceExt;
 use axum::body;
 
 use lerobot_servo_adjust::api::{self, AppState};
-use lerobot_servo_adjust::model::{Joint, Profile};
-use lerobot_servo_adjust::store::Store;
+use lerobot_servo_adjust::bus::{self, feetech::FeetechBus, sim::{SimBus, SimHandle, SimServo}, Register};
+use lerobot_servo_adjust::model::{registry, Joint, Profile};
+use lerobot_servo_adjust::store::{Kind, Store};
 
-fn build_app(tmp: &tempfile::TempDir) -> Router {
+fn build_state(tmp: &tempfile::TempDir) -> AppState {
     let root = tmp.path().to_path_buf();
     std::fs::create_dir_all(root.join("robots")).unwrap();
     std::fs::create_dir_all(root.join("teleoperators")).unwrap();
     let store = Arc::new(Store::new(root));
-    let state = AppState { store, base_url: None, read_only: false };
-    Router::new().merge(api::router(state))
+    AppState { store, base_url: None, read_only: false, bus: None, recorder: Default::default() }
+}
+
+fn build_app(tmp: &tempfile::TempDir) -> Router {
+    Router::new().merge(api::router(build_state(tmp)))
+}
+
+/// App backed by a simulated bus with servos 1..=n, plus a profile `arm`
+/// whose joints `j1..jn` map to those ids.
+fn build_app_with_sim(tmp: &tempfile::TempDir, n: u8) -> (Router, SimHandle, AppState) {
+    let sim = SimBus::feetech((1..=n).map(SimServo::sts3215).collect());
+    let handle = sim.handle();
+    let mut state = build_state(tmp);
+    state.bus = Some(bus::shared(Box::new(FeetechBus::new(sim))));
+    let profile = Profile::new(
+        (1..=n)
+            .map(|i| (format!("j{}", i), Joint { id: i as i32, drive_mode: 0, homing_offset: 0, range_min: 0, range_max: 4095, ..Default::default() }))
+            .collect(),
+    );
+    state.store.write_profile(Kind::Robots, "arm", &profile, false).unwrap();
+    (Router::new().merge(api::router(state.clone())), handle, state)
+}
+
+async fn send(app: &Router, method: &str, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+    let mut req = Request::builder().method(method).uri(uri);
+    let body = match body {
+        Some(v) => {
+            req = req.header("content-type", "application/json");
+            Body::from(serde_json::to_vec(&v).unwrap())
+        }
+        None => Body::empty(),
+    };
+    let res = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+    let status = res.status();
+    let bytes = body::to_bytes(res.into_body(), 1024 * 1024).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
 }
 
 #[tokio::test]
@@ -38,12 +73,12 @@ async fn api_crud_profile() {
     assert_eq!(v["items"].as_array().unwrap().len(), 0);
 
     // create
-    let mut map = HashMap::new();
+    let mut map = IndexMap::new();
     map.insert(
         "j1".to_string(),
-        Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1, range_max: 10 },
+        Joint { id: 1, drive_mode: 0, homing_offset: 0, range_min: 1, range_max: 10, ..Default::default() },
     );
-    let profile = Profile(map);
+    let profile = Profile::new(map);
     let res = app
         .clone()
         .oneshot(
@@ -93,12 +128,12 @@ async fn api_crud_profile() {
     assert_eq!(res.status(), StatusCode::NO_CONTENT);
 
     // put p1
-    let mut map2 = HashMap::new();
+    let mut map2 = IndexMap::new();
     map2.insert(
         "j1".to_string(),
-        Joint { id: 2, drive_mode: 1, homing_offset: 5, range_min: 2, range_max: 30 },
+        Joint { id: 2, drive_mode: 1, homing_offset: 5, range_min: 2, range_max: 30, ..Default::default() },
     );
-    let profile2 = Profile(map2);
+    let profile2 = Profile::new(map2);
     let res = app
         .clone()
         .oneshot(
@@ -127,3 +162,780 @@ async fn api_crud_profile() {
         .unwrap();
     assert_eq!(res.status(), StatusCode::NO_CONTENT);
 }
+
+#[tokio::test]
+async fn api_range_recording() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, sim, state) = build_app_with_sim(&tmp, 2);
+
+    // without a bus the endpoint refuses
+    let (status, _) = send(&build_app(&tmp), "POST", "/api/recordings/robots/arm/start", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    sim.set_raw_position(1, 1500);
+    sim.set_raw_position(2, 2500);
+    let (status, _) = send(&app, "POST", "/api/recordings/robots/arm/start", Some(json!({"interval_ms": 5}))).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let (status, _) = send(&app, "POST", "/api/recordings/robots/arm/start", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    for (a, b) in [(900, 2600), (3100, 2400)] {
+        sim.set_raw_position(1, a);
+        sim.set_raw_position(2, b);
+        tokio::time::sleep(std::time::Duration::from_millis(40)).await;
+    }
+    let (status, v) = send(&app, "GET", "/api/recordings/robots/arm", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(v["samples"].as_u64().unwrap() >= 2);
+
+    let (status, v) = send(&app, "POST", "/api/recordings/robots/arm/stop", Some(json!({"commit": true}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["committed"].as_array().unwrap().len(), 2);
+
+    let p = state.store.read_profile(Kind::Robots, "arm").unwrap();
+    assert_eq!((p.0["j1"].range_min, p.0["j1"].range_max), (900, 3100));
+    assert_eq!((p.0["j2"].range_min, p.0["j2"].range_max), (2400, 2600));
+
+    let (status, _) = send(&app, "GET", "/api/recordings/robots/arm", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_homing_preview_and_apply() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, sim, state) = build_app_with_sim(&tmp, 2);
+    sim.set_raw_position(1, 139);
+    sim.set_raw_position(2, 2047);
+
+    let (status, v) = send(&app, "POST", "/api/homing/robots/arm/preview", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["joints"][0]["new_offset"], json!(-1908));
+    assert_eq!(v["joints"][1]["delta"], json!(0));
+    // preview does not touch the profile
+    assert_eq!(state.store.read_profile(Kind::Robots, "arm").unwrap().0["j1"].homing_offset, 0);
+
+    let preview = v;
+
+    // a write between preview and apply makes the preview stale
+    let mut p = state.store.read_profile(Kind::Robots, "arm").unwrap();
+    p.0.get_mut("j2").unwrap().range_min = 1;
+    state.store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+    let (status, _) = send(&app, "POST", "/api/homing/robots/arm/apply", Some(preview.clone())).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(state.store.read_profile(Kind::Robots, "arm").unwrap().0["j1"].homing_offset, 0);
+
+    // so does moving the arm: the previewed offsets no longer fit the pose
+    let (_, mut preview) = send(&app, "POST", "/api/homing/robots/arm/preview", None).await;
+    sim.set_raw_position(1, 400);
+    let (status, v) = send(&app, "POST", "/api/homing/robots/arm/apply", Some(preview.clone())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(v["details"]["drift"][0], json!({"name": "j1", "previewed": 139, "current": 400}));
+    assert_eq!(state.store.read_profile(Kind::Robots, "arm").unwrap().0["j1"].homing_offset, 0);
+
+    // small jitter is fine, and the previewed offsets are what gets saved
+    sim.set_raw_position(1, 145);
+    preview.as_object_mut().unwrap().remove("version");
+    let (status, v) = send(&app, "POST", "/api/homing/robots/arm/apply", Some(preview)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state.store.version(Kind::Robots, "arm").unwrap().as_deref(), v["version"].as_str());
+    let p = state.store.read_profile(Kind::Robots, "arm").unwrap();
+    assert_eq!(p.0["j1"].homing_offset, -1908);
+    assert_eq!(p.0["j2"].homing_offset, 0);
+
+    let (status, _) = send(&app, "POST", "/api/homing/robots/missing/preview", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_hardware_apply() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, sim, state) = build_app_with_sim(&tmp, 2);
+    let mut p = state.store.read_profile(Kind::Robots, "arm").unwrap();
+    p.0.get_mut("j2").unwrap().homing_offset = -1908;
+    state.store.write_profile(Kind::Robots, "arm", &p, false).unwrap();
+
+    let (status, v) = send(&app, "POST", "/api/hardware/robots/arm/apply?dry_run=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["writes"].as_array().unwrap().len(), 10);
+    assert_eq!(sim.register(2, Register::HomingOffset), 0);
+
+    let (status, v) = send(&app, "POST", "/api/hardware/robots/arm/apply", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(v["joints"].as_array().unwrap().iter().all(|j| j["ok"] == json!(true)));
+    assert_eq!(sim.register(2, Register::HomingOffset), -1908);
+}
+
+#[tokio::test]
+async fn api_hardware_readback() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, sim, state) = build_app_with_sim(&tmp, 3);
+    sim.set_register(2, Register::Lock, 0);
+    sim.set_register(2, Register::HomingOffset, -1880);
+
+    let (status, _) = send(&app, "POST", "/api/hardware/robots/arm/readback", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, v) = send(&app, "POST", "/api/hardware/robots/recovered/readback", Some(json!({"max_id": 4}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(v["missing"].as_array().unwrap().len(), 3);
+    let p = state.store.read_profile(Kind::Robots, "recovered").unwrap();
+    assert_eq!(p.0["shoulder_lift"].homing_offset, -1880);
+    assert!(!p.0.contains_key("wrist_roll"));
+}
+
+#[tokio::test]
+async fn api_id_scan_and_assign() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, handle, _) = build_app_with_sim(&tmp, 2);
+
+    let (status, v) = send(&app, "POST", "/api/ids/scan", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["devices"].as_array().unwrap().len(), 2);
+    assert_eq!(v["devices"][1]["model"], "sts3215");
+
+    let (status, _) = send(&app, "POST", "/api/ids/robots/arm/assign", Some(json!({"joint": "j2"}))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // plug in a single factory-fresh motor
+    handle.disconnect(1);
+    handle.disconnect(2);
+    handle.connect(SimServo::sts3215(1).with_baud_rate(115_200));
+    let (status, v) = send(&app, "POST", "/api/ids/robots/arm/assign", Some(json!({"joint": "j2"}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", v);
+    assert_eq!((v["id"].as_u64(), v["device"]["baud"].as_u64()), (Some(2), Some(115_200)));
+    assert_eq!(handle.ids(), vec![2]);
+
+    let (status, _) = send(&app, "POST", "/api/ids/robots/arm/assign", Some(json!({"joint": "nope"}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// First server-sent event of `uri`, parsed from its `data:` line.
+async fn first_event(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    use tokio_stream::StreamExt;
+    let res = app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+    let status = res.status();
+    let mut stream = res.into_body().into_data_stream();
+    let mut text = String::new();
+    while !text.contains("\n\n") {
+        let Some(chunk) = stream.next().await else { break };
+        text.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+    }
+    let data = text.lines().find_map(|l| l.strip_prefix("data:")).unwrap_or("null");
+    (status, serde_json::from_str(data.trim()).unwrap())
+}
+
+#[tokio::test]
+async fn api_position_stream() {
+    let tmp = tempfile::tempdir().unwrap();
+    // no hardware: the stream runs from a simulated arm seeded by the profile
+    let state = build_state(&tmp);
+    let mut map = IndexMap::new();
+    map.insert("gripper".to_string(), Joint { id: 6, drive_mode: 0, homing_offset: 0, range_min: 2000, range_max: 3000, ..Default::default() });
+    state.store.write_profile(Kind::Robots, "solo", &Profile::new(map), false).unwrap();
+    let app = Router::new().merge(api::router(state));
+    let (status, v) = first_event(&app, "/api/stream/robots/solo?rate_hz=50").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["source"], "sim");
+    assert_eq!(v["joints"][0]["id"], 6);
+    assert!(v["joints"][0]["in_range"].as_bool().unwrap());
+
+    let (app, handle, _) = build_app_with_sim(&tmp, 2);
+    handle.set_raw_position(2, 4000);
+    let (_, v) = first_event(&app, "/api/stream/robots/arm").await;
+    assert_eq!(v["source"], "bus");
+    assert_eq!(v["joints"][1]["raw"], 4000);
+    assert!((v["joints"][1]["normalized"].as_f64().unwrap() - 95.36).abs() < 0.01);
+
+    let (status, _) = send(&app, "GET", "/api/stream/robots/missing", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_torque_and_goal_control() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (app, handle, state) = build_app_with_sim(&tmp, 2);
+    let mut p = state.store.read_profile(Kind::Robots, "arm").unwrap();
+    p.0.get_mut("j2").unwrap().range_max = 3000;
+    state.store.write_profile(Kind::Robots, "arm", &p, true).unwrap();
+
+    let (status, v) = send(&app, "POST", "/api/control/robots/arm/goal", Some(json!({"id": 2, "position": 3900}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", v);
+    assert_eq!((v["goal"].as_i64(), v["clamped"].as_bool()), (Some(3000), Some(true)));
+    assert_eq!(handle.register(2, Register::PresentPosition), 3000);
+
+    let (status, v) = send(&app, "POST", "/api/control/robots/arm/torque", Some(json!({"id": 1, "enabled": true}))).await;
+    assert_eq!((status, v["joint"].as_str()), (StatusCode::OK, Some("j1")));
+    assert_eq!(handle.register(1, Register::TorqueEnable), 1);
+
+    let (status, _) = send(&app, "POST", "/api/control/robots/arm/torque", Some(json!({"id": 9, "enabled": true}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, "POST", "/api/torque/off", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((handle.register(1, Register::TorqueEnable), handle.register(2, Register::TorqueEnable)), (0, 0));
+
+    let (status, _) = send(&build_app(&tmp), "POST", "/api/torque/off", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn api_history_diff_and_rollback() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let joint = |range_max| json!({"gripper": {"id": 6, "drive_mode": 0, "homing_offset": 0, "range_min": 2000, "range_max": range_max}});
+
+    let (status, _) = send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "arm", "profile": joint(3000)}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/api/profiles/robots/arm")
+        .header("content-type", "application/json")
+        .header("x-author", "ana")
+        .body(Body::from(joint(3500).to_string()))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::NO_CONTENT);
+
+    let (status, v) = send(&app, "GET", "/api/history/robots/arm", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let items = v["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1]["author"], "ana");
+    assert_eq!(items[1]["summary"], "gripper.range_max 3000 -> 3500");
+
+    let (_, v) = send(&app, "GET", "/api/history/robots/arm/1", None).await;
+    assert_eq!(v["profile"]["gripper"]["range_max"], 3000);
+    let (_, v) = send(&app, "GET", "/api/history/robots/arm/diff?from=1", None).await;
+    assert_eq!(v["to"], 2);
+    assert_eq!(v["changes"], json!([{"op": "modified", "joint": "gripper", "field": "range_max", "before": 3000, "after": 3500}]));
+
+    let (status, v) = send(&app, "POST", "/api/history/robots/arm/1/rollback", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["revision"], 3);
+    let (_, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(v["gripper"]["range_max"], 3000);
+    let (status, v) = send(&app, "POST", "/api/history/robots/arm/1/rollback", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(v["message"], "profile is already at revision 1");
+
+    let (status, _) = send(&app, "GET", "/api/history/robots/arm/42", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, "GET", "/api/history/robots/missing", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_diff_profiles_and_revisions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let follower = registry::lookup("so101_follower").unwrap().template();
+    let mut leader = follower.clone();
+    leader.0.get_mut("gripper").unwrap().homing_offset = -40;
+    leader.0.shift_remove("wrist_roll");
+    send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "so101_follower/arm", "profile": follower}))).await;
+    send(&app, "POST", "/api/profiles/teleoperators", Some(json!({"name": "lead", "profile": leader}))).await;
+
+    let (status, v) = send(&app, "GET", "/api/diff?left=robots/arm&right=teleoperators/lead", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((v["changed"].clone(), v["added"].clone(), v["removed"].clone()), (json!(1), json!(0), json!(1)));
+    let joints = v["joints"].as_array().unwrap();
+    assert_eq!(joints.len(), 6);
+    assert_eq!(joints[4]["joint"], "wrist_roll");
+    assert_eq!(joints[4]["status"], "removed");
+    assert_eq!(joints[5]["status"], "changed");
+    assert_eq!(joints[5]["fields"][2], json!({"field": "homing_offset", "left": 0, "right": -40, "delta": -40}));
+
+    // a revision against an uploaded body
+    let mut edited = follower.clone();
+    edited.0.get_mut("shoulder_pan").unwrap().range_min = 100;
+    let (status, v) = send(&app, "POST", "/api/diff", Some(json!({"left": "robots/so101_follower/arm@1", "right": edited}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["right"], "upload");
+    assert_eq!(v["joints"][0]["fields"][3]["delta"], 100);
+
+    let (status, _) = send(&app, "GET", "/api/diff?left=arm&right=teleoperators/lead", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, "GET", "/api/diff?left=robots/arm@9&right=teleoperators/lead", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_copy_rename_and_template() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+
+    let (status, v) = send(&app, "POST", "/api/profiles/teleoperators", Some(json!({"name": "lead", "template": "so101_leader"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(v, json!({"kind": "teleoperators", "name": "so101_leader/lead"}));
+    let (_, v) = send(&app, "GET", "/api/profiles/teleoperators/lead", None).await;
+    assert_eq!(v["wrist_roll"], json!({"id": 5, "drive_mode": 0, "homing_offset": 0, "range_min": 0, "range_max": 4095}));
+    let (status, _) = send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "x", "template": "so101_leader"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // seed a follower from the leader
+    let (status, v) = send(&app, "POST", "/api/profiles/teleoperators/lead/copy", Some(json!({"kind": "robots", "to": "so101_follower/arm"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(v["name"], "so101_follower/arm");
+    let (status, _) = send(&app, "POST", "/api/profiles/teleoperators/lead/copy", Some(json!({"kind": "robots", "to": "arm"}))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, v) = send(&app, "POST", "/api/profiles/robots/arm/rename", Some(json!({"to": "so101_follower/left"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v, json!({"kind": "robots", "name": "so101_follower/left"}));
+    let (status, _) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, v) = send(&app, "GET", "/api/history/robots/left", None).await;
+    let summaries: Vec<&str> = v["items"].as_array().unwrap().iter().map(|m| m["summary"].as_str().unwrap()).collect();
+    assert_eq!(summaries, ["copied from teleoperators/so101_leader/lead", "renamed from robots/so101_follower/arm"]);
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/profiles/robots/left/rename")
+        .header("content-type", "application/json")
+        .header("if-match", "\"0000\"")
+        .body(Body::from(json!({"to": "right"}).to_string()))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(req).await.unwrap().status(), StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn api_rejects_path_traversal_names() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let body = json!({"gripper": {"id": 6, "drive_mode": 0, "homing_offset": 0, "range_min": 2000, "range_max": 3000}});
+    std::fs::write(tmp.path().join("secret.json"), body.to_string()).unwrap();
+
+    for uri in [
+        "/api/profiles/robots/..%2Fsecret",
+        "/api/profiles/robots/..%2F..%2Fetc%2Fpasswd",
+        "/api/profiles/robots/%2Fetc%2Fpasswd",
+        "/api/profiles/robots/..%5Csecret",
+        "/api/profiles/robots/.history",
+        "/api/profiles/robots/my%20arm",
+    ] {
+        let (status, v) = send(&app, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert_eq!(v["message"], "invalid profile name", "{}", uri);
+        let (status, _) = send(&app, "PUT", uri, Some(body.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        let (status, _) = send(&app, "DELETE", uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+    assert!(tmp.path().join("secret.json").exists());
+
+    let (status, v) = send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "../../escape", "profile": body}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(v["details"]["name"], "../../escape");
+    assert!(!tmp.path().parent().unwrap().join("escape.json").exists());
+    let (status, _) = send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "so101_follower/a/b", "profile": body}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "arm", "profile": body}))).await;
+    let (status, _) = send(&app, "POST", "/api/profiles/robots/arm/copy", Some(json!({"to": "../../stolen"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, "POST", "/api/profiles/robots/arm/rename", Some(json!({"kind": "teleoperators", "to": ".hidden"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(tmp.path().join("robots/arm.json").exists());
+}
+
+#[tokio::test]
+async fn api_trash_restore_and_purge() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let body = json!({"gripper": {"id": 6, "drive_mode": 0, "homing_offset": 0, "range_min": 2000, "range_max": 3000}});
+    send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "arm", "profile": body}))).await;
+
+    let (status, _) = send(&app, "DELETE", "/api/profiles/robots/arm", None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, v) = send(&app, "GET", "/api/trash", None).await;
+    let items = v["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!((items[0]["kind"].as_str(), items[0]["name"].as_str()), (Some("robots"), Some("arm")));
+    let id = items[0]["id"].as_str().unwrap().to_string();
+    let (_, v) = send(&app, "GET", &format!("/api/trash/{}", id), None).await;
+    assert_eq!(v["profile"], body);
+
+    let (status, v) = send(&app, "POST", &format!("/api/trash/{}/restore", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v, json!({"kind": "robots", "name": "arm"}));
+    let (_, v) = send(&app, "GET", "/api/history/robots/arm", None).await;
+    assert_eq!(v["items"].as_array().unwrap().len(), 2);
+
+    // restore under another name, then purge
+    send(&app, "DELETE", "/api/profiles/robots/arm", None).await;
+    send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "arm", "profile": body}))).await;
+    let (_, v) = send(&app, "GET", "/api/trash", None).await;
+    let id = v["items"][0]["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, "POST", &format!("/api/trash/{}/restore", id), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, v) = send(&app, "POST", &format!("/api/trash/{}/restore", id), Some(json!({"to": "arm_old"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["name"], "arm_old");
+
+    send(&app, "DELETE", "/api/profiles/robots/arm_old", None).await;
+    let (_, v) = send(&app, "GET", "/api/trash", None).await;
+    let id = v["items"][0]["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, "DELETE", &format!("/api/trash/{}", id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&app, "DELETE", &format!("/api/trash/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, "DELETE", "/api/trash/..%2Frobots", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(tmp.path().join("robots/arm.json").exists());
+
+    send(&app, "DELETE", "/api/profiles/robots/arm", None).await;
+    let (_, v) = send(&app, "DELETE", "/api/trash?older_than_days=1", None).await;
+    assert_eq!(v["purged"].as_array().unwrap().len(), 0);
+    let (status, _) = send(&app, "DELETE", "/api/trash", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, "DELETE", "/api/trash?older_than_days=1&all=true", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, v) = send(&app, "DELETE", "/api/trash?all=true", None).await;
+    assert_eq!(v["purged"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn api_if_match_rejects_stale_writes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let joint = |range_max| json!({"gripper": {"id": 6, "drive_mode": 0, "homing_offset": 0, "range_min": 2000, "range_max": range_max}});
+    send(&app, "POST", "/api/profiles/robots", Some(json!({"name": "arm", "profile": joint(3000)}))).await;
+
+    let get_etag = || async {
+        let res = app.clone().oneshot(Request::builder().uri("/api/profiles/robots/arm").body(Body::empty()).unwrap()).await.unwrap();
+        res.headers()["etag"].to_str().unwrap().to_string()
+    };
+    let request = |method: &str, etag: &str, body: Option<serde_json::Value>| {
+        Request::builder()
+            .method(method)
+            .uri("/api/profiles/robots/arm")
+            .header("content-type", "application/json")
+            .header("if-match", etag)
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap()
+    };
+    let stale = get_etag().await;
+    assert!(stale.starts_with('"') && stale.ends_with('"'));
+
+    // first writer wins and gets the new version back
+    let res = app.clone().oneshot(request("PUT", &stale, Some(joint(3500)))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let fresh = res.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(fresh, get_etag().await);
+    assert_ne!(fresh, stale);
+
+    // the second, holding the old version, is refused on every write method
+    let res = app.clone().oneshot(request("PUT", &stale, Some(joint(3100)))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let patch = json!({"gripper": {"range_max": 3100}});
+    let res = app.clone().oneshot(request("PATCH", &stale, Some(patch.clone()))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let res = app.clone().oneshot(request("DELETE", &stale, None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let (_, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(v["gripper"]["range_max"], 3500);
+
+    let res = app.clone().oneshot(request("PATCH", &fresh, Some(patch))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let res = app.clone().oneshot(request("DELETE", "*", None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn api_profiles_in_robot_type_directories() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let profile = serde_json::to_value(registry::lookup("so101_follower").unwrap().template()).unwrap();
+    for name in ["so101_follower/arm", "so100_follower/arm"] {
+        let (status, _) = send(&app, "POST", "/api/profiles/robots", Some(json!({"name": name, "profile": profile}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    assert!(tmp.path().join("robots/so101_follower/arm.json").exists());
+
+    let (_, v) = send(&app, "GET", "/api/profiles?kind=robots", None).await;
+    assert_eq!(v["items"], json!(["so100_follower/arm", "so101_follower/arm"]));
+    assert_eq!(v["duplicates"][0]["id"], "arm");
+
+    let (status, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(v["details"]["candidates"].as_array().unwrap().len(), 2);
+
+    let (status, _) = send(&app, "PATCH", "/api/profiles/robots/so101_follower%2Farm", Some(json!({"gripper": {"range_max": 3500}}))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, v) = send(&app, "GET", "/api/profiles/robots/so101_follower%2Farm", None).await;
+    assert_eq!(v["gripper"]["range_max"], 3500);
+    // writes are checked against the robot type
+    let (status, v) = send(&app, "PUT", "/api/profiles/robots/so101_follower%2Farm", Some(json!({"gripper": v["gripper"]}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(v["message"].as_str().unwrap().contains("missing joint `shoulder_pan`"));
+    let (status, _) = send(&app, "DELETE", "/api/profiles/robots/so100_follower%2Farm", None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // with the duplicate gone the bare id is unambiguous again
+    let (status, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["gripper"]["range_max"], 3500);
+}
+
+#[tokio::test]
+async fn api_robot_type_registry() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let (status, v) = send(&app, "GET", "/api/robot-types", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = v["items"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert!(names.contains(&"so101_follower") && names.contains(&"koch_leader") && names.contains(&"lekiwi"));
+
+    let (_, v) = send(&app, "GET", "/api/robot-types/koch_follower", None).await;
+    assert_eq!((v["kind"].as_str(), v["protocol"].as_str(), v["resolution"].as_u64()), (Some("robots"), Some("dynamixel"), Some(4096)));
+    assert_eq!(v["joints"][0], json!({"name": "shoulder_pan", "id": 1, "motor": "xl430-w250"}));
+    let (status, _) = send(&app, "GET", "/api/robot-types/r2d2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_reports_every_rule_violation() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let body = json!({
+        "j1": {"id": 1, "drive_mode": 3, "homing_offset": 0, "range_min": 100, "range_max": 5000},
+        "j2": {"id": 1, "drive_mode": 0, "homing_offset": 2100, "range_min": 100, "range_max": 200}
+    });
+    let (status, v) = send(&app, "PUT", "/api/profiles/robots/arm", Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let issues = v["details"]["issues"].as_array().unwrap();
+    let rules: Vec<(&str, &str)> = issues.iter().map(|i| (i["rule"].as_str().unwrap(), i["severity"].as_str().unwrap())).collect();
+    assert_eq!(
+        rules,
+        [
+            ("position_range", "error"),
+            ("drive_mode", "error"),
+            ("narrow_range", "warning"),
+            ("homing_offset", "error"),
+            ("unique_id", "error"),
+            ("unique_id", "error"),
+        ]
+    );
+    assert_eq!(issues[0]["joint"], "j1");
+    assert_eq!(issues[0]["field"], "range_max");
+
+    // warnings alone do not block a write
+    let body = json!({"j1": {"id": 1, "drive_mode": 0, "homing_offset": 0, "range_min": 100, "range_max": 200}});
+    let (status, _) = send(&app, "PUT", "/api/profiles/robots/arm", Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn api_validate_and_lint() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let mut profile = serde_json::to_value(registry::lookup("so101_follower").unwrap().template()).unwrap();
+    profile["gripper"]["range_max"] = json!(200);
+
+    let (status, v) = send(&app, "POST", "/api/validate", Some(json!({"profile": profile, "robot_type": "so101_follower"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((v["valid"].as_bool(), v["errors"].as_u64(), v["warnings"].as_u64()), (Some(true), Some(0), Some(1)));
+    profile["elbow_flex"]["drive_mode"] = json!(2);
+    let (_, v) = send(&app, "POST", "/api/validate", Some(json!({"profile": profile}))).await;
+    assert_eq!((v["valid"].as_bool(), v["issues"][0]["rule"].as_str()), (Some(false), Some("drive_mode")));
+    let (status, _) = send(&app, "POST", "/api/validate", Some(json!({"profile": profile, "robot_type": "r2d2"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // nothing was saved
+    let (_, v) = send(&app, "GET", "/api/profiles?kind=robots", None).await;
+    assert_eq!(v["items"], json!([]));
+
+    std::fs::write(tmp.path().join("robots/broken.json"), "not json").unwrap();
+    std::fs::write(tmp.path().join("teleoperators/leader.json"), serde_json::to_vec(&profile).unwrap()).unwrap();
+    let (status, v) = send(&app, "GET", "/api/lint", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((v["errors"].as_u64(), v["warnings"].as_u64()), (Some(2), Some(1)));
+    assert_eq!(v["items"][0]["path"], "robots/broken.json");
+    assert_eq!(v["items"][0]["issues"][0]["rule"], "parse");
+    let (_, v) = send(&app, "GET", "/api/lint?kind=teleoperators", None).await;
+    assert_eq!(v["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn api_keeps_unknown_fields() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let mut profile = serde_json::to_value(registry::lookup("so101_follower").unwrap().template()).unwrap();
+    profile["gripper"]["torque_limit"] = json!(500);
+    profile["metadata"] = json!({"tool": "lerobot", "version": "0.3"});
+    // any other object is taken for a broken joint, not kept as an unknown field
+    let mut broken = profile.clone();
+    broken["calibrated_by"] = json!({"drive_mode": 0});
+    let (status, v) = send(&app, "PUT", "/api/profiles/robots/so101_follower%2Farm", Some(broken)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(v["details"]["issues"].as_array().unwrap().iter().any(|i| i["rule"] == "malformed_joint" && i["joint"] == "calibrated_by"));
+    let (status, _) = send(&app, "PUT", "/api/profiles/robots/so101_follower%2Farm", Some(profile)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = send(&app, "PATCH", "/api/profiles/robots/arm", Some(json!({"gripper": {"range_max": 3500}}))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!((v["gripper"]["range_max"].as_i64(), v["gripper"]["torque_limit"].as_i64()), (Some(3500), Some(500)));
+    assert_eq!(v["metadata"]["tool"], "lerobot");
+    let text = std::fs::read_to_string(tmp.path().join("robots/so101_follower/arm.json")).unwrap();
+    assert!(text.contains("\"torque_limit\": 500") && text.contains("\"metadata\""));
+
+    let (_, v) = send(&app, "POST", "/api/validate", Some(json!({"profile": v, "robot_type": "so101_follower"}))).await;
+    assert_eq!((v["valid"].as_bool(), v["warnings"].as_u64()), (Some(true), Some(2)));
+    let rules: Vec<&str> = v["issues"].as_array().unwrap().iter().map(|i| i["rule"].as_str().unwrap()).collect();
+    assert_eq!(rules, ["unknown_field", "unknown_field"]);
+}
+
+#[tokio::test]
+async fn api_imports_and_exports_legacy_calibration() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let legacy = json!({
+        "homing_offset": [0, 0, 0, 0, 0, 3000],
+        "drive_mode": [0, 1, 0, 0, 0, 0],
+        "start_pos": [100, 100, 100, 100, 100, 3200],
+        "end_pos": [3000, 3000, 3000, 3000, 3000, 2000],
+        "calib_mode": ["DEGREE", "DEGREE", "DEGREE", "DEGREE", "DEGREE", "LINEAR"],
+        "motor_names": ["shoulder_pan", "shoulder_lift", "elbow_flex", "wrist_flex", "wrist_roll", "gripper"]
+    });
+    // a legacy file PUT as is is rejected with a pointer to the importer
+    let (status, v) = send(&app, "PUT", "/api/profiles/robots/arm", Some(legacy.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(v["details"]["issues"][0]["rule"], "legacy_layout");
+
+    let (status, v) = send(&app, "POST", "/api/profiles/robots/arm/import?robot_type=so101_follower&dry_run=true", Some(legacy.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((v["layout"].as_str(), v["name"].as_str()), (Some("legacy"), Some("so101_follower/arm")));
+    assert_eq!(v["profile"]["gripper"], json!({"id": 6, "drive_mode": 0, "homing_offset": 2047, "range_min": 2000, "range_max": 3200}));
+    let gripper: Vec<&str> = v["losses"].as_array().unwrap().iter().filter(|l| l["joint"] == "gripper").map(|l| l["field"].as_str().unwrap()).collect();
+    assert_eq!(gripper, ["homing_offset", "range"]);
+    let (_, v) = send(&app, "GET", "/api/profiles?kind=robots", None).await;
+    assert_eq!(v["items"], json!([]));
+
+    let (status, v) = send(&app, "POST", "/api/profiles/robots/arm/import?robot_type=so101_follower", Some(legacy.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(v["name"], "so101_follower/arm");
+    let (_, h) = send(&app, "GET", "/api/history/robots/arm", None).await;
+    assert!(h["items"][0]["summary"].as_str().unwrap().starts_with("imported from legacy layout"));
+    let (status, _) = send(&app, "POST", "/api/profiles/robots/so101_follower%2Farm/import", Some(legacy.clone())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, v) = send(&app, "POST", "/api/profiles/robots/x/import", Some(json!({"motor_names": ["a"], "homing_offset": []}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(v["message"], "invalid calibration file");
+
+    let (status, v) = send(&app, "GET", "/api/profiles/robots/arm/legacy", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(v["legacy"]["motor_names"], legacy["motor_names"]);
+    assert_eq!(v["legacy"]["calib_mode"], legacy["calib_mode"]);
+    assert_eq!((v["legacy"]["start_pos"][5].as_i64(), v["legacy"]["end_pos"][5].as_i64()), (Some(2000), Some(3200)));
+    assert_eq!(v["losses"][0]["field"], "id");
+}
+
+async fn send_bytes(app: &Router, method: &str, uri: &str, body: Vec<u8>) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+    let req = Request::builder().method(method).uri(uri).body(Body::from(body)).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let (status, headers) = (res.status(), res.headers().clone());
+    let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, headers, bytes.to_vec())
+}
+
+#[tokio::test]
+async fn api_archive_export_and_import() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    for (kind, name, ty) in [("robots", "so101_follower/arm", "so101_follower"), ("teleoperators", "so101_leader/lead", "so101_leader")] {
+        let profile = serde_json::to_value(registry::lookup(ty).unwrap().template()).unwrap();
+        send(&app, "POST", &format!("/api/profiles/{}", kind), Some(json!({"name": name, "profile": profile}))).await;
+    }
+    let (status, headers, zip) = send_bytes(&app, "GET", "/api/archive?format=zip", vec![]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/zip");
+    assert!(headers["content-disposition"].to_str().unwrap().contains("calibration.zip"));
+    let (_, _, robots_only) = send_bytes(&app, "GET", "/api/archive?kind=robots", vec![]).await;
+
+    let other = tempfile::tempdir().unwrap();
+    let target = build_app(&other);
+    let (status, _, plan) = send_bytes(&target, "POST", "/api/archive?dry_run=true", robots_only).await;
+    assert_eq!(status, StatusCode::OK);
+    let plan: serde_json::Value = serde_json::from_slice(&plan).unwrap();
+    assert_eq!(plan["applied"], false);
+    assert_eq!(plan["items"], json!([{"kind": "robots", "name": "so101_follower/arm", "action": "create"}]));
+    let (_, v) = send(&target, "GET", "/api/profiles?kind=robots", None).await;
+    assert_eq!(v["items"], json!([]));
+
+    // an edited profile on the target is a conflict
+    let mut edited = registry::lookup("so101_follower").unwrap().template();
+    edited.0.get_mut("gripper").unwrap().range_min = 100;
+    send(&target, "POST", "/api/profiles/robots", Some(json!({"name": "so101_follower/arm", "profile": edited}))).await;
+    let (status, _, _) = send_bytes(&target, "POST", "/api/archive?conflict=fail", zip.clone()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _, plan) = send_bytes(&target, "POST", "/api/archive", zip.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let plan: serde_json::Value = serde_json::from_slice(&plan).unwrap();
+    let actions: Vec<&str> = plan["items"].as_array().unwrap().iter().map(|i| i["action"].as_str().unwrap()).collect();
+    assert_eq!((plan["applied"].as_bool(), actions), (Some(true), vec!["skip", "create"]));
+    let (_, v) = send(&target, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(v["gripper"]["range_min"], 100);
+    send_bytes(&target, "POST", "/api/archive?conflict=overwrite", zip).await;
+    let (_, v) = send(&target, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!(v["gripper"]["range_min"], 0);
+
+    let (status, _, _) = send_bytes(&target, "POST", "/api/archive", b"garbage".to_vec()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+async fn send_text(app: &Router, method: &str, uri: &str, content_type: &str, body: &str) -> (StatusCode, serde_json::Value) {
+    let req = Request::builder().method(method).uri(uri).header("content-type", content_type).body(Body::from(body.to_string())).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+#[tokio::test]
+async fn api_profiles_as_csv_and_yaml() {
+    let tmp = tempfile::tempdir().unwrap();
+    let app = build_app(&tmp);
+    let mut profile = serde_json::to_value(registry::lookup("so101_follower").unwrap().template()).unwrap();
+    profile["calibrated_by"] = json!("lab-2");
+    send(&app, "PUT", "/api/profiles/robots/so101_follower%2Farm", Some(profile)).await;
+
+    let req = Request::builder().uri("/api/profiles/robots/arm").header("accept", "text/csv").body(Body::empty()).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.headers()["content-type"], "text/csv; charset=utf-8");
+    assert!(res.headers().contains_key("etag"));
+    assert_eq!(res.headers()["content-disposition"], "attachment; filename=\"arm.csv\"");
+    let csv = String::from_utf8(body::to_bytes(res.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+    assert_eq!(csv.lines().count(), 7);
+    let (_, _, yaml) = send_bytes(&app, "GET", "/api/profiles/robots/arm?format=yaml", vec![]).await;
+    let yaml = String::from_utf8(yaml).unwrap();
+    assert!(yaml.contains("gripper:\n  id: 6\n") && yaml.contains("calibrated_by: lab-2"));
+
+    // an edited sheet comes back in; top-level fields stay as stored
+    let edited = csv.replace("gripper,6,0,0,0,4095", "gripper,6,0,12,1000,3000");
+    let (status, _) = send_text(&app, "PUT", "/api/profiles/robots/arm", "text/csv", &edited).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, v) = send(&app, "GET", "/api/profiles/robots/arm", None).await;
+    assert_eq!((v["gripper"]["homing_offset"].as_i64(), v["calibrated_by"].as_str()), (Some(12), Some("lab-2")));
+
+    // rule violations and parse errors point at the cell
+    let bad = csv.replace("elbow_flex,3,0,0,0,4095", "elbow_flex,3,2,0,0,4095");
+    let (status, v) = send_text(&app, "PUT", "/api/profiles/robots/arm", "text/csv", &bad).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let issue = &v["details"]["issues"][0];
+    assert_eq!((issue["rule"].as_str(), issue["row"].as_u64(), issue["column"].as_u64()), (Some("drive_mode"), Some(4), Some(3)));
+    let (status, v) = send_text(&app, "PUT", "/api/profiles/robots/arm", "text/csv", &csv.replace(",4095\ngripper", ",x\ngripper")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!((v["message"].as_str(), v["details"]["row"].as_u64(), v["details"]["field"].as_str()), (Some("invalid csv"), Some(6), Some("range_max")));
+
+    let (status, v) = send_text(&app, "POST", "/api/profiles/teleoperators?name=so101_leader/lead", "application/yaml", &yaml.replace("follower", "leader")).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", v);
+    assert_eq!(v["name"], "so101_leader/lead");
+    let yaml_bad = yaml.replace("gripper:\n  id: 6\n  drive_mode: 0\n  homing_offset: 0", "gripper:\n  id: 6\n  drive_mode: 0\n  homing_offset: 9999");
+    let (status, v) = send_text(&app, "POST", "/api/profiles/robots?name=so101_follower/other", "application/yaml", &yaml_bad).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let issue = &v["details"]["issues"][0];
+    assert_eq!((issue["field"].as_str(), issue["row"].as_u64(), issue["column"].as_u64()), (Some("homing_offset"), Some(34), Some(3)));
+    let (status, _) = send_text(&app, "POST", "/api/profiles/robots", "text/csv", &csv).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}